@@ -0,0 +1,218 @@
+//! Derive macros for `nio-client`. Use them through the `derive` feature of
+//! `nio-client` (`nio_client::axum::WebResource`), not this crate directly:
+//! the generated code refers to `::nio_client` paths.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr};
+
+/// HTTP methods a `#[nio(..)]` container attribute may map to a relation, in
+/// the order the generated `rel` tests them.
+const METHODS: [&str; 7] = ["get", "head", "post", "put", "patch", "delete", "options"];
+
+/// Derives `nio_client::axum::WebResource` for a struct with named fields.
+///
+/// ```ignore
+/// #[derive(WebResource)]
+/// #[nio(namespace = "article", get = "viewer", put = "editor")]
+/// struct Article {
+///     #[nio(object)]
+///     id: String,
+/// }
+/// ```
+///
+/// Container attributes: `namespace` (required) and one relation per HTTP
+/// method (`get`, `head`, `post`, `put`, `patch`, `delete`, `options`).
+/// `head` falls back to `get`; any other unmapped method yields `None`, which
+/// the extractors reject as method-not-allowed.
+///
+/// Every field is parsed with `FromStr` from the path parameter of the same
+/// name. Field attributes: `object` marks the one field whose `Display` value
+/// is the checked object, `param = "name"` reads a differently named path
+/// parameter, and `default` fills the field with `Default::default()`
+/// instead. The rejection type is `nio_client::axum::PathParamRejection`.
+#[proc_macro_derive(WebResource, attributes(nio))]
+pub fn derive_web_resource(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+enum Source {
+    Param(String),
+    Default,
+}
+
+struct Field {
+    ident: Ident,
+    source: Source,
+    object: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut namespace: Option<LitStr> = None;
+    let mut rels: Vec<(&'static str, LitStr)> = Vec::new();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("nio")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("namespace") {
+                namespace = Some(meta.value()?.parse()?);
+                return Ok(());
+            }
+            for method in METHODS {
+                if meta.path.is_ident(method) {
+                    let rel: LitStr = meta.value()?.parse()?;
+                    rels.retain(|(m, _)| *m != method);
+                    rels.push((method, rel));
+                    return Ok(());
+                }
+            }
+            Err(meta.error("unknown nio attribute; expected `namespace` or an HTTP method"))
+        })?;
+    }
+    let namespace = namespace.ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            "missing #[nio(namespace = \"...\")] on WebResource derive",
+        )
+    })?;
+
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(named) => &named.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "WebResource derive requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "WebResource derive requires a struct with named fields",
+            ))
+        }
+    };
+
+    let mut parsed = Vec::with_capacity(fields.len());
+    for field in fields {
+        let ident = field.ident.clone().expect("named field");
+        let mut source = Source::Param(ident.to_string());
+        let mut object = false;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("nio")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("object") {
+                    object = true;
+                    Ok(())
+                } else if meta.path.is_ident("param") {
+                    let param: LitStr = meta.value()?.parse()?;
+                    source = Source::Param(param.value());
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    source = Source::Default;
+                    Ok(())
+                } else {
+                    Err(meta.error(
+                        "unknown nio field attribute; expected `object`, `param` or `default`",
+                    ))
+                }
+            })?;
+        }
+        parsed.push(Field {
+            ident,
+            source,
+            object,
+        });
+    }
+
+    let mut objects = parsed.iter().filter(|f| f.object);
+    let object = match (objects.next(), objects.next()) {
+        (Some(f), None) => &f.ident,
+        (None, _) => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "WebResource derive needs exactly one #[nio(object)] field",
+            ))
+        }
+        (Some(_), Some(second)) => {
+            return Err(syn::Error::new_spanned(
+                &second.ident,
+                "only one field may be marked #[nio(object)]",
+            ))
+        }
+    };
+
+    let rel_arms = METHODS.iter().filter_map(|method| {
+        let rel = rels
+            .iter()
+            .find(|(m, _)| m == method)
+            .or_else(|| match *method {
+                "head" => rels.iter().find(|(m, _)| *m == "get"),
+                _ => None,
+            })
+            .map(|(_, rel)| rel)?;
+        let konst = Ident::new(&method.to_uppercase(), Span::call_site());
+        Some(quote! {
+            if *method == ::nio_client::axum::__private::Method::#konst {
+                return ::core::option::Option::Some(::nio_client::Rel::from(#rel));
+            }
+        })
+    });
+
+    let needs_params = parsed.iter().any(|f| matches!(f.source, Source::Param(_)));
+    let fetch_params = needs_params.then(|| {
+        quote! {
+            let params = ::nio_client::axum::__private::path_params(parts, state).await?;
+        }
+    });
+    let inits = parsed.iter().map(|f| {
+        let ident = &f.ident;
+        match &f.source {
+            Source::Param(param) => quote! {
+                #ident: ::nio_client::axum::__private::path_param(&params, #param)?
+            },
+            Source::Default => quote! {
+                #ident: ::core::default::Default::default()
+            },
+        }
+    });
+    let unused_state = (!needs_params).then(|| quote! { let _ = (parts, state); });
+
+    Ok(quote! {
+        impl #impl_generics ::nio_client::axum::WebResource for #name #ty_generics #where_clause {
+            type Rejection = ::nio_client::axum::PathParamRejection;
+
+            fn namespace(&self) -> ::nio_client::Namespace {
+                ::nio_client::Namespace(::std::string::String::from(#namespace))
+            }
+
+            fn rel(
+                &self,
+                method: &::nio_client::axum::__private::Method,
+            ) -> ::core::option::Option<::nio_client::Rel> {
+                #(#rel_arms)*
+                ::core::option::Option::None
+            }
+
+            async fn parse<S: ::core::marker::Send + ::core::marker::Sync>(
+                parts: &mut ::nio_client::axum::__private::Parts,
+                state: &S,
+            ) -> ::core::result::Result<Self, Self::Rejection> {
+                #unused_state
+                #fetch_params
+                ::core::result::Result::Ok(#name {
+                    #(#inits,)*
+                })
+            }
+
+            fn object(&self) -> ::nio_client::Obj {
+                ::nio_client::Obj(::std::string::ToString::to_string(&self.#object))
+            }
+        }
+    })
+}
@@ -7,8 +7,9 @@
 //!   session redirects to sign-in.
 //! * `GET|POST /api/articles/{id}` — a JSON **API** guarded by an
 //!   `Authorization: Bearer` token ([`WithPrincipal`] with [`BearerTokenAuth`]).
-//! * `GET /public/articles/{id}` — a page that is public but authorizes a
-//!   caller who *does* present a session ([`WithOptPrincipal`]).
+//! * `GET /public/articles/{id}` — a page open to signed-out visitors when
+//!   the article is granted to `allUsers`, that authorizes a caller who *does*
+//!   present a session ([`WithOptPrincipal`]).
 //!
 //! Sign-in itself is ordinary app code: authenticate the user, ask the backend
 //! to issue a session, drop the token into a cookie. The interesting nio work —
@@ -198,12 +199,15 @@ async fn ui_article(auth: WithPrincipal<ArticleResource>) -> Html<String> {
     ))
 }
 
-// Public page with optional authorization. No session -> anonymous view; a
-// valid session must still pass the check (article 3 is granted to allUsers).
+// Public page with optional authorization. No session -> the check runs for
+// `allUsers` (anonymous checks are on in main.rs): article 3 is granted to
+// allUsers and renders anonymously, the others redirect to sign-in. A valid
+// session must pass the check as itself.
 async fn public_article(auth: WithOptPrincipal<ArticleResource>) -> Html<String> {
     let id = &auth.resource.id;
     let (title, body) = article(id);
     let who = match &auth.principal {
+        Some(p) if p.is_anonymous() => "anonymous (public grant)".to_string(),
         Some(p) => format!("signed in as <b>{}</b>", display_name(p.as_str())),
         None => "anonymous".to_string(),
     };
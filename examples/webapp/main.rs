@@ -42,7 +42,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let resolver = GrpcSessionResolver::new(session_channel, ResolverConfig::default());
 
     // 3. The state the extractors read. `None` prefix -> sign-in at `/signin`.
-    let auth = AuthState::new(check_client, resolver, None);
+    //    Anonymous checks make `/public` ask nio whether `allUsers` may read
+    //    the article instead of letting every signed-out visitor through.
+    let auth = AuthState::new(check_client, resolver, None).with_anonymous_checks(true);
     let state = AppState { auth, backend };
 
     // 4. Serve.
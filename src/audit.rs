@@ -0,0 +1,405 @@
+//! Structured audit log of authorization decisions.
+//!
+//! Every check answered for the application becomes a [`DecisionRecord`]
+//! handed to an [`AuditSink`] (see [`CheckClient::with_audit`]): RPCs made by
+//! the [`CheckClient`] and answers a [`RequestMemo`] served from its cache
+//! (`memo_hit`). Besides the question and its outcome a record carries the
+//! zookie the check was evaluated at and, inside an [`AuditContext`] scope,
+//! the request id, route, auth type and tenant — the axum extractors set
+//! that scope around the checks they run.
+//!
+//! [`JsonLinesSink`] appends one JSON object per line to a file, rotated by
+//! size, on a background thread.
+//!
+//! [`CheckClient`]: crate::CheckClient
+//! [`CheckClient::with_audit`]: crate::CheckClient::with_audit
+//! [`RequestMemo`]: crate::memo::RequestMemo
+
+use crate::auth::{CallError, CheckResult};
+use crate::{Namespace, Obj, Rel, Timestamp, UserId};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+tokio::task_local! {
+    static CONTEXT: AuditContext;
+}
+
+/// Receives every authorization decision. Called inline on the deciding
+/// task, so implementations must not block; hand records off instead.
+pub trait AuditSink: Send + Sync + 'static {
+    fn record(&self, record: &DecisionRecord);
+}
+
+/// How a check was decided.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecisionOutcome {
+    Granted,
+    Denied,
+    /// The user id is not a known principal.
+    UnknownUser,
+    /// No decision: the call failed.
+    Error,
+}
+
+impl DecisionOutcome {
+    pub(crate) fn of(result: &Result<CheckResult, CallError>) -> Self {
+        match result {
+            Ok(CheckResult::Ok(_)) => DecisionOutcome::Granted,
+            Ok(CheckResult::Forbidden(_)) => DecisionOutcome::Denied,
+            Ok(CheckResult::UnknownPutativeUser) => DecisionOutcome::UnknownUser,
+            Err(_) => DecisionOutcome::Error,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DecisionOutcome::Granted => "granted",
+            DecisionOutcome::Denied => "denied",
+            DecisionOutcome::UnknownUser => "unknown_user",
+            DecisionOutcome::Error => "error",
+        }
+    }
+}
+
+/// The request a decision was made for. Set it with [`AuditContext::scope`]
+/// around the checks of one request; decisions made outside any scope carry
+/// none.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditContext {
+    pub request_id: Option<String>,
+    /// The matched route (e.g. `/docs/{id}`), else the request path.
+    pub route: Option<String>,
+    /// The credential kind the caller authenticated with.
+    pub auth_type: Option<&'static str>,
+    pub tenant: Option<String>,
+}
+
+impl AuditContext {
+    /// Runs `fut` with this context attached to the decisions it makes.
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        CONTEXT.scope(self, fut).await
+    }
+
+    /// The context of the request being handled on this task, if any.
+    pub fn current() -> Option<AuditContext> {
+        CONTEXT.try_with(Clone::clone).ok()
+    }
+}
+
+/// One authorization decision with its full context.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecisionRecord {
+    pub at: DateTime<Utc>,
+    pub ns: Namespace,
+    pub obj: Obj,
+    pub rel: Rel,
+    pub user: UserId,
+    /// The zookie the check was evaluated at least as fresh as; `None` for
+    /// any current snapshot.
+    pub zookie: Option<Timestamp>,
+    pub outcome: DecisionOutcome,
+    /// The failure, for [`DecisionOutcome::Error`].
+    pub error: Option<String>,
+    /// RPC latency; zero for memo hits.
+    pub duration: Duration,
+    /// Answered from the request's memo without an RPC.
+    pub memo_hit: bool,
+    pub context: AuditContext,
+}
+
+impl DecisionRecord {
+    /// A record of `result` in the current [`AuditContext`].
+    pub(crate) fn new(
+        check: (&Namespace, &Obj, &Rel, &UserId),
+        zookie: Option<Timestamp>,
+        result: &Result<CheckResult, CallError>,
+        duration: Duration,
+        memo_hit: bool,
+    ) -> Self {
+        let (ns, obj, rel, user) = check;
+        DecisionRecord {
+            at: Utc::now(),
+            ns: ns.clone(),
+            obj: obj.clone(),
+            rel: rel.clone(),
+            user: user.clone(),
+            zookie: zookie.filter(|ts| *ts != Timestamp::empty()),
+            outcome: DecisionOutcome::of(result),
+            error: result.as_ref().err().map(ToString::to_string),
+            duration,
+            memo_hit,
+            context: AuditContext::current().unwrap_or_default(),
+        }
+    }
+
+    /// The record as one line of JSON (without the newline). Absent values
+    /// are `null`.
+    pub fn to_json(&self) -> String {
+        let ctx = &self.context;
+        let mut out = String::with_capacity(256);
+        out.push('{');
+        field(
+            &mut out,
+            "at",
+            Some(&self.at.to_rfc3339_opts(SecondsFormat::Micros, true)),
+        );
+        field(&mut out, "ns", Some(&self.ns.0));
+        field(&mut out, "obj", Some(&self.obj.0));
+        field(&mut out, "rel", Some(&self.rel.0));
+        field(&mut out, "user", Some(&self.user.0));
+        field(&mut out, "zookie", self.zookie.as_ref().map(|ts| &ts.0));
+        field(&mut out, "outcome", Some(self.outcome.as_str()));
+        field(&mut out, "error", self.error.as_deref());
+        let _ = write!(
+            out,
+            "\"duration_us\":{},\"memo_hit\":{},",
+            self.duration.as_micros(),
+            self.memo_hit
+        );
+        field(&mut out, "request_id", ctx.request_id.as_deref());
+        field(&mut out, "route", ctx.route.as_deref());
+        field(&mut out, "auth_type", ctx.auth_type);
+        field(&mut out, "tenant", ctx.tenant.as_deref());
+        out.pop(); // trailing comma
+        out.push('}');
+        out
+    }
+}
+
+/// Appends `"name":value,` with `value` as a JSON string or `null`.
+fn field<S: AsRef<str> + ?Sized>(out: &mut String, name: &str, value: Option<&S>) {
+    let _ = write!(out, "\"{name}\":");
+    match value {
+        None => out.push_str("null"),
+        Some(value) => {
+            out.push('"');
+            for c in value.as_ref().chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    c if c < ' ' => {
+                        let _ = write!(out, "\\u{:04x}", c as u32);
+                    }
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+        }
+    }
+    out.push(',');
+}
+
+/// Rotation policy of a [`JsonLinesSink`].
+#[derive(Clone, Debug)]
+pub struct JsonLinesConfig {
+    /// Rotate before a write would grow the file past this size.
+    pub max_bytes: u64,
+    /// Rotated files to keep (`<path>.1` is the newest); older ones are
+    /// deleted. Zero truncates instead of keeping any.
+    pub max_files: usize,
+}
+
+impl Default for JsonLinesConfig {
+    /// 100 MiB per file, 5 rotated files.
+    fn default() -> Self {
+        JsonLinesConfig {
+            max_bytes: 100 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// Audit sink writing [`DecisionRecord::to_json`] lines to a size-rotated
+/// file. Writes happen on a dedicated thread; dropping the sink flushes and
+/// joins it.
+pub struct JsonLinesSink {
+    tx: Option<mpsc::Sender<String>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl JsonLinesSink {
+    /// Opens (appending to) the log at `path`.
+    pub fn open(path: impl Into<PathBuf>, config: JsonLinesConfig) -> io::Result<Self> {
+        let mut file = RotatingFile::open(path.into(), config)?;
+        let (tx, rx) = mpsc::channel::<String>();
+        let writer = std::thread::Builder::new()
+            .name("nio-audit".into())
+            .spawn(move || {
+                while let Ok(line) = rx.recv() {
+                    let mut result = file.write_line(&line);
+                    // Flush once the backlog is drained.
+                    while let (Ok(()), Ok(line)) = (&result, rx.try_recv()) {
+                        result = file.write_line(&line);
+                    }
+                    if let Err(err) = result.and_then(|()| file.flush()) {
+                        log::error!("nio-client: audit log write failed: {err}");
+                    }
+                }
+            })?;
+        Ok(JsonLinesSink {
+            tx: Some(tx),
+            writer: Some(writer),
+        })
+    }
+}
+
+impl AuditSink for JsonLinesSink {
+    fn record(&self, record: &DecisionRecord) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(record.to_json());
+        }
+    }
+}
+
+impl Drop for JsonLinesSink {
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    config: JsonLinesConfig,
+    out: BufWriter<File>,
+    len: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, config: JsonLinesConfig) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let len = file.metadata()?.len();
+        Ok(RotatingFile {
+            path,
+            config,
+            out: BufWriter::new(file),
+            len,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let size = line.len() as u64 + 1;
+        if self.len > 0 && self.len + size > self.config.max_bytes {
+            self.rotate()?;
+        }
+        self.out.write_all(line.as_bytes())?;
+        self.out.write_all(b"\n")?;
+        self.len += size;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Shifts `<path>.N-1` to `<path>.N` (dropping the oldest), moves the
+    /// live file to `<path>.1` and starts a new one.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        let max = self.config.max_files;
+        if max > 0 {
+            for n in (1..max).rev() {
+                let from = rotated(&self.path, n);
+                if from.exists() {
+                    std::fs::rename(&from, rotated(&self.path, n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated(&self.path, 1))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.out = BufWriter::new(file);
+        self.len = 0;
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> DecisionRecord {
+        DecisionRecord {
+            at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            ns: Namespace("doc".into()),
+            obj: Obj("a\"b".into()),
+            rel: Rel::viewer(),
+            user: UserId("u1".into()),
+            zookie: None,
+            outcome: DecisionOutcome::Denied,
+            error: None,
+            duration: Duration::from_micros(1500),
+            memo_hit: true,
+            context: AuditContext {
+                request_id: Some("r-1".into()),
+                route: Some("/docs/{id}".into()),
+                auth_type: Some("session_cookie"),
+                tenant: None,
+            },
+        }
+    }
+
+    #[test]
+    fn renders_one_json_object() {
+        assert_eq!(
+            record().to_json(),
+            "{\"at\":\"2023-11-14T22:13:20.000000Z\",\"ns\":\"doc\",\"obj\":\"a\\\"b\",\
+             \"rel\":\"viewer\",\"user\":\"u1\",\"zookie\":null,\"outcome\":\"denied\",\
+             \"error\":null,\"duration_us\":1500,\"memo_hit\":true,\"request_id\":\"r-1\",\
+             \"route\":\"/docs/{id}\",\"auth_type\":\"session_cookie\",\"tenant\":null}"
+        );
+    }
+
+    #[test]
+    fn escapes_control_characters() {
+        let mut out = String::new();
+        field(&mut out, "e", Some("a\nb\u{1}"));
+        assert_eq!(out, "\"e\":\"a\\nb\\u0001\",");
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_max_files() {
+        let dir = std::env::temp_dir().join(format!("nio-audit-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.log");
+        let line = record().to_json();
+        let config = JsonLinesConfig {
+            max_bytes: line.len() as u64 * 2 + 2,
+            max_files: 2,
+        };
+        {
+            let sink = JsonLinesSink::open(&path, config).unwrap();
+            for _ in 0..7 {
+                sink.record(&record());
+            }
+        }
+        let lines = |p: &Path| std::fs::read_to_string(p).unwrap().lines().count();
+        assert_eq!(lines(&path), 1);
+        assert_eq!(lines(&rotated(&path, 1)), 2);
+        assert_eq!(lines(&rotated(&path, 2)), 2);
+        assert!(!rotated(&path, 3).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+use crate::credentials::CredentialError;
+use crate::error::{ErrorKind, StatusDisplay};
 use tonic::Status;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -61,9 +63,36 @@ impl CheckResult {
 }
 
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum CallError {
     #[error("unexpected response format")]
     UnexpectedResponseFormat,
-    #[error("call error: {0}")]
-    Status(#[from] Status),
+    #[error("call error: {}", StatusDisplay(.0))]
+    Status(Status),
+    /// No caller credential could be attached; no RPC was made.
+    #[error("call credentials: {0}")]
+    Credentials(CredentialError),
+}
+
+impl CallError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            CallError::UnexpectedResponseFormat => ErrorKind::ContractViolation,
+            CallError::Status(status) => ErrorKind::of(status.code()),
+            CallError::Credentials(_) => ErrorKind::Unauthenticated,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
+}
+
+impl From<Status> for CallError {
+    fn from(status: Status) -> Self {
+        match CredentialError::of(&status) {
+            Some(e) => CallError::Credentials(e),
+            None => CallError::Status(status),
+        }
+    }
 }
@@ -1,18 +1,32 @@
-use crate::auth::{CheckResult, Principal};
-use crate::session::SessionResolver;
+use crate::audit::AuditContext;
+use crate::auth::{CallError, Principal};
+use crate::guard::{Denial, Guard, GuardOutcome};
+use crate::live::{LiveAccess, LiveAccessConfig};
+use crate::memo::RequestMemo;
+use crate::session::{ResolveError, ResolvedSession, SessionResolver};
+use crate::zookie::ZookieJar;
 use crate::UserId;
-use crate::{CheckClient, Namespace, Obj, Rel};
-use axum::extract::FromRef;
-use axum::http::Method;
+use crate::{
+    CheckClient, ConnectError, ErrorKind, Namespace, Obj, ReadError, Rel, Timestamp, WriteError,
+};
+use axum::extract::rejection::PathRejection;
+use axum::extract::{FromRef, FromRequest, MatchedPath, Request, State};
+use axum::http::header::{ORIGIN, REFERER, SET_COOKIE};
+use axum::http::{HeaderMap, HeaderValue, Method, Uri};
+use axum::middleware::Next;
 use axum::response::{IntoResponse, Redirect, Response};
 use axum::{extract::FromRequestParts, http::request::Parts};
+use axum::{Form, Json};
 use headers::authorization::Bearer;
 use headers::{Authorization, Cookie, HeaderMapExt};
+use serde::de::DeserializeOwned;
+use std::convert::Infallible;
 use std::error::Error;
 use std::fmt::Debug;
 use std::future::Future;
 use std::marker::PhantomData;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 #[derive(Debug, thiserror::Error)]
 #[error("Web resource error")]
@@ -21,11 +35,65 @@ pub enum WebResourceError {
     /// (`{prefix}/signin?back={original-uri}`).
     MissingSession(String),
     Forbidden,
+    /// A composite [`Guard`] was denied; names the deciding clause(s).
+    GuardDenied(Denial),
     MethodNotAllowed,
+    /// A machine client presented no credential, or one that resolves to
+    /// nothing; answered `401` rather than a sign-in redirect.
+    Unauthorized,
+    /// A cookie-authenticated unsafe request failed the [`CsrfConfig`]
+    /// defenses; names the reason.
+    Csrf(String),
     InternalServerError(Box<dyn Error + 'static>),
+    /// check or nio-client failed in a way worth retrying (see
+    /// [`ErrorKind::is_retryable`]); answered `503`.
+    Unavailable(Box<dyn Error + 'static>),
     Parse(Box<dyn Error + 'static>),
 }
 
+impl WebResourceError {
+    /// `Unavailable` for a retryable client error, else
+    /// `InternalServerError`: a failing backend call is never the
+    /// caller's fault.
+    fn from_client(kind: ErrorKind, err: impl Error + 'static) -> Self {
+        if kind.is_retryable() {
+            WebResourceError::Unavailable(Box::new(err))
+        } else {
+            WebResourceError::InternalServerError(Box::new(err))
+        }
+    }
+}
+
+impl From<CallError> for WebResourceError {
+    fn from(err: CallError) -> Self {
+        Self::from_client(err.kind(), err)
+    }
+}
+
+impl From<ReadError> for WebResourceError {
+    fn from(err: ReadError) -> Self {
+        Self::from_client(err.kind(), err)
+    }
+}
+
+impl From<WriteError> for WebResourceError {
+    fn from(err: WriteError) -> Self {
+        Self::from_client(err.kind(), err)
+    }
+}
+
+impl From<ConnectError> for WebResourceError {
+    fn from(err: ConnectError) -> Self {
+        Self::from_client(err.kind(), err)
+    }
+}
+
+impl From<ResolveError> for WebResourceError {
+    fn from(err: ResolveError) -> Self {
+        Self::from_client(err.kind(), err)
+    }
+}
+
 impl IntoResponse for WebResourceError {
     fn into_response(self) -> Response {
         // Each variant maps to a distinct status so ops/clients can
@@ -33,14 +101,27 @@ impl IntoResponse for WebResourceError {
         // (NIO-015). MissingSession stays a browser redirect.
         match self {
             WebResourceError::MissingSession(loc) => Redirect::to(loc.as_str()).into_response(),
+            WebResourceError::Unauthorized => axum::http::StatusCode::UNAUTHORIZED.into_response(),
             WebResourceError::Forbidden => axum::http::StatusCode::FORBIDDEN.into_response(),
+            WebResourceError::GuardDenied(denial) => {
+                log::debug!("web resource guard {denial}");
+                axum::http::StatusCode::FORBIDDEN.into_response()
+            }
             WebResourceError::MethodNotAllowed => {
                 axum::http::StatusCode::METHOD_NOT_ALLOWED.into_response()
             }
+            WebResourceError::Csrf(reason) => {
+                log::debug!("web resource csrf rejection: {reason}");
+                axum::http::StatusCode::FORBIDDEN.into_response()
+            }
             WebResourceError::InternalServerError(err) => {
                 log::error!("web resource internal error: {err}");
                 axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response()
             }
+            WebResourceError::Unavailable(err) => {
+                log::warn!("web resource backend unavailable: {err}");
+                axum::http::StatusCode::SERVICE_UNAVAILABLE.into_response()
+            }
             WebResourceError::Parse(_) => axum::http::StatusCode::BAD_REQUEST.into_response(),
         }
     }
@@ -48,11 +129,110 @@ impl IntoResponse for WebResourceError {
 
 pub struct SessionCookieAuth;
 pub struct BearerTokenAuth;
+/// Service-account API keys in the `X-Api-Key` header, resolved through
+/// [`AuthState::with_api_keys`]. Failures are `401`, never a redirect.
+pub struct ApiKeyAuth;
+
+/// Request header carrying a service-account API key.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Where an extractor finds the caller's raw session token: the `session`
+/// cookie ([`SessionCookieAuth`]), an `Authorization: Bearer` header
+/// ([`BearerTokenAuth`]) or an `X-Api-Key` header ([`ApiKeyAuth`]).
+pub trait AuthType: Send + Sync + 'static {
+    /// The raw token, or `None` when the request carries none.
+    fn token(parts: &Parts) -> Option<String>;
+
+    /// The resolver turning this type's tokens into principals; the
+    /// session resolver by default.
+    fn resolver(auth_state: &AuthState) -> Result<&Arc<dyn SessionResolver>, WebResourceError> {
+        Ok(&auth_state.resolver)
+    }
+
+    /// The rejection for a request without a usable token; the sign-in
+    /// redirect by default.
+    fn unauthenticated(auth_state: &AuthState, parts: &Parts) -> WebResourceError {
+        WebResourceError::MissingSession(auth_state.signin_location(parts))
+    }
+
+    /// The credential kind, as recorded in [`AuditContext::auth_type`].
+    fn name() -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// True when browsers attach the credential on their own (cookies), so
+    /// unsafe requests carrying it are subject to [`CsrfConfig`].
+    fn is_ambient() -> bool {
+        false
+    }
+}
+
+impl AuthType for SessionCookieAuth {
+    fn token(parts: &Parts) -> Option<String> {
+        parts
+            .headers
+            .typed_get::<Cookie>()
+            .and_then(|c| c.get("session").map(String::from))
+    }
+
+    fn name() -> &'static str {
+        "session_cookie"
+    }
+
+    fn is_ambient() -> bool {
+        true
+    }
+}
+
+impl AuthType for BearerTokenAuth {
+    // TODO impl proper oauth2 response
+    fn token(parts: &Parts) -> Option<String> {
+        match parts.headers.typed_try_get::<Authorization<Bearer>>() {
+            Ok(Some(bearer)) => Some(bearer.token().to_string()),
+            Ok(None) | Err(_) => None,
+        }
+    }
+
+    fn name() -> &'static str {
+        "bearer_token"
+    }
+}
+
+impl AuthType for ApiKeyAuth {
+    fn token(parts: &Parts) -> Option<String> {
+        parts
+            .headers
+            .get(API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(String::from)
+    }
+
+    fn name() -> &'static str {
+        "api_key"
+    }
+
+    fn resolver(auth_state: &AuthState) -> Result<&Arc<dyn SessionResolver>, WebResourceError> {
+        auth_state
+            .api_keys
+            .as_ref()
+            .ok_or_else(|| WebResourceError::InternalServerError(Box::new(ApiKeysNotConfigured)))
+    }
+
+    fn unauthenticated(_auth_state: &AuthState, _parts: &Parts) -> WebResourceError {
+        WebResourceError::Unauthorized
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("ApiKeyAuth used without AuthState::with_api_keys")]
+struct ApiKeysNotConfigured;
 
 /// Outcome of turning a raw session token into the subject passed to `check`.
 enum Subject {
-    /// Resolved — send this principal `UserId` to `check`.
-    Principal(UserId),
+    /// Resolved — send this session's principal `UserId` to `check`.
+    Principal(ResolvedSession),
     /// Token unknown / expired / revoked. Zero `check` RPCs.
     NotFound,
     /// Backend/transport fault while resolving.
@@ -66,15 +246,502 @@ enum Subject {
 async fn resolve_subject(resolver: &Arc<dyn SessionResolver>, token: &str) -> Subject {
     let hash = crate::session::token_hash(token);
     match resolver.resolve(&hash).await {
-        Ok(Some(session)) => Subject::Principal(UserId(session.principal)),
+        Ok(Some(session)) => Subject::Principal(session),
         Ok(None) => Subject::NotFound,
         Err(err) => {
             log::error!("nio-client: session resolve failed: {err}");
-            Subject::Error(WebResourceError::InternalServerError(Box::new(err)))
+            Subject::Error(err.into())
         }
     }
 }
 
+/// The caller's raw token per `A`; a request without one is rejected with
+/// [`AuthType::unauthenticated`]. Ambient credentials must pass the
+/// configured CSRF defenses.
+fn require_token<A: AuthType>(
+    auth_state: &AuthState,
+    parts: &Parts,
+) -> Result<String, WebResourceError> {
+    let token = A::token(parts).ok_or_else(|| A::unauthenticated(auth_state, parts))?;
+    verify_csrf::<A>(auth_state, parts, &token)?;
+    Ok(token)
+}
+
+fn verify_csrf<A: AuthType>(
+    auth_state: &AuthState,
+    parts: &Parts,
+    token: &str,
+) -> Result<(), WebResourceError> {
+    match &auth_state.csrf {
+        Some(csrf) if A::is_ambient() => csrf.verify(parts, token),
+        _ => Ok(()),
+    }
+}
+
+/// Request header carrying the double-submit CSRF token.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Cross-site request forgery defenses for cookie-authenticated requests
+/// with unsafe methods (see [`AuthState::with_csrf`]). Safe methods (GET,
+/// HEAD, OPTIONS, TRACE) and header-borne credentials are never checked.
+#[derive(Clone, Debug, Default)]
+pub struct CsrfConfig {
+    /// Origins (`scheme://host[:port]`) allowed to send unsafe requests,
+    /// matched against `Origin`, else the origin of `Referer`. A request
+    /// carrying neither is rejected. Empty disables the origin check.
+    pub allowed_origins: Vec<String>,
+    /// Require an `X-CSRF-Token` header equal to [`csrf_token`] of the
+    /// session token.
+    pub double_submit: bool,
+}
+
+/// The double-submit token for a session, derived from the session token's
+/// hash. Render it into pages (e.g. a `<meta>` tag) for scripts to send back
+/// in `X-CSRF-Token`; a cross-site page cannot compute it without the
+/// cookie.
+pub fn csrf_token(session_token: &str) -> String {
+    let hash = crate::session::token_hash(session_token);
+    crate::session::token_hash(&format!("nio-csrf:{hash}"))
+}
+
+impl CsrfConfig {
+    fn verify(&self, parts: &Parts, token: &str) -> Result<(), WebResourceError> {
+        if matches!(
+            parts.method,
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        ) {
+            return Ok(());
+        }
+        if !self.allowed_origins.is_empty() {
+            let origin = request_origin(&parts.headers)
+                .ok_or_else(|| WebResourceError::Csrf("no origin".into()))?;
+            let allowed = self
+                .allowed_origins
+                .iter()
+                .any(|o| o.trim_end_matches('/').eq_ignore_ascii_case(&origin));
+            if !allowed {
+                return Err(WebResourceError::Csrf(format!(
+                    "origin {origin} not allowed"
+                )));
+            }
+        }
+        if self.double_submit {
+            let sent = parts
+                .headers
+                .get(CSRF_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default();
+            if !constant_time_eq(sent.as_bytes(), csrf_token(token).as_bytes()) {
+                return Err(WebResourceError::Csrf("token mismatch".into()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `Origin`, else the `scheme://authority` of `Referer`. The opaque origin
+/// `null` counts as none.
+fn request_origin(headers: &HeaderMap) -> Option<String> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    if let Some(origin) = header(ORIGIN).filter(|o| *o != "null") {
+        return Some(origin.trim_end_matches('/').to_string());
+    }
+    let referer: Uri = header(REFERER)?.parse().ok()?;
+    Some(format!("{}://{}", referer.scheme()?, referer.authority()?))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Resolves `token` through `A`'s resolver to the subject to check; an
+/// unknown token is rejected with [`AuthType::unauthenticated`].
+async fn require_subject<A: AuthType>(
+    auth_state: &AuthState,
+    parts: &Parts,
+    token: &str,
+) -> Result<UserId, WebResourceError> {
+    require_session::<A>(auth_state, parts, token)
+        .await
+        .map(|session| UserId(session.principal))
+}
+
+/// [`require_subject`], keeping the whole resolved session.
+async fn require_session<A: AuthType>(
+    auth_state: &AuthState,
+    parts: &Parts,
+    token: &str,
+) -> Result<ResolvedSession, WebResourceError> {
+    let resolver = A::resolver(auth_state)?;
+    match resolve_subject(resolver, token).await {
+        Subject::Principal(session) => Ok(session),
+        Subject::NotFound => Err(A::unauthenticated(auth_state, parts)),
+        Subject::Error(err) => Err(err),
+    }
+}
+
+/// Evaluates the guard for a resolved subject and maps its outcome onto the
+/// extractor rejections: a denied single check is [`WebResourceError::Forbidden`],
+/// a denied composite guard [`WebResourceError::GuardDenied`].
+///
+/// Checks go through the request's [`RequestMemo`], if any (see
+/// [`request_memo`]); `at` bounds them to a content version's zookie.
+async fn authorize(
+    auth_state: &AuthState,
+    memo: Option<&RequestMemo>,
+    guard: Guard,
+    user: UserId,
+    at: Option<Timestamp>,
+) -> Result<Principal, WebResourceError> {
+    let client = &auth_state.check_client;
+    // Memo keys carry no zookie, so checks bound to a content version bypass it.
+    let outcome = match (memo, at) {
+        (_, Some(ts)) => {
+            let ts = crate::zookie::at_least(ts);
+            client.check_guard(&guard, user, Some(ts)).await
+        }
+        (Some(memo), None) => memo.check_guard(&guard, user).await,
+        (None, None) => client.check_guard(&guard, user, None).await,
+    };
+    match outcome {
+        Err(err) => {
+            log::error!("nio-client: check returned error: {err}");
+            Err(err.into())
+        }
+        Ok(GuardOutcome::Granted(principal)) => Ok(principal),
+        // TODO consider passing along principal even when not authorized
+        Ok(GuardOutcome::Denied(_)) if matches!(guard, Guard::Check(_)) => {
+            Err(WebResourceError::Forbidden)
+        }
+        Ok(GuardOutcome::Denied(denial)) => Err(WebResourceError::GuardDenied(denial)),
+    }
+}
+
+/// Request header naming the principal to act as (see
+/// [`AuthState::with_impersonation`]).
+pub const ACT_AS_HEADER: &str = "x-nio-act-as";
+/// Cookie naming the principal to act as; the header wins.
+pub const ACT_AS_COOKIE: &str = "act_as";
+
+/// One decision made while a caller acted as another principal. Also sent
+/// for a refused impersonation attempt, with the admin gate as `guard`.
+#[derive(Clone, Debug)]
+pub struct ImpersonationEvent {
+    /// The caller whose session authenticated the request.
+    pub actor: UserId,
+    /// The principal the check ran as.
+    pub subject: UserId,
+    pub guard: Guard,
+    pub granted: bool,
+    pub method: Method,
+    pub uri: String,
+}
+
+pub type ImpersonationAuditFn = Arc<dyn Fn(&ImpersonationEvent) + Send + Sync>;
+
+/// A caller acting as `subject`, having passed the admin gate.
+struct Impersonation {
+    actor: Principal,
+    subject: UserId,
+    method: Method,
+    uri: String,
+}
+
+/// The admin gate a caller must pass to act as another principal.
+fn impersonation_gate() -> Guard {
+    Guard::check(Namespace::iam(), Obj::root(), Rel::iam_update())
+}
+
+/// The audit hook and the act-as target, when impersonation is enabled and
+/// the request names a principal other than the caller.
+fn act_as<'a>(
+    auth_state: &'a AuthState,
+    parts: &Parts,
+    caller: &UserId,
+) -> Option<(&'a ImpersonationAuditFn, UserId)> {
+    let audit = auth_state.impersonation.as_ref()?;
+    let header = parts
+        .headers
+        .get(ACT_AS_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string());
+    let target = header
+        .or_else(|| {
+            parts
+                .headers
+                .typed_get::<Cookie>()
+                .and_then(|c| c.get(ACT_AS_COOKIE).map(String::from))
+        })
+        .filter(|t| !t.is_empty() && *t != caller.0)?;
+    Some((audit, UserId(target)))
+}
+
+/// The act-as target when impersonation is enabled and requested; the
+/// caller must then pass `iam:root#iam.update` (else
+/// [`WebResourceError::Forbidden`]).
+async fn impersonation(
+    auth_state: &AuthState,
+    parts: &Parts,
+    caller: &UserId,
+) -> Result<Option<Impersonation>, WebResourceError> {
+    let Some((audit, subject)) = act_as(auth_state, parts, caller) else {
+        return Ok(None);
+    };
+    let gate = impersonation_gate();
+    let method = parts.method.clone();
+    let uri = parts.uri.to_string();
+    let result = authorize(auth_state, None, gate.clone(), caller.clone(), None).await;
+    let actor = match result {
+        Ok(actor) => actor,
+        Err(err) => {
+            if matches!(err, WebResourceError::Forbidden) {
+                audit(&ImpersonationEvent {
+                    actor: caller.clone(),
+                    subject,
+                    guard: gate,
+                    granted: false,
+                    method,
+                    uri,
+                });
+            }
+            return Err(err);
+        }
+    };
+    Ok(Some(Impersonation {
+        actor,
+        subject,
+        method,
+        uri,
+    }))
+}
+
+/// Rejects an act-as request with [`WebResourceError::Forbidden`] on an
+/// extractor that cannot act as another principal, so the caller is never
+/// authorized as itself while believing otherwise. The attempt is audited
+/// as refused.
+fn refuse_impersonation(
+    auth_state: &AuthState,
+    parts: &Parts,
+    caller: &UserId,
+) -> Result<(), WebResourceError> {
+    let Some((audit, subject)) = act_as(auth_state, parts, caller) else {
+        return Ok(());
+    };
+    audit(&ImpersonationEvent {
+        actor: caller.clone(),
+        subject,
+        guard: impersonation_gate(),
+        granted: false,
+        method: parts.method.clone(),
+        uri: parts.uri.to_string(),
+    });
+    Err(WebResourceError::Forbidden)
+}
+
+/// [`authorize`] as the caller, or as the impersonated principal with the
+/// decision sent to the audit hook.
+async fn authorize_as(
+    auth_state: &AuthState,
+    memo: Option<&RequestMemo>,
+    impersonation: Option<&Impersonation>,
+    guard: Guard,
+    caller: UserId,
+    at: Option<Timestamp>,
+) -> Result<Principal, WebResourceError> {
+    let (Some(imp), Some(audit)) = (impersonation, &auth_state.impersonation) else {
+        return authorize(auth_state, memo, guard, caller, at).await;
+    };
+    let result = authorize(auth_state, memo, guard.clone(), imp.subject.clone(), at).await;
+    let granted = match &result {
+        Ok(_) => Some(true),
+        Err(WebResourceError::Forbidden | WebResourceError::GuardDenied(_)) => Some(false),
+        Err(_) => None,
+    };
+    if let Some(granted) = granted {
+        audit(&ImpersonationEvent {
+            actor: caller,
+            subject: imp.subject.clone(),
+            guard,
+            granted,
+            method: imp.method.clone(),
+            uri: imp.uri.clone(),
+        });
+    }
+    result
+}
+
+/// One decision made in shadow mode (see [`WebResource::shadow`]).
+#[derive(Clone, Debug)]
+pub struct ShadowEvent {
+    /// The principal the check ran as.
+    pub principal: UserId,
+    pub guard: Guard,
+    pub granted: bool,
+    /// The guard built from [`WebResource::old_rel`], if any.
+    pub old_guard: Option<Guard>,
+    /// The old guard's decision; `None` without one or when its check
+    /// failed.
+    pub old_granted: Option<bool>,
+    pub method: Method,
+    pub uri: String,
+}
+
+impl ShadowEvent {
+    /// True when the old relation decided differently.
+    pub fn diverged(&self) -> bool {
+        self.old_granted.is_some_and(|old| old != self.granted)
+    }
+}
+
+pub type ShadowReportFn = Arc<dyn Fn(&ShadowEvent) + Send + Sync>;
+
+/// A request whose guard is evaluated in shadow mode.
+struct Shadow {
+    old_guard: Option<Guard>,
+    method: Method,
+    uri: String,
+}
+
+impl Shadow {
+    /// `Some` when the resource or the state puts the request in shadow
+    /// mode.
+    fn new(
+        auth_state: &AuthState,
+        parts: &Parts,
+        shadowed: bool,
+        old_guard: Option<Guard>,
+    ) -> Option<Shadow> {
+        (shadowed || auth_state.shadow_mode).then(|| Shadow {
+            old_guard,
+            method: parts.method.clone(),
+            uri: parts.uri.to_string(),
+        })
+    }
+
+    /// The guard whose decision is enforced: the old one, if any.
+    fn in_force(&self, guard: &Guard) -> Guard {
+        self.old_guard.clone().unwrap_or_else(|| guard.clone())
+    }
+
+    fn report(
+        self,
+        auth_state: &AuthState,
+        principal: UserId,
+        guard: Guard,
+        granted: bool,
+        old_granted: Option<bool>,
+    ) {
+        let event = ShadowEvent {
+            principal,
+            guard,
+            granted,
+            old_guard: self.old_guard,
+            old_granted,
+            method: self.method,
+            uri: self.uri,
+        };
+        match &auth_state.shadow_report {
+            Some(report) => report(&event),
+            None => log::info!(
+                "nio-client: shadow {} {}: granted={} old={:?}",
+                event.method,
+                event.uri,
+                event.granted,
+                event.old_granted
+            ),
+        }
+    }
+}
+
+/// [`authorize_as`], or in shadow mode: evaluate the guard, report its
+/// decision, and enforce the old guard instead when there is one (without
+/// one the guard itself stays in force). Shadow mode never grants what the
+/// relation in force denies, and backend faults still reject.
+async fn enforce(
+    auth_state: &AuthState,
+    memo: Option<&RequestMemo>,
+    impersonation: Option<&Impersonation>,
+    shadow: Option<Shadow>,
+    guard: Guard,
+    caller: UserId,
+    at: Option<Timestamp>,
+) -> Result<Principal, WebResourceError> {
+    let Some(shadow) = shadow else {
+        return authorize_as(auth_state, memo, impersonation, guard, caller, at).await;
+    };
+    let subject = impersonation.map_or_else(|| caller.clone(), |imp| imp.subject.clone());
+    let Some(old) = shadow.old_guard.clone() else {
+        let result = authorize_as(auth_state, memo, impersonation, guard.clone(), caller, at).await;
+        if let Some(granted) = decided(&result) {
+            shadow.report(auth_state, subject, guard, granted, None);
+        }
+        return result;
+    };
+    // Matched without a binding, and before the old guard: a held
+    // `WebResourceError` is not `Send`. A fault here only skips the report.
+    let granted =
+        match authorize(auth_state, memo, guard.clone(), subject.clone(), at.clone()).await {
+            Ok(_) => Some(true),
+            Err(WebResourceError::Forbidden | WebResourceError::GuardDenied(_)) => Some(false),
+            Err(err) => {
+                log::warn!("nio-client: shadow check of the new guard failed: {err:?}");
+                None
+            }
+        };
+    let result = authorize_as(auth_state, memo, impersonation, old, caller, at).await;
+    if let Some(granted) = granted {
+        shadow.report(auth_state, subject, guard, granted, decided(&result));
+    }
+    result
+}
+
+/// The decision behind an authorization result; `None` for a fault.
+fn decided(result: &Result<Principal, WebResourceError>) -> Option<bool> {
+    match result {
+        Ok(_) => Some(true),
+        Err(WebResourceError::Forbidden | WebResourceError::GuardDenied(_)) => Some(false),
+        Err(_) => None,
+    }
+}
+
+/// Request header whose value is recorded as [`AuditContext::request_id`].
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The audit context of a request authenticated with `auth_type` (`None`
+/// for anonymous callers) for `tenant`.
+fn audit_context(
+    parts: &Parts,
+    auth_type: Option<&'static str>,
+    tenant: Option<&str>,
+) -> AuditContext {
+    let route = match parts.extensions.get::<MatchedPath>() {
+        Some(matched) => matched.as_str().to_string(),
+        None => parts.uri.path().to_string(),
+    };
+    AuditContext {
+        request_id: parts
+            .headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from),
+        route: Some(route),
+        auth_type,
+        tenant: tenant.map(String::from),
+    }
+}
+
+/// The memo [`install_memo`] put in the request extensions, if any.
+fn request_memo(parts: &Parts) -> Option<&RequestMemo> {
+    parts.extensions.get::<Arc<RequestMemo>>().map(Arc::as_ref)
+}
+
+/// The single check on [`WebResource::old_rel`], if the resource names one.
+fn old_guard<R: WebResource>(resource: &R, method: &Method) -> Option<Guard> {
+    resource
+        .old_rel(method)
+        .map(|rel| Guard::check(resource.namespace(), resource.object(), rel))
+}
+
 /// Percent-encodes a query component (RFC 3986 unreserved characters pass
 /// through).
 fn urlencode(s: &str) -> String {
@@ -90,6 +757,79 @@ fn urlencode(s: &str) -> String {
     out
 }
 
+/// Rejection of a derived [`WebResource`] (see the `derive` feature): the
+/// route's path parameters could not be extracted, one is missing, or one
+/// does not parse into its field type.
+#[derive(Debug, thiserror::Error)]
+pub enum PathParamRejection {
+    #[error(transparent)]
+    Path(#[from] PathRejection),
+    #[error("missing path parameter '{0}'")]
+    Missing(String),
+    #[error("invalid path parameter '{param}': {source}")]
+    Invalid {
+        param: String,
+        source: Box<dyn Error + Send + Sync>,
+    },
+}
+
+impl IntoResponse for PathParamRejection {
+    fn into_response(self) -> Response {
+        match self {
+            PathParamRejection::Path(rejection) => rejection.into_response(),
+            // The route does not bind a parameter the resource needs: a
+            // wiring fault, not a client error.
+            PathParamRejection::Missing(_) => {
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+            PathParamRejection::Invalid { .. } => {
+                axum::http::StatusCode::BAD_REQUEST.into_response()
+            }
+        }
+    }
+}
+
+#[cfg(feature = "derive")]
+pub use nio_client_derive::WebResource;
+
+/// Support code for `#[derive(WebResource)]`; not part of the stable API.
+#[doc(hidden)]
+pub mod __private {
+    use super::PathParamRejection;
+    use axum::extract::{FromRequestParts, Path};
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    pub use axum::http::request::Parts;
+    pub use axum::http::Method;
+
+    pub async fn path_params<S: Send + Sync>(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<HashMap<String, String>, PathParamRejection> {
+        let Path(params) =
+            Path::<HashMap<String, String>>::from_request_parts(parts, state).await?;
+        Ok(params)
+    }
+
+    pub fn path_param<T>(
+        params: &HashMap<String, String>,
+        name: &str,
+    ) -> Result<T, PathParamRejection>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let raw = params
+            .get(name)
+            .ok_or_else(|| PathParamRejection::Missing(name.to_string()))?;
+        raw.parse().map_err(|err| PathParamRejection::Invalid {
+            param: name.to_string(),
+            source: Box::new(err),
+        })
+    }
+}
+
 pub trait WebResource: Sized {
     type Rejection: IntoResponse + Error;
 
@@ -100,11 +840,38 @@ pub trait WebResource: Sized {
         state: &S,
     ) -> impl Future<Output = Result<Self, Self::Rejection>> + Send;
     fn object(&self) -> Obj;
+
+    /// The permission required for `method`: by default the single check
+    /// ⟨[`Self::namespace`], [`Self::object`], [`Self::rel`]⟩. Override to
+    /// require an all-of / any-of [`Guard`] over several checks; `None` is
+    /// method-not-allowed.
+    fn guard(&self, method: &Method) -> Option<Guard> {
+        self.rel(method)
+            .map(|rel| Guard::check(self.namespace(), self.object(), rel))
+    }
+
+    /// Puts `method` in shadow mode: the guard is evaluated and reported
+    /// (see [`AuthState::with_shadow_report`]) while [`Self::old_rel`], if
+    /// any, stays in force; without one the guard itself is enforced.
+    /// [`AuthState::with_shadow_mode`] does this for every resource.
+    fn shadow(&self, _method: &Method) -> bool {
+        false
+    }
+
+    /// The relation `method` required before the one [`Self::rel`] returns
+    /// now. In shadow mode it decides the request, and the report says
+    /// whether the new guard would have decided differently.
+    fn old_rel(&self, _method: &Method) -> Option<Rel> {
+        None
+    }
 }
 
 pub struct WithPrincipal<R, A = SessionCookieAuth> {
     pub principal: Principal,
     pub resource: R,
+    /// The real caller when acting as `principal` (see
+    /// [`AuthState::with_impersonation`]).
+    pub actor: Option<Principal>,
     auth_type: PhantomData<A>,
 }
 
@@ -126,181 +893,424 @@ impl<R, A> WithPrincipal<R, A> {
         WithPrincipal {
             principal: self.principal,
             resource,
+            actor: self.actor,
             auth_type: PhantomData,
         }
     }
 }
 
-impl<S, R> FromRequestParts<S> for WithPrincipal<R, SessionCookieAuth>
+impl<S, R, A> FromRequestParts<S> for WithPrincipal<R, A>
 where
     R: WebResource + Send + 'static,
+    A: AuthType,
     AuthState: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = WebResourceError;
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.extractor",
+            skip_all,
+            fields(extractor = "WithPrincipal", auth_type = A::name(), path = %parts.uri.path()),
+            err(level = "debug", Debug)
+        )
+    )]
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        {
-            let auth_state = AuthState::from_ref(state);
+        let auth_state = AuthState::from_ref(state);
 
-            let resource = R::parse(parts, state)
-                .await
-                .map_err(|err| WebResourceError::Parse(Box::new(err)))?;
+        let resource = R::parse(parts, state)
+            .await
+            .map_err(|err| WebResourceError::Parse(Box::new(err)))?;
 
-            let token = match parts
-                .headers
-                .typed_get::<Cookie>()
-                .and_then(|c| c.get("session").map(String::from))
-            {
-                None => {
-                    return Err(WebResourceError::MissingSession(
-                        auth_state.signin_location(parts),
-                    ))
-                }
-                Some(token) => token,
-            };
-
-            let ns = resource.namespace();
-            let obj = resource.object();
-            let rel = resource
-                .rel(&parts.method)
-                .ok_or(WebResourceError::MethodNotAllowed)?;
-
-            let u = match resolve_subject(&auth_state.resolver, &token).await {
-                Subject::Principal(u) => u,
-                Subject::NotFound => {
-                    return Err(WebResourceError::MissingSession(
-                        auth_state.signin_location(parts),
-                    ))
-                }
-                Subject::Error(err) => return Err(err),
-            };
-
-            let mut cc = auth_state.check_client;
-
-            match cc.check(ns, obj, rel, u, None).await {
-                Err(err) => {
-                    log::error!("nio-client: check returned error: {err:?}");
-                    Err(WebResourceError::InternalServerError(Box::new(err)))
-                }
-                Ok(CheckResult::Ok(principal)) => Ok(WithPrincipal {
-                    principal,
-                    resource,
-                    auth_type: PhantomData,
-                }),
-                // TODO consider passing along principal even when not authorized
-                Ok(CheckResult::Forbidden(_)) => Err(WebResourceError::Forbidden),
-                Ok(CheckResult::UnknownPutativeUser) => Err(WebResourceError::Forbidden),
-            }
-        }
+        let token = require_token::<A>(&auth_state, parts)?;
+
+        let guard = resource
+            .guard(&parts.method)
+            .ok_or(WebResourceError::MethodNotAllowed)?;
+
+        let session = require_session::<A>(&auth_state, parts, &token).await?;
+        let audit = audit_context(parts, Some(A::name()), Some(&session.tenant_id));
+        let u = UserId(session.principal);
+        let imp = audit
+            .clone()
+            .scope(impersonation(&auth_state, parts, &u))
+            .await?;
+        let shadow = Shadow::new(
+            &auth_state,
+            parts,
+            resource.shadow(&parts.method),
+            old_guard(&resource, &parts.method),
+        );
+        let memo = request_memo(parts);
+        let decision = enforce(&auth_state, memo, imp.as_ref(), shadow, guard, u, None);
+        let principal = audit.scope(decision).await?;
+        Ok(WithPrincipal {
+            principal,
+            resource,
+            actor: imp.map(|imp| imp.actor),
+            auth_type: PhantomData,
+        })
+    }
+}
+
+/// A [`WebResource`] loaded together with the zookie stored with its
+/// current content version (see [`crate::content`]). Load the content in
+/// [`WebResource::parse`]; [`WithPrincipalAt`] then checks at that zookie.
+pub trait VersionedResource: WebResource {
+    /// The zookie stored with the loaded content version; `None` for content
+    /// without one, checked as [`WithPrincipal`] would.
+    fn zookie(&self) -> Option<Timestamp>;
+}
+
+/// [`WithPrincipal`] for content-versioned resources: the check runs at a
+/// snapshot at least as fresh as the loaded content's zookie (or the
+/// request's zookie, if fresher), so an ACL change made before the content
+/// was last modified is never missed.
+pub struct WithPrincipalAt<R, A = SessionCookieAuth> {
+    pub principal: Principal,
+    pub resource: R,
+    /// The real caller when acting as `principal` (see
+    /// [`AuthState::with_impersonation`]).
+    pub actor: Option<Principal>,
+    auth_type: PhantomData<A>,
+}
+
+impl<R, A> WithPrincipalAt<R, A> {
+    pub fn into_principal_and_resource(self) -> (Principal, R) {
+        (self.principal, self.resource)
     }
 }
 
-impl<S, R> FromRequestParts<S> for WithPrincipal<R, BearerTokenAuth>
+impl<S, R, A> FromRequestParts<S> for WithPrincipalAt<R, A>
+where
+    R: VersionedResource + Send + 'static,
+    A: AuthType,
+    AuthState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = WebResourceError;
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.extractor",
+            skip_all,
+            fields(extractor = "WithPrincipalAt", auth_type = A::name(), path = %parts.uri.path()),
+            err(level = "debug", Debug)
+        )
+    )]
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let auth_state = AuthState::from_ref(state);
+
+        let resource = R::parse(parts, state)
+            .await
+            .map_err(|err| WebResourceError::Parse(Box::new(err)))?;
+
+        let token = require_token::<A>(&auth_state, parts)?;
+
+        let guard = resource
+            .guard(&parts.method)
+            .ok_or(WebResourceError::MethodNotAllowed)?;
+
+        let session = require_session::<A>(&auth_state, parts, &token).await?;
+        let audit = audit_context(parts, Some(A::name()), Some(&session.tenant_id));
+        let u = UserId(session.principal);
+        let imp = audit
+            .clone()
+            .scope(impersonation(&auth_state, parts, &u))
+            .await?;
+        let shadow = Shadow::new(
+            &auth_state,
+            parts,
+            resource.shadow(&parts.method),
+            old_guard(&resource, &parts.method),
+        );
+        let at = resource.zookie();
+        let memo = request_memo(parts);
+        let decision = enforce(&auth_state, memo, imp.as_ref(), shadow, guard, u, at);
+        let principal = audit.scope(decision).await?;
+        Ok(WithPrincipalAt {
+            principal,
+            resource,
+            actor: imp.map(|imp| imp.actor),
+            auth_type: PhantomData,
+        })
+    }
+}
+
+/// [`WithPrincipal`] for websocket and SSE routes: authorizes the upgrade
+/// the same way, then keeps re-validating the session and the guard for as
+/// long as the handler holds [`Self::access`] (see [`crate::live`]). Select
+/// on [`LiveAccess::revoked`] next to the connection and close it when that
+/// resolves. Tunables come from [`AuthState::with_live_access`].
+pub struct WithLiveAccess<R, A = SessionCookieAuth> {
+    pub principal: Principal,
+    pub resource: R,
+    pub access: LiveAccess,
+    auth_type: PhantomData<A>,
+}
+
+impl<S, R, A> FromRequestParts<S> for WithLiveAccess<R, A>
 where
     R: WebResource + Send + 'static,
+    A: AuthType,
     AuthState: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = WebResourceError;
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.extractor",
+            skip_all,
+            fields(extractor = "WithLiveAccess", auth_type = A::name(), path = %parts.uri.path()),
+            err(level = "debug", Debug)
+        )
+    )]
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        {
-            let auth_state = AuthState::from_ref(state);
-
-            let resource = R::parse(parts, state)
-                .await
-                .map_err(|err| WebResourceError::Parse(Box::new(err)))?;
-
-            // TODO impl proper oauth2 response
-            let bearer = match parts.headers.typed_try_get::<Authorization<Bearer>>() {
-                Ok(Some(bearer)) => bearer,
-                Ok(None) | Err(_) => {
-                    return Err(WebResourceError::MissingSession(
-                        auth_state.signin_location(parts),
-                    ))
-                }
-            };
-
-            let ns = resource.namespace();
-            let obj = resource.object();
-            let rel = resource
-                .rel(&parts.method)
-                .ok_or(WebResourceError::MethodNotAllowed)?;
-
-            let u = match resolve_subject(&auth_state.resolver, bearer.token()).await {
-                Subject::Principal(u) => u,
-                Subject::NotFound => {
-                    return Err(WebResourceError::MissingSession(
-                        auth_state.signin_location(parts),
-                    ))
-                }
-                Subject::Error(err) => return Err(err),
-            };
-
-            let mut cc = auth_state.check_client;
-            match cc.check(ns, obj, rel, u, None).await {
-                Err(err) => Err(WebResourceError::InternalServerError(Box::new(err))),
-                Ok(CheckResult::Ok(principal)) => Ok(WithPrincipal {
-                    principal,
-                    resource,
-                    auth_type: PhantomData,
-                }),
-                Ok(CheckResult::Forbidden(_)) => Err(WebResourceError::Forbidden),
-                Ok(CheckResult::UnknownPutativeUser) => Err(WebResourceError::Forbidden),
-            }
-        }
+        let auth_state = AuthState::from_ref(state);
+
+        let resource = R::parse(parts, state)
+            .await
+            .map_err(|err| WebResourceError::Parse(Box::new(err)))?;
+
+        let token = require_token::<A>(&auth_state, parts)?;
+
+        let guard = resource
+            .guard(&parts.method)
+            .ok_or(WebResourceError::MethodNotAllowed)?;
+
+        let session = require_session::<A>(&auth_state, parts, &token).await?;
+        let u = UserId(session.principal.clone());
+        // Re-validation runs as the session's principal, so act-as cannot
+        // carry over to the connection.
+        refuse_impersonation(&auth_state, parts, &u)?;
+        let audit = audit_context(parts, Some(A::name()), Some(&session.tenant_id));
+        let shadow = Shadow::new(
+            &auth_state,
+            parts,
+            resource.shadow(&parts.method),
+            old_guard(&resource, &parts.method),
+        );
+        // Re-validation keeps enforcing whatever the upgrade was decided by.
+        let in_force = match &shadow {
+            Some(shadow) => shadow.in_force(&guard),
+            None => guard.clone(),
+        };
+        let memo = request_memo(parts);
+        let decision = enforce(&auth_state, memo, None, shadow, guard, u, None);
+        let principal = audit.scope(decision).await?;
+        let resolver = A::resolver(&auth_state)?.clone();
+        let access = LiveAccess::start(
+            auth_state.check_client.clone(),
+            resolver,
+            crate::session::token_hash(&token),
+            session,
+            in_force,
+            auth_state.live_access.clone(),
+        );
+        Ok(WithLiveAccess {
+            principal,
+            resource,
+            access,
+            auth_type: PhantomData,
+        })
     }
 }
 
 /// Authenticates the caller and yields the principal **without** running a
 /// check.
 ///
-/// For handlers whose authorization object is not knowable from the request
-/// [`Parts`] — e.g. where the object is identified by the request *body*. A
-/// [`WebResource`] guard cannot express that, since `parse` never sees the
-/// body. Such a handler **must** call [`CheckClient::check`] itself once it
-/// knows the object; this extractor only establishes *who* is calling.
+/// For handlers that authorize by other means. When the object is identified
+/// by the request *body*, prefer [`WithPrincipalBody`], which runs the check
+/// itself; a handler using this extractor **must** call
+/// [`CheckClient::check`] once it knows the object — this extractor only
+/// establishes *who* is calling.
 pub struct Authenticated<A = BearerTokenAuth> {
     pub principal: UserId,
     auth_type: PhantomData<A>,
 }
 
-impl<S> FromRequestParts<S> for Authenticated<BearerTokenAuth>
+impl<S, A> FromRequestParts<S> for Authenticated<A>
 where
+    A: AuthType,
     AuthState: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = WebResourceError;
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.extractor",
+            skip_all,
+            fields(extractor = "Authenticated", auth_type = A::name(), path = %parts.uri.path()),
+            err(level = "debug", Debug)
+        )
+    )]
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        {
-            let auth_state = AuthState::from_ref(state);
-            let bearer = match parts.headers.typed_try_get::<Authorization<Bearer>>() {
-                Ok(Some(bearer)) => bearer,
-                Ok(None) | Err(_) => {
-                    return Err(WebResourceError::MissingSession(
-                        auth_state.signin_location(parts),
-                    ))
-                }
-            };
-            match resolve_subject(&auth_state.resolver, bearer.token()).await {
-                Subject::Principal(principal) => Ok(Authenticated {
-                    principal,
-                    auth_type: PhantomData,
-                }),
-                Subject::NotFound => Err(WebResourceError::MissingSession(
-                    auth_state.signin_location(parts),
-                )),
-                Subject::Error(err) => Err(err),
-            }
+        let auth_state = AuthState::from_ref(state);
+        let token = require_token::<A>(&auth_state, parts)?;
+        let principal = require_subject::<A>(&auth_state, parts, &token).await?;
+        refuse_impersonation(&auth_state, parts, &principal)?;
+        Ok(Authenticated {
+            principal,
+            auth_type: PhantomData,
+        })
+    }
+}
+
+/// A guard whose object is carried in the request body rather than the path:
+/// [`WebResource`] with `object` derived from the deserialized body `B`.
+pub trait BodyResource<B>: Sized {
+    type Rejection: IntoResponse + Error;
+
+    fn namespace(&self) -> Namespace;
+    fn rel(&self, method: &Method) -> Option<Rel>;
+    fn parse<S: Send + Sync>(
+        parts: &mut Parts,
+        state: &S,
+    ) -> impl Future<Output = Result<Self, Self::Rejection>> + Send;
+    fn object(&self, body: &B) -> Obj;
+
+    /// See [`WebResource::shadow`].
+    fn shadow(&self, _method: &Method) -> bool {
+        false
+    }
+
+    /// See [`WebResource::old_rel`].
+    fn old_rel(&self, _method: &Method) -> Option<Rel> {
+        None
+    }
+}
+
+/// Like [`WithPrincipal`], for guards whose object comes from the body: the
+/// body is deserialized (JSON, or form for
+/// `application/x-www-form-urlencoded`), the object is taken from it via
+/// [`BodyResource::object`], the check runs, and the handler gets the
+/// principal together with the typed body. Rejections match
+/// [`WithPrincipal`]; an undecodable body is [`WebResourceError::Parse`].
+///
+/// The caller is authenticated before the body is read, so an anonymous
+/// request never gets its body buffered. As a body extractor it must be the
+/// last handler argument.
+pub struct WithPrincipalBody<R, B, A = SessionCookieAuth> {
+    pub principal: Principal,
+    pub resource: R,
+    pub body: B,
+    /// The real caller when acting as `principal` (see
+    /// [`AuthState::with_impersonation`]).
+    pub actor: Option<Principal>,
+    auth_type: PhantomData<A>,
+}
+
+impl<R, B, A> WithPrincipalBody<R, B, A> {
+    pub fn into_parts(self) -> (Principal, R, B) {
+        (self.principal, self.resource, self.body)
+    }
+}
+
+impl<S, R, B, A> FromRequest<S> for WithPrincipalBody<R, B, A>
+where
+    R: BodyResource<B> + Send + 'static,
+    B: DeserializeOwned + Send + 'static,
+    A: AuthType,
+    AuthState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = WebResourceError;
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.extractor",
+            skip_all,
+            fields(extractor = "WithPrincipalBody", auth_type = A::name(), path = %req.uri().path()),
+            err(level = "debug", Debug)
+        )
+    )]
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let auth_state = AuthState::from_ref(state);
+        let (mut parts, body) = req.into_parts();
+
+        let resource = R::parse(&mut parts, state)
+            .await
+            .map_err(|err| WebResourceError::Parse(Box::new(err)))?;
+
+        let token = require_token::<A>(&auth_state, &parts)?;
+
+        let ns = resource.namespace();
+        let rel = resource
+            .rel(&parts.method)
+            .ok_or(WebResourceError::MethodNotAllowed)?;
+
+        let session = require_session::<A>(&auth_state, &parts, &token).await?;
+        let audit = audit_context(&parts, Some(A::name()), Some(&session.tenant_id));
+        let u = UserId(session.principal);
+        let imp = audit
+            .clone()
+            .scope(impersonation(&auth_state, &parts, &u))
+            .await?;
+        let memo = parts.extensions.get::<Arc<RequestMemo>>().cloned();
+        let shadowed = resource.shadow(&parts.method);
+        let old_rel = resource.old_rel(&parts.method);
+        let mut shadow = Shadow::new(&auth_state, &parts, shadowed, None);
+
+        let body = decode_body::<S, B>(Request::from_parts(parts, body), state).await?;
+        let obj = resource.object(&body);
+        if let (Some(shadow), Some(old_rel)) = (&mut shadow, old_rel) {
+            shadow.old_guard = Some(Guard::check(ns.clone(), obj.clone(), old_rel));
         }
+        let guard = Guard::check(ns, obj, rel);
+        let memo = memo.as_deref();
+        let decision = enforce(&auth_state, memo, imp.as_ref(), shadow, guard, u, None);
+        let principal = audit.scope(decision).await?;
+        Ok(WithPrincipalBody {
+            principal,
+            resource,
+            body,
+            actor: imp.map(|imp| imp.actor),
+            auth_type: PhantomData,
+        })
     }
 }
 
+/// Deserializes the body as a form for `application/x-www-form-urlencoded`,
+/// else as JSON (which insists on a JSON content type).
+async fn decode_body<S, B>(req: Request, state: &S) -> Result<B, WebResourceError>
+where
+    B: DeserializeOwned + Send + 'static,
+    S: Send + Sync,
+{
+    let is_form = req
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/x-www-form-urlencoded"));
+    if is_form {
+        let Form(body) = Form::<B>::from_request(req, state)
+            .await
+            .map_err(|err| WebResourceError::Parse(Box::new(err)))?;
+        Ok(body)
+    } else {
+        let Json(body) = Json::<B>::from_request(req, state)
+            .await
+            .map_err(|err| WebResourceError::Parse(Box::new(err)))?;
+        Ok(body)
+    }
+}
+
+/// Authorizes a caller who presents a `session` cookie; one who does not gets
+/// `principal: None` (see [`AuthState::with_anonymous_checks`]).
 pub struct WithOptPrincipal<R> {
     pub principal: Option<Principal>,
     pub resource: R,
+    /// The real caller when acting as `principal` (see
+    /// [`AuthState::with_impersonation`]).
+    pub actor: Option<Principal>,
 }
 
 impl<S, R> FromRequestParts<S> for WithOptPrincipal<R>
@@ -311,59 +1321,314 @@ where
 {
     type Rejection = WebResourceError;
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.extractor",
+            skip_all,
+            fields(extractor = "WithOptPrincipal", auth_type = SessionCookieAuth::name(), path = %parts.uri.path()),
+            err(level = "debug", Debug)
+        )
+    )]
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        {
-            let auth_state = AuthState::from_ref(state);
+        let auth_state = AuthState::from_ref(state);
 
-            let resource = R::parse(parts, state)
-                .await
-                .map_err(|err| WebResourceError::Parse(Box::new(err)))?;
+        let resource = R::parse(parts, state)
+            .await
+            .map_err(|err| WebResourceError::Parse(Box::new(err)))?;
 
-            let token = match parts
-                .headers
-                .typed_get::<Cookie>()
-                .and_then(|c| c.get("session").map(String::from))
-            {
-                None => {
-                    return Ok(WithOptPrincipal {
-                        principal: None,
-                        resource,
-                    })
-                }
-                Some(token) => token,
-            };
-
-            let ns = resource.namespace();
-            let obj = resource.object();
-            let rel = resource
-                .rel(&parts.method)
-                .ok_or(WebResourceError::MethodNotAllowed)?;
-
-            let u = match resolve_subject(&auth_state.resolver, &token).await {
-                Subject::Principal(u) => u,
-                Subject::NotFound => {
-                    return Ok(WithOptPrincipal {
-                        principal: None,
-                        resource,
-                    })
-                }
-                Subject::Error(err) => return Err(err),
-            };
-
-            let mut cc = auth_state.check_client;
-            match cc.check(ns, obj, rel, u, None).await {
-                Err(err) => Err(WebResourceError::InternalServerError(Box::new(err))),
-                Ok(CheckResult::Ok(principal)) => Ok(WithOptPrincipal {
-                    principal: Some(principal),
-                    resource,
-                }),
-                Ok(CheckResult::Forbidden(_)) => Err(WebResourceError::Forbidden),
-                Ok(CheckResult::UnknownPutativeUser) => Err(WebResourceError::Forbidden),
-            }
+        let token = match SessionCookieAuth::token(parts) {
+            None => return anonymous(&auth_state, parts, resource).await,
+            Some(token) => token,
+        };
+        verify_csrf::<SessionCookieAuth>(&auth_state, parts, &token)?;
+
+        let guard = resource
+            .guard(&parts.method)
+            .ok_or(WebResourceError::MethodNotAllowed)?;
+
+        let session = match resolve_subject(&auth_state.resolver, &token).await {
+            Subject::Principal(session) => Some(session),
+            Subject::NotFound => None,
+            Subject::Error(err) => return Err(err),
+        };
+        let Some(session) = session else {
+            return anonymous(&auth_state, parts, resource).await;
+        };
+
+        let auth_type = SessionCookieAuth::name();
+        let audit = audit_context(parts, Some(auth_type), Some(&session.tenant_id));
+        let u = UserId(session.principal);
+        let imp = audit
+            .clone()
+            .scope(impersonation(&auth_state, parts, &u))
+            .await?;
+        let shadow = Shadow::new(
+            &auth_state,
+            parts,
+            resource.shadow(&parts.method),
+            old_guard(&resource, &parts.method),
+        );
+        let memo = request_memo(parts);
+        let decision = enforce(&auth_state, memo, imp.as_ref(), shadow, guard, u, None);
+        let principal = audit.scope(decision).await?;
+        Ok(WithOptPrincipal {
+            principal: Some(principal),
+            resource,
+            actor: imp.map(|imp| imp.actor),
+        })
+    }
+}
+
+/// [`WithOptPrincipal`] for a caller without a usable session: `principal:
+/// None` without any check, or — with [`AuthState::with_anonymous_checks`] —
+/// the guard evaluated for [`UserId::all_users`]. A public grant yields
+/// [`Principal::anonymous`]; a denial sends the caller to sign-in.
+async fn anonymous<R: WebResource>(
+    auth_state: &AuthState,
+    parts: &Parts,
+    resource: R,
+) -> Result<WithOptPrincipal<R>, WebResourceError> {
+    if !auth_state.anonymous_checks {
+        return Ok(WithOptPrincipal {
+            principal: None,
+            resource,
+            actor: None,
+        });
+    }
+    let guard = resource
+        .guard(&parts.method)
+        .ok_or(WebResourceError::MethodNotAllowed)?;
+    let shadow = Shadow::new(
+        auth_state,
+        parts,
+        resource.shadow(&parts.method),
+        old_guard(&resource, &parts.method),
+    );
+    let memo = request_memo(parts);
+    let decision = enforce(
+        auth_state,
+        memo,
+        None,
+        shadow,
+        guard,
+        UserId::all_users(),
+        None,
+    );
+    match audit_context(parts, None, None).scope(decision).await {
+        Ok(_) => Ok(WithOptPrincipal {
+            principal: Some(Principal::anonymous()),
+            resource,
+            actor: None,
+        }),
+        Err(WebResourceError::Forbidden | WebResourceError::GuardDenied(_)) => Err(
+            WebResourceError::MissingSession(auth_state.signin_location(parts)),
+        ),
+        Err(err) => Err(err),
+    }
+}
+
+/// The `iam:root` admin gate checked with `iam.get` for every method. Use
+/// through [`RequireIamViewer`].
+pub struct IamViewerGate;
+
+/// The `iam:root` admin gate checked with `iam.update` for every method. Use
+/// through [`RequireIamAdmin`].
+pub struct IamAdminGate;
+
+impl WebResource for IamViewerGate {
+    type Rejection = Infallible;
+
+    fn namespace(&self) -> Namespace {
+        Namespace::iam()
+    }
+    fn rel(&self, _method: &Method) -> Option<Rel> {
+        Some(Rel::iam_get())
+    }
+    async fn parse<S: Send + Sync>(_parts: &mut Parts, _state: &S) -> Result<Self, Infallible> {
+        Ok(IamViewerGate)
+    }
+    fn object(&self) -> Obj {
+        Obj::root()
+    }
+}
+
+impl WebResource for IamAdminGate {
+    type Rejection = Infallible;
+
+    fn namespace(&self) -> Namespace {
+        Namespace::iam()
+    }
+    fn rel(&self, _method: &Method) -> Option<Rel> {
+        Some(Rel::iam_update())
+    }
+    async fn parse<S: Send + Sync>(_parts: &mut Parts, _state: &S) -> Result<Self, Infallible> {
+        Ok(IamAdminGate)
+    }
+    fn object(&self) -> Obj {
+        Obj::root()
+    }
+}
+
+/// Admin viewer routes: the caller must hold `iam:root#iam.get`.
+pub type RequireIamViewer<A = SessionCookieAuth> = WithPrincipal<IamViewerGate, A>;
+
+/// Admin routes: the caller must hold `iam:root#iam.update`.
+pub type RequireIamAdmin<A = SessionCookieAuth> = WithPrincipal<IamAdminGate, A>;
+
+/// Request header carrying a zookie, for API clients that don't keep cookies.
+pub const ZOOKIE_HEADER: &str = "x-nio-zookie";
+/// Cookie the browser echoes the request's newest zookie back in.
+pub const ZOOKIE_COOKIE: &str = "check_ts";
+
+/// How [`propagate_zookie_with`] trusts and re-emits zookies.
+#[derive(Clone, Debug)]
+pub struct ZookieConfig {
+    /// Incoming zookies naming a snapshot later than now plus this are
+    /// ignored: check never issued them, and a made-up far-future zookie
+    /// would bound every check of the request. Allows for clock skew
+    /// between this host and check.
+    pub max_skew: Duration,
+    /// Sends the `check_ts` cookie with `Secure`. Turn off only for plain
+    /// HTTP in development.
+    pub secure_cookie: bool,
+}
+
+impl Default for ZookieConfig {
+    /// 5s of skew, `Secure` cookie.
+    fn default() -> Self {
+        ZookieConfig {
+            max_skew: Duration::from_secs(5),
+            secure_cookie: true,
         }
     }
 }
 
+/// Middleware propagating zookies across requests (add it with
+/// `axum::middleware::from_fn(propagate_zookie)`). Seeds a [`ZookieJar`]
+/// from the `X-Nio-Zookie` header or the `check_ts` cookie, runs the rest
+/// of the stack inside [`ZookieJar::scope`] — so every check made for the
+/// request, extractors included, is at least that fresh and writes record
+/// their commit zookie — then re-emits the newest zookie as a `check_ts`
+/// cookie and an `X-Nio-Zookie` response header. Uses the default
+/// [`ZookieConfig`].
+pub async fn propagate_zookie(req: Request, next: Next) -> Response {
+    propagate(&ZookieConfig::default(), req, next).await
+}
+
+/// [`propagate_zookie`] with `config` (add it with
+/// `axum::middleware::from_fn_with_state(config, propagate_zookie_with)`).
+pub async fn propagate_zookie_with(
+    State(config): State<ZookieConfig>,
+    req: Request,
+    next: Next,
+) -> Response {
+    propagate(&config, req, next).await
+}
+
+async fn propagate(config: &ZookieConfig, mut req: Request, next: Next) -> Response {
+    let limit = SystemTime::now() + config.max_skew;
+    let jar = ZookieJar::new(incoming_zookie(req.headers()).filter(|ts| ts.not_after(limit)));
+    req.extensions_mut().insert(jar.clone());
+    let mut resp = jar.clone().scope(next.run(req)).await;
+    if let Some(ts) = jar.get() {
+        let headers = resp.headers_mut();
+        let secure = if config.secure_cookie { "; Secure" } else { "" };
+        let cookie = format!(
+            "{ZOOKIE_COOKIE}={}; Path=/; HttpOnly; SameSite=Lax{secure}",
+            ts.0
+        );
+        if let Ok(value) = HeaderValue::from_str(&cookie) {
+            headers.append(SET_COOKIE, value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&ts.0) {
+            headers.insert(ZOOKIE_HEADER, value);
+        }
+    }
+    resp
+}
+
+/// The client's zookie: the header wins over the cookie. Malformed values
+/// are ignored rather than rejected — a stale or mangled cookie must not
+/// break the page.
+fn incoming_zookie(headers: &HeaderMap) -> Option<Timestamp> {
+    let header = headers
+        .get(ZOOKIE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| Timestamp(v.trim().to_string()))
+        .filter(Timestamp::is_valid);
+    header.or_else(|| {
+        headers
+            .typed_get::<Cookie>()
+            .and_then(|c| c.get(ZOOKIE_COOKIE).map(|v| Timestamp(v.to_string())))
+            .filter(Timestamp::is_valid)
+    })
+}
+
+/// The request's [`ZookieJar`] as installed by [`propagate_zookie`]; without
+/// the middleware, the task's current jar or an empty, unscoped one.
+impl<S: Send + Sync> FromRequestParts<S> for ZookieJar {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ZookieJar>()
+            .cloned()
+            .or_else(ZookieJar::current)
+            .unwrap_or_default())
+    }
+}
+
+/// Middleware giving each request one [`RequestMemo`] over the state's
+/// check client (add it with
+/// `axum::middleware::from_fn_with_state(auth_state, install_memo)`). The
+/// extractors answer their checks through it, and handlers reach it with
+/// [`Memo`], so a question the guard already asked costs no second RPC.
+///
+/// Mind the memo's read-after-write caveat: a handler that writes and then
+/// re-checks expecting to see its own write must use the check client.
+/// Layered inside [`propagate_zookie`], memoized checks are evaluated at the
+/// request's zookie.
+pub async fn install_memo(
+    State(auth_state): State<AuthState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let memo = RequestMemo::new(auth_state.check_client.clone());
+    req.extensions_mut().insert(Arc::new(memo));
+    next.run(req).await
+}
+
+/// The request's [`RequestMemo`]. Without [`install_memo`] a fresh memo is
+/// created and kept for the extractors that run after this one.
+pub struct Memo(pub Arc<RequestMemo>);
+
+impl<S> FromRequestParts<S> for Memo
+where
+    AuthState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(memo) = parts.extensions.get::<Arc<RequestMemo>>() {
+            return Ok(Memo(memo.clone()));
+        }
+        let memo = Arc::new(RequestMemo::new(AuthState::from_ref(state).check_client));
+        parts.extensions.insert(memo.clone());
+        Ok(Memo(memo))
+    }
+}
+
+impl std::ops::Deref for Memo {
+    type Target = RequestMemo;
+
+    fn deref(&self) -> &RequestMemo {
+        &self.0
+    }
+}
+
 #[derive(Clone)]
 pub struct AuthState {
     pub check_client: CheckClient,
@@ -372,6 +1637,13 @@ pub struct AuthState {
     /// never sent to `check` (#243).
     pub resolver: Arc<dyn SessionResolver>,
     prefix: String,
+    anonymous_checks: bool,
+    csrf: Option<Arc<CsrfConfig>>,
+    live_access: LiveAccessConfig,
+    impersonation: Option<ImpersonationAuditFn>,
+    api_keys: Option<Arc<dyn SessionResolver>>,
+    shadow_mode: bool,
+    shadow_report: Option<ShadowReportFn>,
 }
 
 impl AuthState {
@@ -392,9 +1664,73 @@ impl AuthState {
             check_client,
             resolver,
             prefix: prefix.to_string(),
+            anonymous_checks: false,
+            csrf: None,
+            live_access: LiveAccessConfig::default(),
+            impersonation: None,
+            api_keys: None,
+            shadow_mode: false,
+            shadow_report: None,
         }
     }
 
+    /// Makes [`WithOptPrincipal`] check callers without a session against
+    /// the public marker [`UserId::all_users`] instead of letting them
+    /// through unchecked: a public resource yields
+    /// [`Principal::anonymous`], a private one the sign-in redirect.
+    pub fn with_anonymous_checks(mut self, enabled: bool) -> Self {
+        self.anonymous_checks = enabled;
+        self
+    }
+
+    /// Enables CSRF defenses for cookie-authenticated unsafe requests;
+    /// failures are rejected with [`WebResourceError::Csrf`].
+    pub fn with_csrf(mut self, config: CsrfConfig) -> Self {
+        self.csrf = Some(Arc::new(config));
+        self
+    }
+
+    /// Re-validation tunables for [`WithLiveAccess`] connections.
+    pub fn with_live_access(mut self, config: LiveAccessConfig) -> Self {
+        self.live_access = config;
+        self
+    }
+
+    /// Enables "act as": a caller holding `iam:root#iam.update` may name a
+    /// principal in the `X-Nio-Act-As` header (or `act_as` cookie), and
+    /// [`WithPrincipal`], [`WithPrincipalAt`], [`WithPrincipalBody`] and
+    /// [`WithOptPrincipal`] then check as that principal, keeping the caller
+    /// in `actor`. [`WithLiveAccess`] and [`Authenticated`] cannot act as
+    /// anyone else and reject such requests with
+    /// [`WebResourceError::Forbidden`]. Every impersonated decision, and
+    /// every refused attempt, goes to `audit`.
+    pub fn with_impersonation(mut self, audit: ImpersonationAuditFn) -> Self {
+        self.impersonation = Some(audit);
+        self
+    }
+
+    /// Resolver for [`ApiKeyAuth`] keys, typically an
+    /// [`ApiKeyResolver`](crate::session::ApiKeyResolver). The resolved
+    /// service account is the principal its checks run as.
+    pub fn with_api_keys(mut self, resolver: Arc<dyn SessionResolver>) -> Self {
+        self.api_keys = Some(resolver);
+        self
+    }
+
+    /// Puts every resource in shadow mode (see [`WebResource::shadow`]):
+    /// guards are evaluated and reported, and the old relation of a
+    /// resource that names one is enforced instead.
+    pub fn with_shadow_mode(mut self, enabled: bool) -> Self {
+        self.shadow_mode = enabled;
+        self
+    }
+
+    /// Receives every shadow-mode decision; without it they are logged.
+    pub fn with_shadow_report(mut self, report: ShadowReportFn) -> Self {
+        self.shadow_report = Some(report);
+        self
+    }
+
     pub fn prefix(&self) -> &str {
         &self.prefix
     }
@@ -432,22 +1768,67 @@ mod tests {
     fn web_resource_error_status_mapping() {
         // NIO-015: variants must not all collapse to 404.
         assert_eq!(status(WebResourceError::Forbidden), StatusCode::FORBIDDEN);
+        assert_eq!(
+            status(WebResourceError::Unauthorized),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(WebResourceError::GuardDenied(crate::guard::Denial {
+                clauses: vec![]
+            })),
+            StatusCode::FORBIDDEN
+        );
         assert_eq!(
             status(WebResourceError::MethodNotAllowed),
             StatusCode::METHOD_NOT_ALLOWED
         );
+        assert_eq!(
+            status(WebResourceError::Csrf("no origin".into())),
+            StatusCode::FORBIDDEN
+        );
         assert_eq!(
             status(WebResourceError::InternalServerError(Box::new(
                 TestInternalError
             ))),
             StatusCode::INTERNAL_SERVER_ERROR
         );
+        assert_eq!(
+            status(WebResourceError::Unavailable(Box::new(TestInternalError))),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
         assert_eq!(
             status(WebResourceError::Parse(Box::new(TestParseError))),
             StatusCode::BAD_REQUEST
         );
     }
 
+    #[test]
+    fn client_errors_are_503_when_retryable_else_500() {
+        let unavailable = CallError::Status(tonic::Status::unavailable("down"));
+        assert!(matches!(
+            WebResourceError::from(unavailable),
+            WebResourceError::Unavailable(_)
+        ));
+        let internal = ReadError::Grpc(tonic::Status::internal("boom"));
+        assert!(matches!(
+            WebResourceError::from(internal),
+            WebResourceError::InternalServerError(_)
+        ));
+        // check refusing our own call is still our fault, not the caller's.
+        let denied = WriteError::Grpc(tonic::Status::permission_denied("no"));
+        assert!(matches!(
+            WebResourceError::from(denied),
+            WebResourceError::InternalServerError(_)
+        ));
+        assert!(matches!(
+            WebResourceError::from(ResolveError::Status {
+                code: tonic::Code::Unavailable,
+                message: "x".into(),
+            }),
+            WebResourceError::Unavailable(_)
+        ));
+    }
+
     #[test]
     fn missing_session_redirects_to_location() {
         let resp = WebResourceError::MissingSession("/app/signin?back=%2Fx".into()).into_response();
@@ -517,4 +1898,61 @@ mod tests {
         assert_eq!(urlencode("/a b?c=d&e"), "%2Fa%20b%3Fc%3Dd%26e");
         assert_eq!(urlencode("AZaz09-._~"), "AZaz09-._~");
     }
+
+    fn csrf_parts(method: Method, headers: &[(&str, &str)]) -> Parts {
+        let mut builder = axum::http::Request::builder().method(method).uri("/docs/1");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    fn csrf_config(double_submit: bool) -> CsrfConfig {
+        CsrfConfig {
+            allowed_origins: vec!["https://app.example".into()],
+            double_submit,
+        }
+    }
+
+    #[test]
+    fn csrf_leaves_safe_methods_alone() {
+        let parts = csrf_parts(Method::GET, &[("origin", "https://evil.example")]);
+        assert!(csrf_config(true).verify(&parts, "tok").is_ok());
+    }
+
+    #[test]
+    fn csrf_checks_origin_then_referer() {
+        let csrf = csrf_config(false);
+        let ok = csrf_parts(Method::POST, &[("origin", "https://app.example")]);
+        assert!(csrf.verify(&ok, "tok").is_ok());
+        let referer = csrf_parts(Method::POST, &[("referer", "https://app.example/docs/1?x")]);
+        assert!(csrf.verify(&referer, "tok").is_ok());
+        for headers in [
+            &[("origin", "https://evil.example")][..],
+            &[("origin", "null")][..],
+            &[("referer", "https://app.example.evil.example/")][..],
+            &[][..],
+        ] {
+            let parts = csrf_parts(Method::DELETE, headers);
+            assert!(
+                matches!(csrf.verify(&parts, "tok"), Err(WebResourceError::Csrf(_))),
+                "{headers:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn csrf_double_submit_token_is_tied_to_the_session() {
+        let csrf = CsrfConfig {
+            allowed_origins: vec![],
+            double_submit: true,
+        };
+        let token = csrf_token("tok");
+        assert_ne!(token, crate::session::token_hash("tok"));
+        let ok = csrf_parts(Method::PUT, &[("x-csrf-token", &token)]);
+        assert!(csrf.verify(&ok, "tok").is_ok());
+        assert!(csrf.verify(&ok, "other").is_err(), "other session");
+        let missing = csrf_parts(Method::PUT, &[]);
+        assert!(csrf.verify(&missing, "tok").is_err());
+    }
 }
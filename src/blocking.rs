@@ -0,0 +1,279 @@
+//! A blocking [`CheckClient`] for synchronous callers (batch jobs, build
+//! scripts).
+//!
+//! [`CheckClient`] here owns a current-thread tokio runtime on a background
+//! thread, the way `reqwest::blocking` does. The runtime drives the
+//! channel, so HTTP/2 keepalive pings keep flowing between calls. Each call
+//! runs on that runtime while the calling thread waits for the result.
+//! Clones share the runtime, which shuts down when the last clone is
+//! dropped.
+//!
+//! Calling it from inside an async runtime would block one of that
+//! runtime's threads, so every method (and constructor) panics there. In
+//! async code use the async [`crate::CheckClient`], or move the blocking
+//! work into `tokio::task::spawn_blocking`.
+//!
+//! ```rust,ignore
+//! let client = nio_client::blocking::CheckClient::create("unix:///run/nio/check.sock".parse::<Target>()?)?;
+//! let result = client.check(Namespace("doc".into()), Obj("1".into()), Rel::viewer(), UserId("u1".into()), None)?;
+//! ```
+//!
+//! [`CheckClient`]: crate::blocking::CheckClient
+
+// The errors are the async client's, which carry a `tonic::Status`.
+#![allow(clippy::result_large_err)]
+
+use crate::auth::{CallError, CheckResult};
+use crate::builder::{BuildError, NioClientBuilder};
+use crate::{
+    ConnectError, ContentChangeCheckResult, ExpandResult, ListResult, Namespace, NamespaceMeta,
+    Obj, ReadError, ReadFilter, ReadResult, Rel, Target, Timestamp, Tuple, UserId, UserSet,
+    WriteError,
+};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use tonic::transport::ClientTlsConfig;
+
+/// The background runtime. Dropping it ends the runtime thread.
+struct Runtime {
+    handle: tokio::runtime::Handle,
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl Runtime {
+    /// Panics if the runtime or its thread cannot be started.
+    fn start() -> Self {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build the nio-client blocking runtime");
+        let handle = runtime.handle().clone();
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        std::thread::Builder::new()
+            .name("nio-client-blocking".into())
+            .spawn(move || {
+                runtime.block_on(async {
+                    let _ = stopped.await;
+                })
+            })
+            .expect("start the nio-client blocking runtime thread");
+        Runtime {
+            handle,
+            _shutdown: shutdown,
+        }
+    }
+
+    fn block_on<F>(&self, future: F) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        assert!(
+            tokio::runtime::Handle::try_current().is_err(),
+            "nio_client::blocking called from inside an async runtime; \
+             use the async CheckClient or tokio::task::spawn_blocking"
+        );
+        let (tx, rx) = oneshot::channel();
+        self.handle.spawn(async move {
+            let _ = tx.send(future.await);
+        });
+        rx.blocking_recv()
+            .expect("nio-client blocking runtime stopped")
+    }
+}
+
+/// Blocking counterpart of [`crate::CheckClient`]; see the module docs.
+/// `watch` has no blocking form.
+#[derive(Clone)]
+pub struct CheckClient {
+    inner: crate::CheckClient,
+    runtime: Arc<Runtime>,
+}
+
+impl std::fmt::Debug for CheckClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CheckClient").finish_non_exhaustive()
+    }
+}
+
+impl CheckClient {
+    pub fn create(target: impl Into<Target>) -> Result<Self, ConnectError> {
+        Self::create_with_tls(target, None)
+    }
+
+    pub fn create_with_tls(
+        target: impl Into<Target>,
+        tls_config: Option<ClientTlsConfig>,
+    ) -> Result<Self, ConnectError> {
+        let target = target.into();
+        let runtime = Runtime::start();
+        let inner = runtime.block_on(async move {
+            crate::CheckClient::create_with_tls(target, tls_config).await
+        })?;
+        Ok(CheckClient {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Builds the client with every setting of `builder` (timeouts,
+    /// observers, …).
+    pub fn from_builder(builder: &NioClientBuilder) -> Result<Self, BuildError> {
+        let builder = builder.clone();
+        let runtime = Runtime::start();
+        let inner = runtime.block_on(async move { builder.build_check_client().await })?;
+        Ok(CheckClient {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Runs `call` on a clone of the async client.
+    fn run<T, F>(&self, call: impl FnOnce(crate::CheckClient) -> F) -> T
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.runtime.block_on(call(self.inner.clone()))
+    }
+
+    /// See [`crate::CheckClient::check`].
+    pub fn check(
+        &self,
+        ns: Namespace,
+        obj: Obj,
+        rel: Rel,
+        user_id: UserId,
+        timestamp: Option<Timestamp>,
+    ) -> Result<CheckResult, CallError> {
+        self.run(|mut c| async move { c.check(ns, obj, rel, user_id, timestamp).await })
+    }
+
+    /// See [`crate::CheckClient::list`].
+    pub fn list(
+        &self,
+        ns: Namespace,
+        rel: Rel,
+        user_id: UserId,
+        timestamp: Option<Timestamp>,
+    ) -> Result<ListResult, CallError> {
+        self.run(|mut c| async move { c.list(ns, rel, user_id, timestamp).await })
+    }
+
+    /// See [`crate::CheckClient::expand`].
+    pub fn expand(
+        &self,
+        ns: Namespace,
+        obj: Obj,
+        rel: Rel,
+        timestamp: Option<Timestamp>,
+    ) -> Result<ExpandResult, ReadError> {
+        self.run(|mut c| async move { c.expand(ns, obj, rel, timestamp).await })
+    }
+
+    /// See [`crate::CheckClient::content_change_check`].
+    pub fn content_change_check(
+        &self,
+        ns: Namespace,
+        obj: Obj,
+        rel: Rel,
+        user_id: UserId,
+    ) -> Result<ContentChangeCheckResult, CallError> {
+        self.run(|mut c| async move { c.content_change_check(ns, obj, rel, user_id).await })
+    }
+
+    /// See [`crate::CheckClient::list_namespaces`].
+    pub fn list_namespaces(&self) -> Result<Vec<NamespaceMeta>, ReadError> {
+        self.run(|mut c| async move { c.list_namespaces().await })
+    }
+
+    /// See [`crate::CheckClient::get_all`].
+    pub fn get_all(&self, ns: &Namespace, obj: &Obj) -> Result<ReadResult, ReadError> {
+        let (ns, obj) = (ns.clone(), obj.clone());
+        self.run(|mut c| async move { c.get_all(&ns, &obj).await })
+    }
+
+    /// See [`crate::CheckClient::get_all_rel`].
+    pub fn get_all_rel(
+        &self,
+        ns: &Namespace,
+        obj: &Obj,
+        rel: &Rel,
+    ) -> Result<ReadResult, ReadError> {
+        let (ns, obj, rel) = (ns.clone(), obj.clone(), rel.clone());
+        self.run(|mut c| async move { c.get_all_rel(&ns, &obj, &rel).await })
+    }
+
+    /// See [`crate::CheckClient::read_by_user`].
+    pub fn read_by_user(
+        &self,
+        ns: &Namespace,
+        user_id: &UserId,
+        rel: Option<Rel>,
+    ) -> Result<ReadResult, ReadError> {
+        let (ns, user_id) = (ns.clone(), user_id.clone());
+        self.run(|mut c| async move { c.read_by_user(&ns, &user_id, rel).await })
+    }
+
+    /// See [`crate::CheckClient::read_by_user_set`].
+    pub fn read_by_user_set(
+        &self,
+        ns: &Namespace,
+        user_set: &UserSet,
+        rel: Option<Rel>,
+    ) -> Result<ReadResult, ReadError> {
+        let (ns, user_set) = (ns.clone(), user_set.clone());
+        self.run(|mut c| async move { c.read_by_user_set(&ns, &user_set, rel).await })
+    }
+
+    /// See [`crate::CheckClient::read`].
+    pub fn read(&self, filters: Vec<ReadFilter>) -> Result<ReadResult, ReadError> {
+        self.run(|mut c| async move { c.read(filters).await })
+    }
+
+    /// See [`crate::CheckClient::read_with_timestamp`].
+    pub fn read_with_timestamp(
+        &self,
+        ts: Timestamp,
+        filters: Vec<ReadFilter>,
+    ) -> Result<ReadResult, ReadError> {
+        self.run(|mut c| async move { c.read_with_timestamp(ts, filters).await })
+    }
+
+    /// See [`crate::CheckClient::write`].
+    pub fn write(
+        &self,
+        add: Vec<Tuple>,
+        del: Vec<Tuple>,
+        precondition: Option<Timestamp>,
+    ) -> Result<Timestamp, WriteError> {
+        self.run(|mut c| async move { c.write(add, del, precondition).await })
+    }
+
+    /// See [`crate::CheckClient::add_one`].
+    pub fn add_one(&self, tuple: Tuple) -> Result<Timestamp, WriteError> {
+        self.run(|mut c| async move { c.add_one(tuple).await })
+    }
+
+    /// See [`crate::CheckClient::add_many`].
+    pub fn add_many(&self, tuples: Vec<Tuple>) -> Result<Timestamp, WriteError> {
+        self.run(|mut c| async move { c.add_many(tuples).await })
+    }
+
+    /// See [`crate::CheckClient::add_parent`].
+    pub fn add_parent(
+        &self,
+        ns: Namespace,
+        obj: Obj,
+        parent_ns: Namespace,
+        parent_obj: Obj,
+    ) -> Result<Timestamp, WriteError> {
+        self.run(|mut c| async move { c.add_parent(ns, obj, parent_ns, parent_obj).await })
+    }
+
+    /// See [`crate::CheckClient::delete_one`].
+    pub fn delete_one(&self, tuple: Tuple) -> Result<Timestamp, WriteError> {
+        self.run(|mut c| async move { c.delete_one(tuple).await })
+    }
+}
@@ -0,0 +1,499 @@
+//! One place for every connection and behavior knob.
+//!
+//! [`NioClientBuilder`] builds the [`CheckClient`], the session resolver
+//! and (with the `axum` feature) the `AuthState` from the same settings:
+//! endpoints, TLS, keepalive, timeouts, concurrency limit, gzip, message
+//! size, user agent, observers and the session cache. Set them one by one,
+//! or start from a [`NioClientConfig`]. With the `serde` feature the config
+//! deserializes from any serde format. The library still reads no
+//! environment variables; the process loads the config.
+//!
+//! ```rust,ignore
+//! let config: NioClientConfig = serde_json::from_str(r#"{
+//!     "check": "unix:///run/nio/check.sock",
+//!     "session": "https://sessions.internal:50052",
+//!     "tls": { "ca": "/certs/ca.crt" },
+//!     "request_timeout": "2s",
+//!     "cache": { "l1_ttl": "30s" }
+//! }"#)?;
+//! let builder = NioClientBuilder::from_config(config).with_rpc_observer(observer);
+//! let auth = builder.build_auth_state(None).await?;
+//! ```
+
+use crate::audit::AuditSink;
+use crate::observe::RpcObserver;
+use crate::session::{GrpcSessionResolver, ResolverConfig, SessionResolver};
+use crate::tls::{ReloadableChannel, TlsError, TlsFiles, TlsReloadConfig, TlsSource};
+use crate::{
+    connect_with, transport, ChannelOptions, CheckClient, Codec, ConnectError, ErrorKind,
+    GrpcTransport, Keepalive, ObserveCheckFn, ObserveListFn, Target,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::transport::ClientTlsConfig;
+
+/// Settings for a [`NioClientBuilder`] that can live in a config file.
+/// Unset fields keep the library defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct NioClientConfig {
+    /// The check endpoint (CheckService, NamespaceService).
+    pub check: Option<Target>,
+    /// The nio-client endpoint (SessionService).
+    pub session: Option<Target>,
+    /// TLS for both endpoints; plaintext if unset.
+    pub tls: Option<TlsConfig>,
+    #[cfg_attr(feature = "serde", serde(with = "humantime_serde"))]
+    pub keepalive_interval: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(with = "humantime_serde"))]
+    pub keepalive_timeout: Option<Duration>,
+    pub keepalive_while_idle: Option<bool>,
+    #[cfg_attr(feature = "serde", serde(with = "humantime_serde"))]
+    pub connect_timeout: Option<Duration>,
+    /// Deadline of each RPC; a `watch` stream must open within it.
+    #[cfg_attr(feature = "serde", serde(with = "humantime_serde"))]
+    pub request_timeout: Option<Duration>,
+    /// Most RPCs in flight per channel.
+    pub concurrency_limit: Option<usize>,
+    pub gzip: bool,
+    pub max_message_size: Option<usize>,
+    pub user_agent: Option<String>,
+    pub cache: CacheConfig,
+}
+
+/// PEM files for [`NioClientConfig::tls`]. `cert` and `key` go together
+/// (mTLS); `ca` alone verifies the server only.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct TlsConfig {
+    pub ca: Option<PathBuf>,
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    /// Server name to verify instead of the endpoint's host.
+    pub domain: Option<String>,
+    /// Re-read the files this often and reconnect when they change (see
+    /// [`NioClientBuilder::with_tls_reload`]); read once if unset.
+    #[cfg_attr(feature = "serde", serde(with = "humantime_serde"))]
+    pub reload_interval: Option<Duration>,
+}
+
+/// The session cache part of [`NioClientConfig`]; see [`ResolverConfig`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct CacheConfig {
+    pub capacity: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(with = "humantime_serde"))]
+    pub l1_ttl: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(with = "humantime_serde"))]
+    pub neg_ttl: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(with = "humantime_serde"))]
+    pub stale_if_error: Option<Duration>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BuildError {
+    #[error("no {0} endpoint configured")]
+    MissingTarget(&'static str),
+    #[error("invalid TLS config: {0}")]
+    InvalidTls(&'static str),
+    #[error(transparent)]
+    Tls(#[from] TlsError),
+    #[error(transparent)]
+    Connect(#[from] ConnectError),
+}
+
+impl BuildError {
+    /// Missing or inconsistent settings are [`ErrorKind::InvalidArgument`];
+    /// TLS and connect failures report their own kind.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            BuildError::MissingTarget(_) | BuildError::InvalidTls(_) => ErrorKind::InvalidArgument,
+            BuildError::Tls(e) => e.kind(),
+            BuildError::Connect(e) => e.kind(),
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Tls {
+    Config(ClientTlsConfig),
+    Files(TlsFiles),
+    Paths(TlsConfig),
+}
+
+/// Builds the check client, the session resolver and `AuthState` from one
+/// set of settings. Each `build_*` call connects its own channel.
+#[derive(Clone, Default)]
+pub struct NioClientBuilder {
+    check: Option<Target>,
+    session: Option<Target>,
+    tls: Option<Tls>,
+    tls_reload: Option<TlsReloadConfig>,
+    channel: ChannelOptions,
+    codec: Codec,
+    observe_check: Option<ObserveCheckFn>,
+    observe_list: Option<ObserveListFn>,
+    rpc_observer: Option<Arc<dyn RpcObserver>>,
+    audit: Option<Arc<dyn AuditSink>>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<crate::metrics::Metrics>,
+    resolver: ResolverConfig,
+}
+
+impl std::fmt::Debug for NioClientBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NioClientBuilder")
+            .field("check", &self.check)
+            .field("session", &self.session)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NioClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder with the settings of `config`. TLS files are read when a
+    /// channel is built, and again every `tls.reload_interval` if set.
+    pub fn from_config(config: NioClientConfig) -> Self {
+        let defaults = Keepalive::default();
+        let mut resolver = ResolverConfig::default();
+        let cache = config.cache;
+        resolver.capacity = cache.capacity.unwrap_or(resolver.capacity);
+        resolver.l1_ttl = cache.l1_ttl.unwrap_or(resolver.l1_ttl);
+        resolver.neg_ttl = cache.neg_ttl.unwrap_or(resolver.neg_ttl);
+        resolver.stale_if_error = cache.stale_if_error.unwrap_or(resolver.stale_if_error);
+        let tls_reload = config
+            .tls
+            .as_ref()
+            .and_then(|tls| tls.reload_interval)
+            .map(|poll_interval| TlsReloadConfig { poll_interval });
+        NioClientBuilder {
+            check: config.check,
+            session: config.session,
+            tls: config.tls.map(Tls::Paths),
+            tls_reload,
+            channel: ChannelOptions {
+                keepalive: Keepalive {
+                    interval: config.keepalive_interval.unwrap_or(defaults.interval),
+                    timeout: config.keepalive_timeout.unwrap_or(defaults.timeout),
+                    while_idle: config.keepalive_while_idle.unwrap_or(defaults.while_idle),
+                },
+                connect_timeout: config.connect_timeout,
+                request_timeout: config.request_timeout,
+                concurrency_limit: config.concurrency_limit,
+                user_agent: config.user_agent,
+            },
+            codec: Codec {
+                gzip: config.gzip,
+                max_message_size: config.max_message_size,
+            },
+            resolver,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_check(mut self, target: impl Into<Target>) -> Self {
+        self.check = Some(target.into());
+        self
+    }
+
+    pub fn with_session(mut self, target: impl Into<Target>) -> Self {
+        self.session = Some(target.into());
+        self
+    }
+
+    pub fn with_tls(mut self, config: ClientTlsConfig) -> Self {
+        self.tls = Some(Tls::Config(config));
+        self
+    }
+
+    /// TLS from PEM files, read when each channel is built. The material
+    /// is fixed for the life of the client unless
+    /// [`Self::with_tls_reload`] is set.
+    pub fn with_tls_files(mut self, files: TlsFiles) -> Self {
+        self.tls = Some(Tls::Files(files));
+        self
+    }
+
+    /// Builds each channel as a [`ReloadableChannel`] over the TLS files,
+    /// so rotated certificates are picked up without a restart. Needs
+    /// [`Self::with_tls_files`] or TLS paths in the config.
+    pub fn with_tls_reload(mut self, cfg: TlsReloadConfig) -> Self {
+        self.tls_reload = Some(cfg);
+        self
+    }
+
+    pub fn with_keepalive(mut self, keepalive: Keepalive) -> Self {
+        self.channel.keepalive = keepalive;
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.channel.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Deadline of each RPC; a `watch` stream must open within it.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.channel.request_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_concurrency_limit(mut self, limit: usize) -> Self {
+        self.channel.concurrency_limit = Some(limit);
+        self
+    }
+
+    /// See [`CheckClient::with_gzip`]; applies to the session channel too.
+    pub fn with_gzip(mut self, enabled: bool) -> Self {
+        self.codec.gzip = enabled;
+        self
+    }
+
+    /// See [`CheckClient::with_max_message_size`].
+    pub fn with_max_message_size(mut self, bytes: usize) -> Self {
+        self.codec.max_message_size = Some(bytes);
+        self
+    }
+
+    /// Prepended to tonic's own user agent.
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.channel.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// See [`CheckClient::with_observe_check`].
+    pub fn with_observe_check(mut self, f: ObserveCheckFn) -> Self {
+        self.observe_check = Some(f);
+        self
+    }
+
+    /// See [`CheckClient::with_observe_list`].
+    pub fn with_observe_list(mut self, f: ObserveListFn) -> Self {
+        self.observe_list = Some(f);
+        self
+    }
+
+    /// See [`CheckClient::with_rpc_observer`].
+    pub fn with_rpc_observer(mut self, observer: Arc<dyn RpcObserver>) -> Self {
+        self.rpc_observer = Some(observer);
+        self
+    }
+
+    /// See [`CheckClient::with_audit`].
+    pub fn with_audit(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit = Some(sink);
+        self
+    }
+
+    /// Installs `metrics` on the check client and the session cache.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: crate::metrics::Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// The session cache tunables.
+    pub fn with_resolver_config(mut self, cfg: ResolverConfig) -> Self {
+        self.resolver = cfg;
+        self
+    }
+
+    pub async fn build_check_client(&self) -> Result<CheckClient, BuildError> {
+        let target = self
+            .check
+            .clone()
+            .ok_or(BuildError::MissingTarget("check"))?;
+        let mut client =
+            CheckClient::from_transport(self.transport(target).await?).with_gzip(self.codec.gzip);
+        if let Some(bytes) = self.codec.max_message_size {
+            client = client.with_max_message_size(bytes);
+        }
+        if let Some(f) = &self.observe_check {
+            client = client.with_observe_check(f.clone());
+        }
+        if let Some(f) = &self.observe_list {
+            client = client.with_observe_list(f.clone());
+        }
+        if let Some(observer) = &self.rpc_observer {
+            client = client.with_rpc_observer(observer.clone());
+        }
+        if let Some(sink) = &self.audit {
+            client = client.with_audit(sink.clone());
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            client = client.with_metrics(metrics.clone());
+        }
+        Ok(client)
+    }
+
+    pub async fn build_session_resolver(&self) -> Result<Arc<dyn SessionResolver>, BuildError> {
+        let target = self
+            .session
+            .clone()
+            .ok_or(BuildError::MissingTarget("session"))?;
+        let transport = self.transport(target).await?;
+        #[allow(unused_mut)]
+        let mut resolver =
+            GrpcSessionResolver::with_codec(transport, self.resolver.clone(), self.codec);
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            resolver = resolver.with_metrics(metrics.clone());
+        }
+        Ok(resolver.into_dyn())
+    }
+
+    /// Builds both clients into an `AuthState`; see `AuthState::new` for
+    /// `prefix`.
+    #[cfg(feature = "axum")]
+    pub async fn build_auth_state(
+        &self,
+        prefix: Option<&str>,
+    ) -> Result<crate::axum::AuthState, BuildError> {
+        Ok(crate::axum::AuthState::new(
+            self.build_check_client().await?,
+            self.build_session_resolver().await?,
+            prefix,
+        ))
+    }
+
+    async fn transport(&self, target: Target) -> Result<GrpcTransport, BuildError> {
+        if let Some(cfg) = &self.tls_reload {
+            let files = self
+                .tls_files()?
+                .ok_or(BuildError::InvalidTls("reload needs TLS files or paths"))?;
+            let channel = ReloadableChannel::connect_with(
+                target,
+                Arc::new(files),
+                cfg.clone(),
+                self.channel.clone(),
+            )
+            .await?;
+            return Ok(transport(channel));
+        }
+        let tls = match (&self.tls, self.tls_files()?) {
+            (Some(Tls::Config(config)), _) => Some(config.clone()),
+            (_, Some(files)) => Some(files.load().await?.config()),
+            (_, None) => None,
+        };
+        Ok(transport(connect_with(target, tls, &self.channel).await?))
+    }
+
+    fn tls_files(&self) -> Result<Option<TlsFiles>, BuildError> {
+        match &self.tls {
+            None | Some(Tls::Config(_)) => Ok(None),
+            Some(Tls::Files(files)) => Ok(Some(files.clone())),
+            Some(Tls::Paths(paths)) => {
+                let mut files = match (&paths.cert, &paths.key, &paths.ca) {
+                    (Some(cert), Some(key), _) => TlsFiles::new(cert, key),
+                    (None, None, Some(ca)) => TlsFiles::ca_only(ca),
+                    (None, None, None) => {
+                        return Err(BuildError::InvalidTls("needs `ca` or `cert` and `key`"))
+                    }
+                    _ => return Err(BuildError::InvalidTls("`cert` and `key` go together")),
+                };
+                if let Some(ca) = &paths.ca {
+                    files = files.with_ca(ca);
+                }
+                if let Some(domain) = &paths.domain {
+                    files = files.with_domain(domain);
+                }
+                Ok(Some(files))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_overrides_only_what_it_sets() {
+        let builder = NioClientBuilder::from_config(NioClientConfig {
+            keepalive_interval: Some(Duration::from_secs(5)),
+            max_message_size: Some(1024),
+            cache: CacheConfig {
+                l1_ttl: Some(Duration::from_secs(10)),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert_eq!(builder.channel.keepalive.interval, Duration::from_secs(5));
+        assert_eq!(
+            builder.channel.keepalive.timeout,
+            Keepalive::default().timeout
+        );
+        assert!(builder.channel.keepalive.while_idle);
+        assert_eq!(builder.codec.max_message_size, Some(1024));
+        assert!(!builder.codec.gzip);
+        assert_eq!(builder.resolver.l1_ttl, Duration::from_secs(10));
+        assert_eq!(
+            builder.resolver.capacity,
+            ResolverConfig::default().capacity
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_deserializes_targets_and_durations() {
+        let config: NioClientConfig = serde_json::from_str(
+            r#"{
+                "check": "unix:///run/nio/check.sock",
+                "session": "https://sessions.internal:50052",
+                "tls": { "ca": "/certs/ca.crt" },
+                "request_timeout": "2s",
+                "keepalive_interval": "500ms",
+                "gzip": true,
+                "cache": { "stale_if_error": "1m" }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            NioClientConfig {
+                check: Some(Target::Unix("/run/nio/check.sock".into())),
+                session: Some(Target::Uri(
+                    "https://sessions.internal:50052".parse().unwrap()
+                )),
+                tls: Some(TlsConfig {
+                    ca: Some("/certs/ca.crt".into()),
+                    ..Default::default()
+                }),
+                request_timeout: Some(Duration::from_secs(2)),
+                keepalive_interval: Some(Duration::from_millis(500)),
+                gzip: true,
+                cache: CacheConfig {
+                    stale_if_error: Some(Duration::from_secs(60)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+        );
+
+        let err = serde_json::from_str::<NioClientConfig>(r#"{ "check": "unix://" }"#)
+            .expect_err("empty socket path");
+        assert!(err.to_string().contains("target"), "{err}");
+        assert!(serde_json::from_str::<NioClientConfig>(r#"{ "chekc": "x" }"#).is_err());
+    }
+}
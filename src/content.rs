@@ -0,0 +1,93 @@
+//! Content-versioned authorization — both halves of the New Enemy protocol
+//! (paper §2.2).
+//!
+//! An update is authorized with [`CheckClient::content_change_check`] at the
+//! freshest snapshot; its zookie is persisted with the new content version
+//! ([`CheckClient::update_content`]). A later read checks at a snapshot at
+//! least as fresh as that stored zookie ([`CheckClient::read_content`]), so
+//! an ACL change made before the update can never be missed when the
+//! content is shown.
+
+use crate::auth::{CallError, CheckResult};
+use crate::{zookie, CheckClient, Namespace, Obj, Rel, Timestamp, UserId};
+use std::convert::Infallible;
+use std::future::Future;
+
+/// Content stored together with the zookie its modification was authorized
+/// at. Persist both; load both.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentVersion<T> {
+    pub content: T,
+    pub zookie: Timestamp,
+}
+
+impl<T> ContentVersion<T> {
+    pub fn new(content: T, zookie: Timestamp) -> Self {
+        ContentVersion { content, zookie }
+    }
+}
+
+/// Why a content update or read did not go through. `E` is the error of the
+/// caller's persist step.
+#[derive(Debug, thiserror::Error)]
+pub enum ContentError<E = Infallible> {
+    #[error("content access forbidden")]
+    Forbidden,
+    #[error("content check: {0}")]
+    Check(#[source] CallError),
+    #[error("persist content: {0}")]
+    Persist(#[source] E),
+}
+
+impl CheckClient {
+    /// Update flow: authorizes `user_id` for `rel` on ⟨ns, obj⟩ with
+    /// [`Self::content_change_check`] and, only if granted, calls `persist`
+    /// with the zookie to store alongside the new content. Returns the
+    /// persisted version.
+    pub async fn update_content<T, E, F, Fut>(
+        &mut self,
+        ns: Namespace,
+        obj: Obj,
+        rel: Rel,
+        user_id: UserId,
+        persist: F,
+    ) -> Result<ContentVersion<T>, ContentError<E>>
+    where
+        F: FnOnce(Timestamp) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let result = self
+            .content_change_check(ns, obj, rel, user_id)
+            .await
+            .map_err(ContentError::Check)?;
+        if !result.ok {
+            return Err(ContentError::Forbidden);
+        }
+        let content = persist(result.ts.clone())
+            .await
+            .map_err(ContentError::Persist)?;
+        zookie::record(&result.ts);
+        Ok(ContentVersion::new(content, result.ts))
+    }
+
+    /// Read flow: checks `rel` on ⟨ns, obj⟩ at a snapshot at least as fresh
+    /// as the version's stored zookie (or the request's zookie, if fresher)
+    /// and hands back the content only if granted.
+    pub async fn read_content<T>(
+        &mut self,
+        ns: Namespace,
+        obj: Obj,
+        rel: Rel,
+        user_id: UserId,
+        version: ContentVersion<T>,
+    ) -> Result<T, ContentError> {
+        let ts = zookie::at_least(version.zookie);
+        match self.check(ns, obj, rel, user_id, Some(ts)).await {
+            Ok(CheckResult::Ok(_)) => Ok(version.content),
+            Ok(CheckResult::Forbidden(_) | CheckResult::UnknownPutativeUser) => {
+                Err(ContentError::Forbidden)
+            }
+            Err(err) => Err(ContentError::Check(err)),
+        }
+    }
+}
@@ -0,0 +1,443 @@
+//! Caller credentials for the check and session services.
+//!
+//! A [`CredentialProvider`] yields a bearer [`Token`]; [`CredentialLayer`]
+//! sends it as `authorization: Bearer …` metadata on every RPC of the
+//! transport it wraps. The token is cached and fetched again once 80% of
+//! its lifetime has passed; while that refresh fails, calls keep using the
+//! current token until it expires. Concurrent calls share one refresh.
+//!
+//! Providers: [`StaticToken`], [`FileToken`] (re-read periodically, e.g. a
+//! projected service-account token) and [`CallbackToken`].
+//!
+//! A provider failure fails the call with [`CredentialError`], surfaced as
+//! the `Credentials` variant of the call's error type rather than as a
+//! server status.
+//!
+//! ```rust,ignore
+//! let credentials = Arc::new(FileToken::new("/var/run/secrets/nio/token"));
+//! let check_client = CheckClient::from_channel(channel).with_credentials(credentials);
+//! ```
+
+use crate::ErrorKind;
+use futures::future::BoxFuture;
+use http::header::AUTHORIZATION;
+use http::HeaderValue;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Why no credential could be attached to a call.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum CredentialError {
+    #[error("read credential file {path}: {message}")]
+    File { path: PathBuf, message: String },
+    #[error("credential provider: {0}")]
+    Provider(String),
+    /// The token is empty or not a valid header value.
+    #[error("invalid credential token")]
+    InvalidToken,
+    /// The cached token expired and could not be refreshed.
+    #[error("credential expired: {0}")]
+    Expired(Box<CredentialError>),
+}
+
+impl CredentialError {
+    /// Always [`ErrorKind::Unauthenticated`].
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::Unauthenticated
+    }
+
+    /// The credential failure a call failed with, if any.
+    pub(crate) fn of(status: &tonic::Status) -> Option<CredentialError> {
+        std::error::Error::source(status)?
+            .downcast_ref::<CredentialError>()
+            .cloned()
+    }
+}
+
+/// A bearer token and, if it has one, the instant it stops being valid.
+#[derive(Clone)]
+pub struct Token {
+    secret: String,
+    expires_at: Option<Instant>,
+}
+
+impl Token {
+    /// A token that never expires.
+    pub fn new(secret: impl Into<String>) -> Self {
+        Token {
+            secret: secret.into(),
+            expires_at: None,
+        }
+    }
+
+    pub fn with_expires_in(mut self, ttl: Duration) -> Self {
+        self.expires_at = Some(Instant::now() + ttl);
+        self
+    }
+
+    pub fn with_expires_at(mut self, at: Instant) -> Self {
+        self.expires_at = Some(at);
+        self
+    }
+
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.expires_at
+    }
+}
+
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Token")
+            .field("secret", &"<redacted>")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// Source of the caller's bearer token. Called again when the cached token
+/// nears its expiry.
+pub trait CredentialProvider: Send + Sync + 'static {
+    fn token(&self) -> BoxFuture<'_, Result<Token, CredentialError>>;
+}
+
+/// A fixed token.
+pub struct StaticToken(Token);
+
+impl StaticToken {
+    pub fn new(secret: impl Into<String>) -> Self {
+        StaticToken(Token::new(secret))
+    }
+}
+
+impl CredentialProvider for StaticToken {
+    fn token(&self) -> BoxFuture<'_, Result<Token, CredentialError>> {
+        let token = self.0.clone();
+        Box::pin(async move { Ok(token) })
+    }
+}
+
+/// A token read from a file (trimmed) and re-read every `refresh` (default
+/// 60s), e.g. a token a sidecar rotates.
+pub struct FileToken {
+    path: PathBuf,
+    refresh: Duration,
+}
+
+impl FileToken {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileToken {
+            path: path.into(),
+            refresh: Duration::from_secs(60),
+        }
+    }
+
+    pub fn with_refresh(mut self, refresh: Duration) -> Self {
+        self.refresh = refresh;
+        self
+    }
+}
+
+impl CredentialProvider for FileToken {
+    fn token(&self) -> BoxFuture<'_, Result<Token, CredentialError>> {
+        let path = self.path.clone();
+        Box::pin(async move {
+            let read = {
+                let path = path.clone();
+                tokio::task::spawn_blocking(move || std::fs::read_to_string(path)).await
+            };
+            let secret = match read {
+                Ok(Ok(secret)) => secret,
+                Ok(Err(e)) => {
+                    return Err(CredentialError::File {
+                        path,
+                        message: e.to_string(),
+                    })
+                }
+                Err(e) => return Err(CredentialError::Provider(e.to_string())),
+            };
+            Ok(Token::new(secret.trim()).with_expires_in(self.refresh))
+        })
+    }
+}
+
+pub type TokenCallbackFn =
+    Arc<dyn Fn() -> BoxFuture<'static, Result<Token, CredentialError>> + Send + Sync>;
+
+/// A token from an application callback, e.g. an OAuth client-credentials
+/// exchange. Give the returned [`Token`] its expiry so it is refreshed.
+pub struct CallbackToken(TokenCallbackFn);
+
+impl CallbackToken {
+    pub fn new(f: TokenCallbackFn) -> Self {
+        CallbackToken(f)
+    }
+}
+
+impl CredentialProvider for CallbackToken {
+    fn token(&self) -> BoxFuture<'_, Result<Token, CredentialError>> {
+        (self.0)()
+    }
+}
+
+/// How long a failed refresh waits before the provider is asked again,
+/// while the current token is still valid.
+const REFRESH_RETRY: Duration = Duration::from_secs(5);
+
+/// The cached token of one provider, shared by all clones of a layer.
+struct Cache {
+    provider: Arc<dyn CredentialProvider>,
+    /// The current token with the instant to refresh it at.
+    slot: Mutex<Option<(Token, Option<Instant>)>>,
+}
+
+impl Cache {
+    async fn header(&self) -> Result<HeaderValue, CredentialError> {
+        let mut slot = self.slot.lock().await;
+        let now = Instant::now();
+        let due = match &*slot {
+            Some((_, refresh_at)) => refresh_at.is_some_and(|at| now >= at),
+            None => true,
+        };
+        if due {
+            match self.provider.token().await {
+                Ok(token) => {
+                    let refresh_at = token.expires_at.map(|at| {
+                        let lifetime = at.saturating_duration_since(now);
+                        now + lifetime.mul_f64(0.8)
+                    });
+                    *slot = Some((token, refresh_at));
+                }
+                Err(e) => match &mut *slot {
+                    Some((token, refresh_at)) if token.expires_at.is_some_and(|at| now < at) => {
+                        log::warn!(
+                            "nio-client: credential refresh failed, using current token: {e}"
+                        );
+                        // Not before expiry, so the last retry still sees
+                        // the token expire.
+                        let remaining = token
+                            .expires_at
+                            .map_or(REFRESH_RETRY, |at| at.saturating_duration_since(now));
+                        *refresh_at = Some(now + REFRESH_RETRY.min(remaining));
+                    }
+                    Some(_) => return Err(CredentialError::Expired(Box::new(e))),
+                    None => return Err(e),
+                },
+            }
+        }
+        let (token, _) = slot.as_ref().expect("credential slot filled above");
+        if token.secret.is_empty() {
+            return Err(CredentialError::InvalidToken);
+        }
+        let mut value = HeaderValue::try_from(format!("Bearer {}", token.secret))
+            .map_err(|_| CredentialError::InvalidToken)?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+}
+
+/// Tower layer adding the provider's token as `authorization` metadata.
+/// See [`crate::CheckClient::with_credentials`]; for the session resolver
+/// wrap its channel and use
+/// [`crate::session::GrpcSessionResolver::from_transport`].
+#[derive(Clone)]
+pub struct CredentialLayer {
+    cache: Arc<Cache>,
+}
+
+impl CredentialLayer {
+    pub fn new(provider: Arc<dyn CredentialProvider>) -> Self {
+        CredentialLayer {
+            cache: Arc::new(Cache {
+                provider,
+                slot: Mutex::new(None),
+            }),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for CredentialLayer {
+    type Service = CredentialService<S>;
+
+    fn layer(&self, inner: S) -> CredentialService<S> {
+        CredentialService {
+            inner,
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+/// Service produced by [`CredentialLayer`].
+#[derive(Clone)]
+pub struct CredentialService<S> {
+    inner: S,
+    cache: Arc<Cache>,
+}
+
+impl<S, B> tower::Service<http::Request<B>> for CredentialService<S>
+where
+    S: tower::Service<http::Request<B>> + Clone + Send + 'static,
+    S::Error: Into<tower::BoxError>,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = tower::BoxError;
+    type Future = BoxFuture<'static, Result<S::Response, tower::BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        // Call the instance that was polled ready; leave a fresh clone.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let cache = self.cache.clone();
+        Box::pin(async move {
+            match cache.header().await {
+                Ok(value) => {
+                    request.headers_mut().insert(AUTHORIZATION, value);
+                }
+                Err(e) => {
+                    let mut status = tonic::Status::unauthenticated(e.to_string());
+                    status.set_source(Arc::new(e));
+                    return Err(status.into());
+                }
+            }
+            inner.call(request).await.map_err(Into::into)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Provider handing out `t1`, `t2`, … valid for `ttl`, or failing.
+    struct Counting {
+        calls: Arc<AtomicUsize>,
+        ttl: Duration,
+        fail: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl CredentialProvider for Counting {
+        fn token(&self) -> BoxFuture<'_, Result<Token, CredentialError>> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            let fail = self.fail.load(Ordering::SeqCst);
+            let ttl = self.ttl;
+            Box::pin(async move {
+                if fail {
+                    return Err(CredentialError::Provider("down".into()));
+                }
+                Ok(Token::new(format!("t{n}")).with_expires_in(ttl))
+            })
+        }
+    }
+
+    fn counting(ttl: Duration) -> (Cache, Arc<AtomicUsize>, Arc<std::sync::atomic::AtomicBool>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fail = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let cache = Cache {
+            provider: Arc::new(Counting {
+                calls: calls.clone(),
+                ttl,
+                fail: fail.clone(),
+            }),
+            slot: Mutex::new(None),
+        };
+        (cache, calls, fail)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refreshes_after_80_percent_of_the_lifetime() {
+        let (cache, calls, _) = counting(Duration::from_secs(100));
+        assert_eq!(cache.header().await.unwrap(), "Bearer t1");
+        tokio::time::advance(Duration::from_secs(79)).await;
+        assert_eq!(cache.header().await.unwrap(), "Bearer t1");
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert_eq!(cache.header().await.unwrap(), "Bearer t2");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keeps_the_current_token_while_a_refresh_fails() {
+        let (cache, _, fail) = counting(Duration::from_secs(100));
+        cache.header().await.unwrap();
+        fail.store(true, Ordering::SeqCst);
+        tokio::time::advance(Duration::from_secs(90)).await;
+        assert_eq!(cache.header().await.unwrap(), "Bearer t1");
+        tokio::time::advance(Duration::from_secs(20)).await;
+        assert_eq!(
+            cache.header().await,
+            Err(CredentialError::Expired(Box::new(
+                CredentialError::Provider("down".into())
+            )))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_refreshes_back_off_instead_of_calling_on_every_request() {
+        let (cache, calls, fail) = counting(Duration::from_secs(100));
+        cache.header().await.unwrap();
+        fail.store(true, Ordering::SeqCst);
+        tokio::time::advance(Duration::from_secs(81)).await;
+        for _ in 0..10 {
+            assert_eq!(cache.header().await.unwrap(), "Bearer t1");
+            tokio::time::advance(Duration::from_millis(400)).await;
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2, "one failed refresh");
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(cache.header().await.unwrap(), "Bearer t1");
+        assert_eq!(calls.load(Ordering::SeqCst), 3, "retried after the backoff");
+
+        fail.store(false, Ordering::SeqCst);
+        tokio::time::advance(REFRESH_RETRY).await;
+        assert_eq!(cache.header().await.unwrap(), "Bearer t4");
+    }
+
+    #[tokio::test]
+    async fn static_token_never_refreshes() {
+        let cache = Cache {
+            provider: Arc::new(StaticToken::new("s3cret")),
+            slot: Mutex::new(None),
+        };
+        assert_eq!(cache.header().await.unwrap(), "Bearer s3cret");
+        assert!(cache.header().await.unwrap().is_sensitive());
+    }
+
+    #[tokio::test]
+    async fn empty_or_unreadable_tokens_are_errors() {
+        let cache = Cache {
+            provider: Arc::new(StaticToken::new("")),
+            slot: Mutex::new(None),
+        };
+        assert_eq!(cache.header().await, Err(CredentialError::InvalidToken));
+
+        let missing = FileToken::new("/nonexistent/nio-token");
+        assert!(matches!(
+            missing.token().await,
+            Err(CredentialError::File { .. })
+        ));
+    }
+
+    #[test]
+    fn token_debug_redacts_the_secret() {
+        assert!(!format!("{:?}", Token::new("s3cret")).contains("s3cret"));
+    }
+
+    #[test]
+    fn finds_the_credential_error_behind_a_status() {
+        let mut status = tonic::Status::unauthenticated("x");
+        status.set_source(Arc::new(CredentialError::InvalidToken));
+        assert_eq!(
+            CredentialError::of(&status),
+            Some(CredentialError::InvalidToken)
+        );
+        assert_eq!(CredentialError::of(&tonic::Status::internal("x")), None);
+    }
+}
@@ -1,5 +1,6 @@
 use std::fmt::{Display, Formatter};
 
+use crate::credentials::CredentialError;
 use tonic::transport::Error;
 use tonic::Status;
 
@@ -17,62 +18,237 @@ pub struct ParseError {
     source: ParseErrorKind,
 }
 
+impl ParseError {
+    pub(crate) fn new(
+        item: &str,
+        value: &str,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        ParseError {
+            item: item.into(),
+            value: value.into(),
+            source: ParseErrorKind::InvalidSyntaxWithInner(source.into()),
+        }
+    }
+}
+
+/// What went wrong, whichever client call failed. Every client error
+/// reports one through `kind()`; match on it instead of digging out the
+/// `tonic::Status` code. `ContentError` is the exception: it carries the
+/// caller's own persist error, so only its `Check` variant has a kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// A write's precondition zookie no longer holds (`FAILED_PRECONDITION`,
+    /// `ABORTED`); re-read and retry the write on top.
+    PreconditionFailed,
+    /// The server rejected the request (`INVALID_ARGUMENT`, `OUT_OF_RANGE`).
+    InvalidArgument,
+    /// `NOT_FOUND`, e.g. an unknown namespace.
+    NotFound,
+    /// The caller's credential is missing or was refused
+    /// (`UNAUTHENTICATED`, a failing credential provider).
+    Unauthenticated,
+    /// The caller may not make this call (`PERMISSION_DENIED`).
+    PermissionDenied,
+    /// Rate limited or over quota (`RESOURCE_EXHAUSTED`).
+    ResourceExhausted,
+    /// The service could not be reached (`UNAVAILABLE`, connect failures).
+    Unavailable,
+    /// `DEADLINE_EXCEEDED`.
+    DeadlineExceeded,
+    /// The server broke the API contract: a response the client cannot map,
+    /// or a method it does not implement (`UNIMPLEMENTED`).
+    ContractViolation,
+    /// Any other server fault (`INTERNAL`, `UNKNOWN`, `DATA_LOSS`,
+    /// `CANCELLED`).
+    Internal,
+}
+
+impl ErrorKind {
+    pub fn of(code: tonic::Code) -> ErrorKind {
+        use tonic::Code;
+        match code {
+            Code::FailedPrecondition | Code::Aborted => ErrorKind::PreconditionFailed,
+            Code::InvalidArgument | Code::OutOfRange => ErrorKind::InvalidArgument,
+            Code::NotFound => ErrorKind::NotFound,
+            Code::Unauthenticated => ErrorKind::Unauthenticated,
+            Code::PermissionDenied => ErrorKind::PermissionDenied,
+            Code::ResourceExhausted => ErrorKind::ResourceExhausted,
+            Code::Unavailable => ErrorKind::Unavailable,
+            Code::DeadlineExceeded => ErrorKind::DeadlineExceeded,
+            Code::Unimplemented => ErrorKind::ContractViolation,
+            Code::Ok
+            | Code::Cancelled
+            | Code::Unknown
+            | Code::AlreadyExists
+            | Code::Internal
+            | Code::DataLoss => ErrorKind::Internal,
+        }
+    }
+
+    /// Whether the same call may succeed if retried after a backoff:
+    /// `Unavailable`, `DeadlineExceeded` and `ResourceExhausted`. A failed
+    /// precondition needs a fresh read first, so it is not.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorKind::Unavailable | ErrorKind::DeadlineExceeded | ErrorKind::ResourceExhausted
+        )
+    }
+}
+
+/// Formats a status as `Code: message` for error messages.
+pub(crate) struct StatusDisplay<'a>(pub &'a Status);
+
+impl Display for StatusDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0.message() {
+            "" => write!(f, "{:?}", self.0.code()),
+            message => write!(f, "{:?}: {message}", self.0.code()),
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct ConnectError(pub(super) Error);
+pub struct ConnectError {
+    source: Error,
+    kind: ErrorKind,
+}
+
+impl ConnectError {
+    /// The endpoint settings are unusable (target, user agent, TLS
+    /// config): [`ErrorKind::InvalidArgument`], never retryable.
+    pub(crate) fn config(source: Error) -> Self {
+        ConnectError {
+            source,
+            kind: ErrorKind::InvalidArgument,
+        }
+    }
+
+    /// Connecting failed. tonic gives every transport failure an
+    /// underlying cause; a failure without one is a setting it could only
+    /// reject at connect time (e.g. a TLS target without a host).
+    pub(crate) fn connect(source: Error) -> Self {
+        let kind = match std::error::Error::source(&source) {
+            Some(_) => ErrorKind::Unavailable,
+            None => ErrorKind::InvalidArgument,
+        };
+        ConnectError { source, kind }
+    }
+
+    /// [`ErrorKind::Unavailable`] when the service could not be reached,
+    /// [`ErrorKind::InvalidArgument`] for settings that can never connect.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
+}
 
 impl Display for ConnectError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "connect to check service")
+        write!(f, "connect to check service: {}", self.source)
     }
 }
 
 impl std::error::Error for ConnectError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        Some(&self.0)
+        Some(&self.source)
     }
 }
 
+/// Errors from Write.
 #[derive(Debug)]
-pub struct WriteError(pub(super) Status);
+#[non_exhaustive]
+pub enum WriteError {
+    /// gRPC transport or server status.
+    Grpc(Status),
+    /// No caller credential could be attached; nothing was written.
+    Credentials(CredentialError),
+}
+
+impl WriteError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            WriteError::Grpc(status) => ErrorKind::of(status.code()),
+            WriteError::Credentials(_) => ErrorKind::Unauthenticated,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
+}
 
 impl Display for WriteError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "write tuples grpc call")
+        match self {
+            WriteError::Grpc(status) => {
+                write!(f, "write tuples grpc call: {}", StatusDisplay(status))
+            }
+            WriteError::Credentials(e) => write!(f, "write credentials: {e}"),
+        }
     }
 }
 
 impl std::error::Error for WriteError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        Some(&self.0)
+        match self {
+            WriteError::Grpc(status) => Some(status),
+            WriteError::Credentials(e) => Some(e),
+        }
     }
 }
 
 impl From<Status> for WriteError {
     fn from(value: Status) -> Self {
-        WriteError(value)
+        match CredentialError::of(&value) {
+            Some(e) => WriteError::Credentials(e),
+            None => WriteError::Grpc(value),
+        }
     }
 }
 
 /// Errors from Read / Expand / Watch / list-namespaces and response decoding.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ReadError {
     /// gRPC transport or server status.
     Grpc(Status),
     /// Server returned a protobuf we cannot map (e.g. missing `Tuple.user`).
     InvalidResponse(String),
+    /// No caller credential could be attached; no RPC was made.
+    Credentials(CredentialError),
 }
 
 impl ReadError {
     pub fn invalid_response(msg: impl Into<String>) -> Self {
         ReadError::InvalidResponse(msg.into())
     }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ReadError::Grpc(status) => ErrorKind::of(status.code()),
+            ReadError::InvalidResponse(_) => ErrorKind::ContractViolation,
+            ReadError::Credentials(_) => ErrorKind::Unauthenticated,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
 }
 
 impl Display for ReadError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            ReadError::Grpc(_) => write!(f, "read tuples grpc call"),
+            ReadError::Grpc(status) => {
+                write!(f, "read tuples grpc call: {}", StatusDisplay(status))
+            }
             ReadError::InvalidResponse(msg) => write!(f, "invalid read response: {msg}"),
+            ReadError::Credentials(e) => write!(f, "read credentials: {e}"),
         }
     }
 }
@@ -82,12 +258,87 @@ impl std::error::Error for ReadError {
         match self {
             ReadError::Grpc(status) => Some(status),
             ReadError::InvalidResponse(_) => None,
+            ReadError::Credentials(e) => Some(e),
         }
     }
 }
 
 impl From<Status> for ReadError {
     fn from(value: Status) -> Self {
-        ReadError::Grpc(value)
+        match CredentialError::of(&value) {
+            Some(e) => ReadError::Credentials(e),
+            None => ReadError::Grpc(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::CallError;
+
+    #[test]
+    fn codes_map_to_kinds() {
+        use tonic::Code;
+        assert_eq!(
+            ErrorKind::of(Code::FailedPrecondition),
+            ErrorKind::PreconditionFailed
+        );
+        assert_eq!(ErrorKind::of(Code::Aborted), ErrorKind::PreconditionFailed);
+        assert_eq!(ErrorKind::of(Code::OutOfRange), ErrorKind::InvalidArgument);
+        assert_eq!(
+            ErrorKind::of(Code::Unimplemented),
+            ErrorKind::ContractViolation
+        );
+        assert_eq!(ErrorKind::of(Code::DataLoss), ErrorKind::Internal);
+        let retryable: Vec<_> = [
+            Code::Unavailable,
+            Code::DeadlineExceeded,
+            Code::ResourceExhausted,
+            Code::FailedPrecondition,
+            Code::PermissionDenied,
+            Code::Internal,
+        ]
+        .into_iter()
+        .filter(|code| ErrorKind::of(*code).is_retryable())
+        .collect();
+        assert_eq!(
+            retryable,
+            [
+                Code::Unavailable,
+                Code::DeadlineExceeded,
+                Code::ResourceExhausted
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_report_kind_and_display_code_and_message() {
+        let write = WriteError::from(Status::failed_precondition("stale zookie"));
+        assert_eq!(write.kind(), ErrorKind::PreconditionFailed);
+        assert!(!write.is_retryable());
+        assert_eq!(
+            write.to_string(),
+            "write tuples grpc call: FailedPrecondition: stale zookie"
+        );
+
+        let read = ReadError::from(Status::unavailable("connection refused"));
+        assert!(read.is_retryable());
+        assert_eq!(
+            read.to_string(),
+            "read tuples grpc call: Unavailable: connection refused"
+        );
+        assert_eq!(
+            ReadError::invalid_response("missing user").kind(),
+            ErrorKind::ContractViolation
+        );
+
+        let call = CallError::from(Status::deadline_exceeded(""));
+        assert_eq!(call.kind(), ErrorKind::DeadlineExceeded);
+        assert_eq!(call.to_string(), "call error: DeadlineExceeded");
+        assert_eq!(
+            CallError::UnexpectedResponseFormat.kind(),
+            ErrorKind::ContractViolation
+        );
     }
 }
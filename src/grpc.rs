@@ -0,0 +1,297 @@
+//! Authorizing our own tonic services with nio, the way the axum extractors
+//! authorize HTTP routes.
+//!
+//! [`NioAuthLayer`] is a tower layer for a tonic server. Per call it looks up
+//! the full method name (`/pkg.Service/Method`) in a [`GrpcPolicy`], reads
+//! the bearer token from the `authorization` metadata, resolves it through a
+//! [`SessionResolver`], and runs the check. The object comes from the
+//! decoded request message ([`GrpcResource`]) or is fixed per method.
+//! Failures are answered with `UNAUTHENTICATED` / `PERMISSION_DENIED`
+//! without reaching the service; on success the [`Principal`] is put in the
+//! request extensions:
+//!
+//! ```rust,ignore
+//! let policy = GrpcPolicy::new()
+//!     .unary::<GetDocRequest>("/docs.Docs/Get", Namespace("doc".into()), Rel::viewer())
+//!     .public("/grpc.health.v1.Health/Check");
+//! Server::builder()
+//!     .layer(NioAuthLayer::new(check_client, resolver, policy))
+//!     .add_service(DocsServer::new(docs))
+//!     .serve(addr)
+//!     .await?;
+//!
+//! // in the service
+//! let principal = request.extensions().get::<Principal>();
+//! ```
+//!
+//! Methods missing from the policy are denied. Only uncompressed unary
+//! requests can be authorized by message content, and their body is
+//! buffered up to [`NioAuthLayer::with_max_message_size`] before the check;
+//! a larger one is answered with `RESOURCE_EXHAUSTED`.
+//!
+//! [`Principal`]: crate::auth::Principal
+
+use crate::guard::{Guard, GuardOutcome};
+use crate::session::{token_hash, SessionResolver};
+use crate::{CheckClient, Namespace, Obj, Rel, UserId};
+use futures::future::BoxFuture;
+use headers::authorization::Bearer;
+use headers::{Authorization, HeaderMapExt};
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::Body;
+use tonic::Status;
+use tower::{Layer, Service};
+
+/// A request message that names the object it is about.
+pub trait GrpcResource {
+    fn object(&self) -> Obj;
+}
+
+type ObjectFn = Arc<dyn Fn(&[u8]) -> Result<Obj, prost::DecodeError> + Send + Sync>;
+
+#[derive(Clone)]
+enum Rule {
+    Public,
+    Fixed(Guard),
+    Message {
+        ns: Namespace,
+        rel: Rel,
+        object: ObjectFn,
+    },
+}
+
+/// Maps full gRPC method names to what a call must be allowed to do.
+#[derive(Clone, Default)]
+pub struct GrpcPolicy {
+    rules: HashMap<String, Rule>,
+}
+
+impl GrpcPolicy {
+    pub fn new() -> Self {
+        GrpcPolicy::default()
+    }
+
+    /// `method` needs no session and no check.
+    pub fn public(mut self, method: &str) -> Self {
+        self.rules.insert(method.to_string(), Rule::Public);
+        self
+    }
+
+    /// `method` requires `rel` on ⟨ns, obj⟩ for a fixed object; suits
+    /// streaming methods and service-wide gates.
+    pub fn fixed(mut self, method: &str, ns: Namespace, obj: Obj, rel: Rel) -> Self {
+        let rule = Rule::Fixed(Guard::check(ns, obj, rel));
+        self.rules.insert(method.to_string(), rule);
+        self
+    }
+
+    /// Unary `method` requires `rel` on ⟨ns, `M::object`⟩ of its request
+    /// message.
+    pub fn unary<M>(mut self, method: &str, ns: Namespace, rel: Rel) -> Self
+    where
+        M: prost::Message + Default + GrpcResource,
+    {
+        let object: ObjectFn = Arc::new(|buf| M::decode(buf).map(|m| m.object()));
+        let rule = Rule::Message { ns, rel, object };
+        self.rules.insert(method.to_string(), rule);
+        self
+    }
+}
+
+/// The default [`NioAuthLayer::with_max_message_size`]: 4 MiB, tonic's
+/// default decoding limit.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Tower layer authorizing tonic server calls per [`GrpcPolicy`].
+#[derive(Clone)]
+pub struct NioAuthLayer {
+    check_client: CheckClient,
+    resolver: Arc<dyn SessionResolver>,
+    policy: Arc<GrpcPolicy>,
+    max_message_size: usize,
+}
+
+impl NioAuthLayer {
+    pub fn new(
+        check_client: CheckClient,
+        resolver: Arc<dyn SessionResolver>,
+        policy: GrpcPolicy,
+    ) -> Self {
+        NioAuthLayer {
+            check_client,
+            resolver,
+            policy: Arc::new(policy),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Largest request message buffered to authorize by its content
+    /// ([`GrpcPolicy::unary`]); match the service's own decoding limit.
+    pub fn with_max_message_size(mut self, limit: usize) -> Self {
+        self.max_message_size = limit;
+        self
+    }
+
+    /// The request to forward, with the principal in its extensions, or the
+    /// status to answer with.
+    async fn authorize(&self, req: http::Request<Body>) -> Result<http::Request<Body>, Status> {
+        let method = req.uri().path();
+        let rule = match self.policy.rules.get(method) {
+            Some(rule) => rule.clone(),
+            None => {
+                log::debug!("nio-client: grpc method {method} not in policy");
+                return Err(Status::permission_denied("method not allowed by policy"));
+            }
+        };
+        if let Rule::Public = rule {
+            return Ok(req);
+        }
+        let token = match req.headers().typed_try_get::<Authorization<Bearer>>() {
+            Ok(Some(bearer)) => bearer.token().to_string(),
+            Ok(None) | Err(_) => return Err(Status::unauthenticated("missing bearer token")),
+        };
+        let user = match self.resolver.resolve(&token_hash(&token)).await {
+            Ok(Some(session)) => UserId(session.principal),
+            Ok(None) => return Err(Status::unauthenticated("unknown session")),
+            Err(err) => {
+                log::error!("nio-client: session resolve failed: {err}");
+                return Err(Status::unavailable("session resolution failed"));
+            }
+        };
+        let (mut req, guard) = match rule {
+            Rule::Public => unreachable!("handled above"),
+            Rule::Fixed(guard) => (req, guard),
+            Rule::Message { ns, rel, object } => {
+                let (parts, body) = req.into_parts();
+                // The frame header is not part of the message size.
+                let body = Limited::new(body, self.max_message_size.saturating_add(5));
+                let bytes = match body.collect().await {
+                    Ok(collected) => collected.to_bytes(),
+                    Err(err) if err.is::<LengthLimitError>() => {
+                        return Err(Status::resource_exhausted(format!(
+                            "request message larger than {} bytes",
+                            self.max_message_size
+                        )));
+                    }
+                    Err(err) => return Err(Status::internal(err.to_string())),
+                };
+                let obj = decode_object(&bytes, &object)?;
+                let req = http::Request::from_parts(parts, Body::new(Full::new(bytes)));
+                (req, Guard::check(ns, obj, rel))
+            }
+        };
+        match self.check_client.check_guard(&guard, user, None).await {
+            Ok(GuardOutcome::Granted(principal)) => {
+                req.extensions_mut().insert(principal);
+                Ok(req)
+            }
+            Ok(GuardOutcome::Denied(denial)) => {
+                log::debug!("nio-client: grpc call {denial}");
+                Err(Status::permission_denied("permission denied"))
+            }
+            Err(err) => {
+                log::error!("nio-client: check returned error: {err:?}");
+                Err(Status::unavailable("authorization check failed"))
+            }
+        }
+    }
+}
+
+/// The object named by a unary request body: exactly one uncompressed,
+/// length-prefixed message (`[compressed:u8][len:u32 BE][message]`).
+#[allow(clippy::result_large_err)] // answered as-is, never propagated further
+fn decode_object(frame: &[u8], object: &ObjectFn) -> Result<Obj, Status> {
+    let Some((header, message)) = frame.split_at_checked(5) else {
+        return Err(Status::invalid_argument("expected one request message"));
+    };
+    if header[0] != 0 {
+        return Err(Status::unimplemented(
+            "compressed requests cannot be authorized",
+        ));
+    }
+    let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if message.len() != len {
+        return Err(Status::invalid_argument("expected one request message"));
+    }
+    object(message).map_err(|_| Status::invalid_argument("undecodable request message"))
+}
+
+impl<S> Layer<S> for NioAuthLayer {
+    type Service = NioAuth<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        NioAuth {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// The service produced by [`NioAuthLayer`].
+#[derive(Clone)]
+pub struct NioAuth<S> {
+    inner: S,
+    layer: NioAuthLayer,
+}
+
+impl<S> Service<http::Request<Body>> for NioAuth<S>
+where
+    S: Service<http::Request<Body>, Response = http::Response<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = http::Response<Body>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        // The ready inner service handles this call; keep a fresh clone.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+        Box::pin(async move {
+            match layer.authorize(req).await {
+                Ok(req) => inner.call(req).await,
+                Err(status) => Ok(status.into_http()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object_fn() -> ObjectFn {
+        Arc::new(|buf| Ok(Obj(String::from_utf8_lossy(buf).into_owned())))
+    }
+
+    fn frame(compressed: u8, message: &[u8]) -> Vec<u8> {
+        let mut frame = vec![compressed];
+        frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        frame.extend_from_slice(message);
+        frame
+    }
+
+    #[test]
+    fn decodes_one_uncompressed_message() {
+        let obj = decode_object(&frame(0, b"d7"), &object_fn()).unwrap();
+        assert_eq!(obj, Obj("d7".into()));
+    }
+
+    #[test]
+    fn rejects_compressed_truncated_and_extra_frames() {
+        let code = |f: &[u8]| decode_object(f, &object_fn()).unwrap_err().code();
+        assert_eq!(code(&frame(1, b"d7")), tonic::Code::Unimplemented);
+        assert_eq!(code(&[0, 0, 0]), tonic::Code::InvalidArgument);
+        let mut two = frame(0, b"d7");
+        two.extend(frame(0, b"d8"));
+        assert_eq!(code(&two), tonic::Code::InvalidArgument);
+    }
+}
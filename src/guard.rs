@@ -0,0 +1,327 @@
+//! Boolean permission guards over several ⟨ns, obj, rel⟩ checks — "editor on
+//! the article AND viewer on its folder", "admin OR owner".
+//!
+//! A [`Guard`] is evaluated for one subject with every clause checked
+//! concurrently against the same freshness bound: the timestamp passed in,
+//! else the request's zookie (see [`crate::zookie`]), resolved once before
+//! the first clause. Check evaluates each clause at a snapshot at least that
+//! fresh and does not report which, so clauses are not pinned to one
+//! snapshot; without any bound each one sees whatever snapshot is current
+//! when it runs. Evaluation short-circuits: an
+//! `all` stops at its first denial, an `any` at its first grant, and the
+//! checks still in flight are dropped. A denial names the clause(s) that
+//! decided it.
+
+use crate::auth::{CallError, CheckResult, Principal};
+use crate::{CheckClient, Namespace, Obj, Rel, Timestamp, UserId};
+use futures::future::BoxFuture;
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::FutureExt;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+
+/// One ⟨ns, obj, rel⟩ question of a guard.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Clause {
+    pub ns: Namespace,
+    pub obj: Obj,
+    pub rel: Rel,
+}
+
+impl Display for Clause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}#{}", self.ns.0, self.obj.0, self.rel.0)
+    }
+}
+
+/// A boolean expression over checks. An empty `All` or `Any` never grants.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Guard {
+    Check(Clause),
+    All(Vec<Guard>),
+    Any(Vec<Guard>),
+}
+
+impl Guard {
+    pub fn check(ns: Namespace, obj: Obj, rel: Rel) -> Guard {
+        Guard::Check(Clause { ns, obj, rel })
+    }
+
+    pub fn all(guards: impl IntoIterator<Item = Guard>) -> Guard {
+        Guard::All(guards.into_iter().collect())
+    }
+
+    pub fn any(guards: impl IntoIterator<Item = Guard>) -> Guard {
+        Guard::Any(guards.into_iter().collect())
+    }
+
+    /// `self` AND `other`.
+    pub fn and(self, other: Guard) -> Guard {
+        match self {
+            Guard::All(mut guards) => {
+                guards.push(other);
+                Guard::All(guards)
+            }
+            guard => Guard::All(vec![guard, other]),
+        }
+    }
+
+    /// `self` OR `other`.
+    pub fn or(self, other: Guard) -> Guard {
+        match self {
+            Guard::Any(mut guards) => {
+                guards.push(other);
+                Guard::Any(guards)
+            }
+            guard => Guard::Any(vec![guard, other]),
+        }
+    }
+}
+
+/// The clauses whose denial decided a guard: the first denied clause of an
+/// `all`, every denied alternative of an `any`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Denial {
+    pub clauses: Vec<Clause>,
+}
+
+impl Display for Denial {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "denied:")?;
+        for (i, clause) in self.clauses.iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(f, "{sep}{clause}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of evaluating a [`Guard`].
+#[derive(Clone, Debug)]
+pub enum GuardOutcome {
+    /// Granted; the principal is the one reported by a granting check.
+    Granted(Principal),
+    Denied(Denial),
+}
+
+impl GuardOutcome {
+    /// True when the guard granted access.
+    pub fn is_granted(&self) -> bool {
+        matches!(self, GuardOutcome::Granted(_))
+    }
+}
+
+impl CheckClient {
+    /// Evaluates `guard` for `user_id`, every clause at a snapshot at least
+    /// as fresh as `timestamp`, or the request's zookie when `None` (see
+    /// [`Self::check`]); the bound is the same for every clause, the
+    /// snapshots need not be. Clauses run concurrently and evaluation
+    /// short-circuits; an error fails the evaluation unless an `any` is
+    /// granted by another alternative.
+    pub async fn check_guard(
+        &self,
+        guard: &Guard,
+        user_id: UserId,
+        timestamp: Option<Timestamp>,
+    ) -> Result<GuardOutcome, CallError> {
+        // Fixed up front: a write recorded mid-evaluation must not move the
+        // bound of the clauses still to run.
+        let timestamp = crate::zookie::or_current(timestamp);
+        let check = |clause: Clause| {
+            let mut client = self.clone();
+            let user_id = user_id.clone();
+            let ts = timestamp.clone();
+            async move {
+                client
+                    .check(clause.ns, clause.obj, clause.rel, user_id, ts)
+                    .await
+            }
+        };
+        evaluate(guard, &check).await
+    }
+}
+
+/// Evaluates `guard` with `check` answering each clause. Shared by
+/// [`CheckClient::check_guard`] and the memoized paths.
+pub(crate) fn evaluate<'a, F, Fut>(
+    guard: &'a Guard,
+    check: &'a F,
+) -> BoxFuture<'a, Result<GuardOutcome, CallError>>
+where
+    F: Fn(Clause) -> Fut + Sync,
+    Fut: Future<Output = Result<CheckResult, CallError>> + Send + 'a,
+{
+    async move {
+        match guard {
+            Guard::Check(clause) => match check(clause.clone()).await? {
+                CheckResult::Ok(principal) => Ok(GuardOutcome::Granted(principal)),
+                CheckResult::Forbidden(_) | CheckResult::UnknownPutativeUser => {
+                    Ok(GuardOutcome::Denied(Denial {
+                        clauses: vec![clause.clone()],
+                    }))
+                }
+            },
+            Guard::All(guards) => {
+                let mut pending: FuturesUnordered<_> =
+                    guards.iter().map(|g| evaluate(g, check)).collect();
+                let mut granted = None;
+                while let Some(outcome) = pending.next().await {
+                    match outcome? {
+                        GuardOutcome::Granted(principal) => {
+                            granted.get_or_insert(principal);
+                        }
+                        denied @ GuardOutcome::Denied(_) => return Ok(denied),
+                    }
+                }
+                Ok(match granted {
+                    Some(principal) => GuardOutcome::Granted(principal),
+                    None => GuardOutcome::Denied(Denial { clauses: vec![] }),
+                })
+            }
+            Guard::Any(guards) => {
+                let mut pending: FuturesUnordered<_> =
+                    guards.iter().map(|g| evaluate(g, check)).collect();
+                let mut clauses = Vec::new();
+                let mut error = None;
+                while let Some(outcome) = pending.next().await {
+                    match outcome {
+                        Ok(granted @ GuardOutcome::Granted(_)) => return Ok(granted),
+                        Ok(GuardOutcome::Denied(denial)) => clauses.extend(denial.clauses),
+                        Err(err) => {
+                            error.get_or_insert(err);
+                        }
+                    }
+                }
+                match error {
+                    Some(err) => Err(err),
+                    None => Ok(GuardOutcome::Denied(Denial { clauses })),
+                }
+            }
+        }
+    }
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    fn clause(obj: &str, rel: &str) -> Clause {
+        Clause {
+            ns: Namespace("doc".into()),
+            obj: Obj(obj.into()),
+            rel: Rel(rel.into()),
+        }
+    }
+
+    fn leaf(obj: &str, rel: &str) -> Guard {
+        Guard::Check(clause(obj, rel))
+    }
+
+    /// Grants the listed (obj, rel) pairs, fails "err", records every call.
+    struct Table {
+        grants: HashSet<(String, String)>,
+        calls: Mutex<Vec<Clause>>,
+    }
+
+    impl Table {
+        fn new(grants: &[(&str, &str)]) -> Self {
+            Table {
+                grants: grants
+                    .iter()
+                    .map(|(o, r)| (o.to_string(), r.to_string()))
+                    .collect(),
+                calls: Mutex::new(vec![]),
+            }
+        }
+
+        async fn run(&self, guard: &Guard) -> Result<GuardOutcome, CallError> {
+            let check = |c: Clause| {
+                self.calls.lock().unwrap().push(c.clone());
+                let granted = self.grants.contains(&(c.obj.0.clone(), c.rel.0.clone()));
+                async move {
+                    if c.rel.0 == "err" {
+                        return Err(CallError::UnexpectedResponseFormat);
+                    }
+                    Ok(if granted {
+                        CheckResult::Ok("p".to_string().into())
+                    } else {
+                        CheckResult::Forbidden("p".to_string().into())
+                    })
+                }
+            };
+            evaluate(guard, &check).await
+        }
+    }
+
+    #[tokio::test]
+    async fn all_grants_when_every_clause_grants() {
+        let t = Table::new(&[("a", "editor"), ("f", "viewer")]);
+        let g = leaf("a", "editor").and(leaf("f", "viewer"));
+        assert!(t.run(&g).await.unwrap().is_granted());
+        assert_eq!(t.calls.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn all_reports_the_denied_clause() {
+        let t = Table::new(&[("a", "editor")]);
+        let g = Guard::all([leaf("a", "editor"), leaf("f", "viewer")]);
+        match t.run(&g).await.unwrap() {
+            GuardOutcome::Denied(d) => assert_eq!(d.clauses, vec![clause("f", "viewer")]),
+            other => panic!("expected denial, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn any_grants_on_one_alternative() {
+        let t = Table::new(&[("a", "owner")]);
+        let g = leaf("a", "admin").or(leaf("a", "owner"));
+        assert!(t.run(&g).await.unwrap().is_granted());
+    }
+
+    #[tokio::test]
+    async fn any_denial_lists_every_alternative() {
+        let t = Table::new(&[]);
+        let g = Guard::any([leaf("a", "admin"), leaf("a", "owner")]);
+        match t.run(&g).await.unwrap() {
+            GuardOutcome::Denied(d) => {
+                assert_eq!(d.clauses.len(), 2);
+                assert_eq!(d.to_string(), "denied: doc:a#admin, doc:a#owner");
+            }
+            other => panic!("expected denial, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn any_tolerates_error_when_another_alternative_grants() {
+        let t = Table::new(&[("a", "owner")]);
+        let g = Guard::any([leaf("a", "err"), leaf("a", "owner")]);
+        assert!(t.run(&g).await.unwrap().is_granted());
+    }
+
+    #[tokio::test]
+    async fn all_fails_on_error() {
+        let t = Table::new(&[("a", "editor")]);
+        let g = Guard::all([leaf("a", "editor"), leaf("a", "err")]);
+        assert!(t.run(&g).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn empty_guards_never_grant() {
+        let t = Table::new(&[]);
+        assert!(!t.run(&Guard::all([])).await.unwrap().is_granted());
+        assert!(!t.run(&Guard::any([])).await.unwrap().is_granted());
+    }
+
+    #[tokio::test]
+    async fn nested_guard() {
+        // (admin OR owner) AND folder viewer
+        let t = Table::new(&[("a", "owner"), ("f", "viewer")]);
+        let g = leaf("a", "admin")
+            .or(leaf("a", "owner"))
+            .and(leaf("f", "viewer"));
+        assert!(t.run(&g).await.unwrap().is_granted());
+    }
+}
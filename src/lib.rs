@@ -1,22 +1,42 @@
 use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+use crate::audit::{AuditSink, DecisionOutcome, DecisionRecord};
 use crate::auth::{CallError, CheckResult};
-use crate::error::ReadError;
+use crate::observe::{RpcEvent, RpcMethod, RpcObserver};
 use chrono::{DateTime, Utc};
-pub use error::ConnectError;
-use error::{ParseError, WriteError};
+use error::ParseError;
+pub use error::{ConnectError, ErrorKind, ReadError, WriteError};
 use http::Uri;
-use tonic::transport::{Channel, ClientTlsConfig};
+use prost::Message as _;
+use tonic::codec::CompressionEncoding;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
 
+pub mod audit;
 pub mod auth;
 #[cfg(feature = "axum")]
 pub mod axum;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod builder;
+pub mod content;
+pub mod credentials;
 mod error;
+#[cfg(feature = "grpc-server")]
+pub mod grpc;
+pub mod guard;
+pub mod live;
 pub mod memo;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod observe;
 pub mod session;
+pub mod tls;
+mod trace;
+pub mod zookie;
 
 /// Ns is a collection of objects.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -357,18 +377,44 @@ pub struct WatchEvent {
 /// until it returns `Ok(None)`; drop the stream to stop watching.
 pub struct WatchStream {
     inner: tonic::Streaming<pb::WatchResponse>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<(metrics::Metrics, String)>,
 }
 
 impl WatchStream {
     /// Blocks until the next Watch event, `Ok(None)` on clean stream end, or
     /// an error.
     pub async fn recv(&mut self) -> Result<Option<WatchEvent>, ReadError> {
-        match self.inner.message().await {
+        let message = self.inner.message().await;
+        #[cfg(feature = "metrics")]
+        self.observe(&message);
+        match message {
             Ok(None) => Ok(None),
             Ok(Some(resp)) => watch_event_from_pb(resp).map(Some),
             Err(status) => Err(status.into()),
         }
     }
+
+    /// Reports the lag of an event's watermark, or the end of the stream
+    /// (once), to the client's metrics.
+    #[cfg(feature = "metrics")]
+    fn observe(&mut self, message: &Result<Option<pb::WatchResponse>, tonic::Status>) {
+        let Some((metrics, ns)) = &self.metrics else {
+            return;
+        };
+        match message {
+            Ok(Some(resp)) => {
+                if let Some((_, millis)) = Timestamp(resp.ts.clone()).decode() {
+                    let now = Utc::now().timestamp_millis().max(0) as u64;
+                    metrics.watch_lag(ns, Duration::from_millis(now.saturating_sub(millis)));
+                }
+            }
+            _ => {
+                metrics.watch_ended(ns);
+                self.metrics = None;
+            }
+        }
+    }
 }
 
 /// One TupleSet filter for the Read API (paper §2.4.2 / §2.4.3). Build with
@@ -441,22 +487,183 @@ const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
 const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(10);
 const KEEPALIVE_WHILE_IDLE: bool = true;
 
+/// HTTP/2 keepalive of a channel. The default is the nio #239 contract;
+/// override it only to match a proxy with a shorter idle timeout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Keepalive {
+    /// How often a ping is sent.
+    pub interval: Duration,
+    /// How long to wait for the ping's ack before closing the connection.
+    pub timeout: Duration,
+    /// Ping even when no call is in flight.
+    pub while_idle: bool,
+}
+
+impl Default for Keepalive {
+    /// 30s interval, 10s timeout, pings while idle.
+    fn default() -> Self {
+        Keepalive {
+            interval: KEEPALIVE_INTERVAL,
+            timeout: KEEPALIVE_TIMEOUT,
+            while_idle: KEEPALIVE_WHILE_IDLE,
+        }
+    }
+}
+
+/// Endpoint settings beyond the target and TLS; the defaults are what
+/// [`connect_channel`] uses.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ChannelOptions {
+    pub keepalive: Keepalive,
+    pub connect_timeout: Option<Duration>,
+    pub request_timeout: Option<Duration>,
+    pub concurrency_limit: Option<usize>,
+    pub user_agent: Option<String>,
+}
+
+/// Message settings applied to the generated gRPC clients.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Codec {
+    /// Compress requests and accept compressed responses with gzip.
+    pub gzip: bool,
+    /// Largest message sent or accepted; tonic's default (4 MiB) if unset.
+    pub max_message_size: Option<usize>,
+}
+
+/// Where a channel connects: an `http://` / `https://` URI over TCP, or a
+/// Unix domain socket written `unix:///path/to/socket` (a check sidecar
+/// that exposes no port). Parse one from configuration, or convert a [`Uri`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Uri(Uri),
+    Unix(PathBuf),
+}
+
+impl From<Uri> for Target {
+    fn from(uri: Uri) -> Self {
+        Target::Uri(uri)
+    }
+}
+
+impl FromStr for Target {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(rest) = s.strip_prefix("unix:") else {
+            return Uri::from_str(s)
+                .map(Target::Uri)
+                .map_err(|e| ParseError::new("target", s, e));
+        };
+        let path = rest.strip_prefix("//").unwrap_or(rest);
+        if path.is_empty() {
+            return Err(ParseError::new("target", s, "missing socket path"));
+        }
+        Ok(Target::Unix(path.into()))
+    }
+}
+
+impl TryFrom<String> for Target {
+    type Error = ParseError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Target::from_str(&value)
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Uri(uri) => write!(f, "{uri}"),
+            Target::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Target {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 /// Opens a gRPC channel with HTTP/2 keepalive (30s interval, 10s timeout,
 /// pings while idle) so idle connections survive L4 idle-eviction (IPVS,
 /// cloud LBs, NAT — nio #239). Used for both the check and the session
-/// endpoint; pass `None` for an insecure channel (local dev only).
+/// endpoint, over TCP or a Unix socket (see [`Target`]); pass `None` for an
+/// insecure channel (local dev and sidecars only).
 pub async fn connect_channel(
-    uri: Uri,
+    target: impl Into<Target>,
+    tls_config: Option<ClientTlsConfig>,
+) -> Result<Channel, ConnectError> {
+    connect_with(target.into(), tls_config, &ChannelOptions::default()).await
+}
+
+pub(crate) async fn connect_with(
+    target: Target,
     tls_config: Option<ClientTlsConfig>,
+    options: &ChannelOptions,
 ) -> Result<Channel, ConnectError> {
-    let mut builder = Channel::builder(uri)
-        .http2_keep_alive_interval(KEEPALIVE_INTERVAL)
-        .keep_alive_timeout(KEEPALIVE_TIMEOUT)
-        .keep_alive_while_idle(KEEPALIVE_WHILE_IDLE);
+    let endpoint = match target {
+        Target::Uri(uri) => Channel::builder(uri),
+        Target::Unix(path) => Endpoint::from_shared(format!("unix:{}", path.display()))
+            .map_err(ConnectError::config)?,
+    };
+    let mut builder = endpoint
+        .http2_keep_alive_interval(options.keepalive.interval)
+        .keep_alive_timeout(options.keepalive.timeout)
+        .keep_alive_while_idle(options.keepalive.while_idle);
+    if let Some(timeout) = options.connect_timeout {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(timeout) = options.request_timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(limit) = options.concurrency_limit {
+        builder = builder.concurrency_limit(limit);
+    }
+    if let Some(user_agent) = &options.user_agent {
+        builder = builder
+            .user_agent(user_agent.as_str())
+            .map_err(ConnectError::config)?;
+    }
     if let Some(tls) = tls_config {
-        builder = builder.tls_config(tls).map_err(ConnectError)?;
+        builder = builder.tls_config(tls).map_err(ConnectError::config)?;
     }
-    builder.connect().await.map_err(ConnectError)
+    builder.connect().await.map_err(ConnectError::connect)
+}
+
+/// The transport under [`CheckClient`] and
+/// [`session::GrpcSessionResolver`]: a type-erased tower service speaking
+/// gRPC — a [`Channel`], possibly wrapped in layers or interceptors, or a
+/// test double. Build one with [`transport`]. Transport failures are
+/// already mapped to the [`tonic::Status`] the call fails with.
+pub type GrpcTransport = tower::util::BoxCloneSyncService<
+    http::Request<tonic::body::Body>,
+    http::Response<tonic::body::Body>,
+    tonic::Status,
+>;
+
+/// Erases a gRPC `service` into a [`GrpcTransport`], e.g. a [`Channel`]
+/// wrapped in an `InterceptedService` or a `tower::ServiceBuilder` stack.
+pub fn transport<S, B>(service: S) -> GrpcTransport
+where
+    S: tower::Service<http::Request<tonic::body::Body>, Response = http::Response<B>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Error: Into<tower::BoxError>,
+    S::Future: Send + 'static,
+    B: http_body::Body<Data = bytes::Bytes> + Send + 'static,
+    B::Error: Into<tower::BoxError>,
+{
+    use tower::ServiceExt;
+
+    GrpcTransport::new(
+        service
+            .map_response(|response| response.map(tonic::body::Body::new))
+            .map_err(|e| tonic::Status::from_error(e.into())),
+    )
 }
 
 /// RPC-only check client (CheckService + NamespaceService). It has no session
@@ -464,10 +671,16 @@ pub async fn connect_channel(
 /// [`session::SessionResolver`] (see the `axum` module's `AuthState`).
 #[derive(Clone)]
 pub struct CheckClient {
-    check: pb::check_service_client::CheckServiceClient<Channel>,
-    ns: pb::namespace_service_client::NamespaceServiceClient<Channel>,
+    transport: GrpcTransport,
+    codec: Codec,
+    check: pb::check_service_client::CheckServiceClient<GrpcTransport>,
+    ns: pb::namespace_service_client::NamespaceServiceClient<GrpcTransport>,
     observe_check: Option<ObserveCheckFn>,
     observe_list: Option<ObserveListFn>,
+    rpc_observer: Option<Arc<dyn RpcObserver>>,
+    audit: Option<Arc<dyn AuditSink>>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<metrics::Metrics>,
 }
 
 impl std::fmt::Debug for CheckClient {
@@ -477,27 +690,143 @@ impl std::fmt::Debug for CheckClient {
 }
 
 impl CheckClient {
-    pub async fn create(uri: Uri) -> Result<Self, ConnectError> {
-        Self::create_with_tls(uri, None).await
+    pub async fn create(target: impl Into<Target>) -> Result<Self, ConnectError> {
+        Self::create_with_tls(target, None).await
     }
 
     pub async fn create_with_tls(
-        uri: Uri,
+        target: impl Into<Target>,
         tls_config: Option<ClientTlsConfig>,
     ) -> Result<Self, ConnectError> {
-        let channel = connect_channel(uri, tls_config).await?;
+        let channel = connect_channel(target, tls_config).await?;
         Ok(Self::from_channel(channel))
     }
 
     pub fn from_channel(channel: Channel) -> Self {
+        Self::from_transport(transport(channel))
+    }
+
+    /// A client over any gRPC transport: a layered or intercepted
+    /// [`Channel`], or a mock. See [`transport`].
+    pub fn from_transport(transport: GrpcTransport) -> Self {
+        let codec = Codec::default();
+        let (check, ns) = Self::clients(&transport, codec);
         CheckClient {
-            check: pb::check_service_client::CheckServiceClient::new(channel.clone()),
-            ns: pb::namespace_service_client::NamespaceServiceClient::new(channel),
+            transport,
+            codec,
+            check,
+            ns,
             observe_check: None,
             observe_list: None,
+            rpc_observer: None,
+            audit: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Wraps the transport in `layer` (rate limits, retries, extra
+    /// metadata, …). Layers added later run first.
+    pub fn with_layer<L, B>(self, layer: L) -> Self
+    where
+        L: tower::Layer<GrpcTransport>,
+        L::Service: tower::Service<http::Request<tonic::body::Body>, Response = http::Response<B>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        <L::Service as tower::Service<http::Request<tonic::body::Body>>>::Error:
+            Into<tower::BoxError>,
+        <L::Service as tower::Service<http::Request<tonic::body::Body>>>::Future: Send + 'static,
+        B: http_body::Body<Data = bytes::Bytes> + Send + 'static,
+        B::Error: Into<tower::BoxError>,
+    {
+        let transport = transport(layer.layer(self.transport.clone()));
+        let (check, ns) = Self::clients(&transport, self.codec);
+        CheckClient {
+            transport,
+            check,
+            ns,
+            ..self
         }
     }
 
+    /// Compresses requests with gzip and accepts gzip-compressed responses.
+    /// The server must accept gzip, or every call fails with
+    /// `Unimplemented`.
+    pub fn with_gzip(self, enabled: bool) -> Self {
+        let codec = Codec {
+            gzip: enabled,
+            ..self.codec
+        };
+        self.with_codec(codec)
+    }
+
+    /// Caps the size of messages sent and received (tonic's default is
+    /// 4 MiB for responses). Bigger responses fail with `OutOfRange`.
+    pub fn with_max_message_size(self, bytes: usize) -> Self {
+        let codec = Codec {
+            max_message_size: Some(bytes),
+            ..self.codec
+        };
+        self.with_codec(codec)
+    }
+
+    fn with_codec(self, codec: Codec) -> Self {
+        let (check, ns) = Self::clients(&self.transport, codec);
+        CheckClient {
+            codec,
+            check,
+            ns,
+            ..self
+        }
+    }
+
+    fn clients(
+        transport: &GrpcTransport,
+        codec: Codec,
+    ) -> (
+        pb::check_service_client::CheckServiceClient<GrpcTransport>,
+        pb::namespace_service_client::NamespaceServiceClient<GrpcTransport>,
+    ) {
+        let mut check = pb::check_service_client::CheckServiceClient::new(transport.clone());
+        let mut ns = pb::namespace_service_client::NamespaceServiceClient::new(transport.clone());
+        if codec.gzip {
+            check = check
+                .send_compressed(CompressionEncoding::Gzip)
+                .accept_compressed(CompressionEncoding::Gzip);
+            ns = ns
+                .send_compressed(CompressionEncoding::Gzip)
+                .accept_compressed(CompressionEncoding::Gzip);
+        }
+        if let Some(bytes) = codec.max_message_size {
+            check = check
+                .max_decoding_message_size(bytes)
+                .max_encoding_message_size(bytes);
+            ns = ns
+                .max_decoding_message_size(bytes)
+                .max_encoding_message_size(bytes);
+        }
+        (check, ns)
+    }
+
+    /// Sends the caller's token from `provider` as `authorization`
+    /// metadata on every RPC. A provider failure fails the call with a
+    /// `Credentials` error.
+    pub fn with_credentials(self, provider: Arc<dyn credentials::CredentialProvider>) -> Self {
+        self.with_layer(credentials::CredentialLayer::new(provider))
+    }
+
+    /// Runs `interceptor` on every outgoing request, e.g. to add
+    /// per-call metadata; a failing interceptor fails the call with its
+    /// status.
+    pub fn with_interceptor<I>(self, interceptor: I) -> Self
+    where
+        I: tonic::service::Interceptor + Clone + Send + Sync + 'static,
+    {
+        self.with_layer(tonic::service::InterceptorLayer::new(interceptor))
+    }
+
     /// Sets an observe function called after every check RPC with
     /// (ns, obj, rel, user_id, duration, ok, is_error).
     pub fn with_observe_check(mut self, f: ObserveCheckFn) -> Self {
@@ -512,14 +841,89 @@ impl CheckClient {
         self
     }
 
+    /// Sends an [`RpcEvent`] for every RPC this client makes to `observer`.
+    pub fn with_rpc_observer(mut self, observer: Arc<dyn RpcObserver>) -> Self {
+        self.rpc_observer = Some(observer);
+        self
+    }
+
+    /// Sends every check decision — including those a [`memo::RequestMemo`]
+    /// over this client answers from its cache — to `sink`.
+    pub fn with_audit(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit = Some(sink);
+        self
+    }
+
+    pub(crate) fn audit_sink(&self) -> Option<&Arc<dyn AuditSink>> {
+        self.audit.as_ref()
+    }
+
+    /// Reports RPC latencies and errors, watch lag and reconnects, and the
+    /// lookups of every [`memo::RequestMemo`] over this client to `metrics`.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: metrics::Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    #[cfg(feature = "metrics")]
+    pub(crate) fn metrics(&self) -> Option<&metrics::Metrics> {
+        self.metrics.as_ref()
+    }
+
+    /// Reports one `method` RPC to the RPC observer and the metrics
+    /// registry. `outcome` is the encoded response size or the failure; the
+    /// event is only built when one of them is installed.
+    fn observe_rpc(
+        &self,
+        method: RpcMethod,
+        started: std::time::Instant,
+        outcome: Result<usize, &tonic::Status>,
+        zookie: Option<&str>,
+        request: impl FnOnce() -> String,
+    ) {
+        #[cfg(feature = "metrics")]
+        let metrics = self.metrics.as_ref().map(|m| m as &dyn RpcObserver);
+        #[cfg(not(feature = "metrics"))]
+        let metrics = None;
+        let observers = [self.rpc_observer.as_deref(), metrics];
+        if observers.iter().all(Option::is_none) {
+            return;
+        }
+        let event = RpcEvent {
+            method,
+            request: request(),
+            duration: started.elapsed(),
+            code: outcome.map_or_else(|status| status.code(), |_| tonic::Code::Ok),
+            response_size: outcome.unwrap_or(0),
+            zookie: zookie
+                .filter(|ts| !ts.is_empty() && *ts != Timestamp::EMPTY)
+                .map(|ts| Timestamp(ts.to_string())),
+        };
+        for observer in observers.into_iter().flatten() {
+            observer.observe(&event);
+        }
+    }
+
     /// Calls the check server's Check API: may `user_id` — a principal UUID;
     /// resolve session tokens to a principal client-side first (see
     /// [`crate::session`]) — exercise `rel` on ⟨ns, obj⟩? Evaluated at a
     /// snapshot at least as fresh as `timestamp` (a zookie from an earlier
-    /// write/read); `None` accepts any current snapshot. An unknown principal
+    /// write/read); `None` accepts any current snapshot. Inside a
+    /// [`zookie::ZookieJar`] scope (e.g. under the axum `propagate_zookie`
+    /// middleware) `None` means the request's zookie instead; pass
+    /// [`Timestamp::empty`] to opt out. An unknown principal
     /// maps to [`CheckResult::UnknownPutativeUser`], a known-but-unauthorized
     /// user to [`CheckResult::Forbidden`]. [`Rel::IMPOSSIBLE`] short-circuits
     /// to a denial (empty principal) without an RPC.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.check",
+            skip_all,
+            fields(ns = %ns.0, obj = %obj.0, rel = %rel.0, outcome = tracing::field::Empty)
+        )
+    )]
     pub async fn check(
         &mut self,
         ns: Namespace,
@@ -528,31 +932,43 @@ impl CheckClient {
         user_id: UserId,
         timestamp: Option<Timestamp>,
     ) -> Result<CheckResult, CallError> {
+        let ts = zookie::or_current(timestamp);
         if rel.0 == Rel::IMPOSSIBLE {
-            return Ok(CheckResult::Forbidden(String::new().into()));
+            let result = Ok(CheckResult::Forbidden(String::new().into()));
+            if let Some(sink) = &self.audit {
+                let check = (&ns, &obj, &rel, &user_id);
+                sink.record(&DecisionRecord::new(
+                    check,
+                    ts,
+                    &result,
+                    Duration::ZERO,
+                    false,
+                ));
+            }
+            return result;
         }
         let r = pb::CheckRequest {
             ns: ns.0.clone(),
             obj: obj.0.clone(),
             rel: rel.0.clone(),
             user_id: user_id.0.clone(),
-            ts: timestamp.unwrap_or_else(Timestamp::empty).0,
+            ts: ts.clone().unwrap_or_else(Timestamp::empty).0,
         };
         let started = std::time::Instant::now();
-        let result = self.check.check(r).await;
+        let result = self.check.check(trace::request(r)).await;
+        let elapsed = started.elapsed();
+        self.observe_rpc(
+            RpcMethod::Check,
+            started,
+            result.as_ref().map(|r| r.get_ref().encoded_len()),
+            ts.as_ref().map(|ts| ts.0.as_str()),
+            || format!("{}:{}#{}@{}", ns.0, obj.0, rel.0, user_id.0),
+        );
         if let Some(observe) = &self.observe_check {
             let ok = result.as_ref().map(|r| r.get_ref().ok).unwrap_or(false);
-            observe(
-                &ns,
-                &obj,
-                &rel,
-                &user_id,
-                started.elapsed(),
-                ok,
-                result.is_err(),
-            );
+            observe(&ns, &obj, &rel, &user_id, elapsed, ok, result.is_err());
         }
-        match result.map(|r| r.into_inner()) {
+        let result = match result.map(|r| r.into_inner()) {
             Ok(pb::CheckResponse {
                 principal: Some(pb::Principal { id }),
                 ok,
@@ -573,14 +989,30 @@ impl CheckClient {
                 ok: true,
             }) => Err(CallError::UnexpectedResponseFormat),
             Err(status) => Err(status.into()),
+        };
+        trace::record("outcome", DecisionOutcome::of(&result).as_str());
+        if let Some(sink) = &self.audit {
+            let check = (&ns, &obj, &rel, &user_id);
+            sink.record(&DecisionRecord::new(check, ts, &result, elapsed, false));
         }
+        result
     }
 
     /// Calls the check server's List API: the objects in `ns` on which the
     /// user holds `rel`, with rewrite rules applied — the user→objects dual
-    /// of [`Self::check`]. Same zookie semantics as `check`. The returned
+    /// of [`Self::check`]. Same zookie semantics as `check`: `None` inside a
+    /// [`zookie::ZookieJar`] scope means the request's zookie. The returned
     /// `ts` is the evaluation snapshot so callers can chain a subsequent
     /// check/list/read to the same point in time.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.list",
+            skip_all,
+            fields(ns = %ns.0, rel = %rel.0),
+            err(level = "debug")
+        )
+    )]
     pub async fn list(
         &mut self,
         ns: Namespace,
@@ -592,10 +1024,19 @@ impl CheckClient {
             ns: ns.0.clone(),
             rel: rel.0.clone(),
             user_id: user_id.0.clone(),
-            ts: timestamp.unwrap_or_else(Timestamp::empty).0,
+            ts: zookie::or_current(timestamp)
+                .unwrap_or_else(Timestamp::empty)
+                .0,
         };
         let started = std::time::Instant::now();
-        let result = self.check.list(r).await;
+        let result = self.check.list(trace::request(r)).await;
+        self.observe_rpc(
+            RpcMethod::List,
+            started,
+            result.as_ref().map(|r| r.get_ref().encoded_len()),
+            result.as_ref().ok().map(|r| r.get_ref().ts.as_str()),
+            || format!("{}:*#{}@{}", ns.0, rel.0, user_id.0),
+        );
         if let Some(observe) = &self.observe_list {
             observe(&ns, &rel, &user_id, started.elapsed(), result.is_err());
         }
@@ -613,6 +1054,15 @@ impl CheckClient {
     /// through userset rewrite rules. Pass the `ts` returned by a previous
     /// call to evaluate several expansions against one consistent snapshot;
     /// `None` lets the server choose.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.expand",
+            skip_all,
+            fields(ns = %ns.0, obj = %obj.0, rel = %rel.0),
+            err(level = "debug")
+        )
+    )]
     pub async fn expand(
         &mut self,
         ns: Namespace,
@@ -621,12 +1071,21 @@ impl CheckClient {
         timestamp: Option<Timestamp>,
     ) -> Result<ExpandResult, ReadError> {
         let r = pb::ExpandRequest {
-            ns: ns.0,
-            obj: obj.0,
-            rel: rel.0,
+            ns: ns.0.clone(),
+            obj: obj.0.clone(),
+            rel: rel.0.clone(),
             ts: timestamp.unwrap_or_else(Timestamp::empty).0,
         };
-        match self.check.expand(r).await.map(|r| r.into_inner()) {
+        let started = std::time::Instant::now();
+        let result = self.check.expand(trace::request(r)).await;
+        self.observe_rpc(
+            RpcMethod::Expand,
+            started,
+            result.as_ref().map(|r| r.get_ref().encoded_len()),
+            result.as_ref().ok().map(|r| r.get_ref().ts.as_str()),
+            || format!("{}:{}#{}", ns.0, obj.0, rel.0),
+        );
+        match result.map(|r| r.into_inner()) {
             Ok(response) => Ok(ExpandResult {
                 ts: Timestamp(response.ts),
                 user_ids: response.user_ids,
@@ -647,6 +1106,15 @@ impl CheckClient {
     /// Authorizes a content modification against the freshest snapshot (never
     /// a client-supplied zookie). Returns the evaluation zookie to store with
     /// the new content version.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.content_change_check",
+            skip_all,
+            fields(ns = %ns.0, obj = %obj.0, rel = %rel.0),
+            err(level = "debug")
+        )
+    )]
     pub async fn content_change_check(
         &mut self,
         ns: Namespace,
@@ -655,17 +1123,21 @@ impl CheckClient {
         user_id: UserId,
     ) -> Result<ContentChangeCheckResult, CallError> {
         let r = pb::ContentChangeCheckRequest {
-            ns: ns.0,
-            obj: obj.0,
-            rel: rel.0,
-            user_id: user_id.0,
+            ns: ns.0.clone(),
+            obj: obj.0.clone(),
+            rel: rel.0.clone(),
+            user_id: user_id.0.clone(),
         };
-        match self
-            .check
-            .content_change_check(r)
-            .await
-            .map(|r| r.into_inner())
-        {
+        let started = std::time::Instant::now();
+        let result = self.check.content_change_check(trace::request(r)).await;
+        self.observe_rpc(
+            RpcMethod::ContentChangeCheck,
+            started,
+            result.as_ref().map(|r| r.get_ref().encoded_len()),
+            result.as_ref().ok().map(|r| r.get_ref().ts.as_str()),
+            || format!("{}:{}#{}@{}", ns.0, obj.0, rel.0, user_id.0),
+        );
+        match result.map(|r| r.into_inner()) {
             Ok(response) => Ok(ContentChangeCheckResult {
                 ok: response.ok,
                 ts: Timestamp(response.ts),
@@ -679,19 +1151,40 @@ impl CheckClient {
     /// oldest-first, interleaved with heartbeats (empty updates). Drop the
     /// stream to stop. Resume later by passing any previously received
     /// event's `ts` as `start_ts`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "nio.watch", skip_all, fields(ns = %ns.0), err(level = "debug"))
+    )]
     pub async fn watch(
         &mut self,
         ns: Namespace,
         start_ts: Timestamp,
     ) -> Result<WatchStream, CallError> {
         let r = pb::WatchRequest {
-            ns: ns.0,
-            start_ts: start_ts.0,
+            ns: ns.0.clone(),
+            start_ts: start_ts.0.clone(),
         };
-        match self.check.watch(r).await {
-            Ok(response) => Ok(WatchStream {
-                inner: response.into_inner(),
-            }),
+        let started = std::time::Instant::now();
+        let result = self.check.watch(trace::request(r)).await;
+        self.observe_rpc(
+            RpcMethod::Watch,
+            started,
+            result.as_ref().map(|_| 0),
+            Some(&start_ts.0),
+            || ns.0.clone(),
+        );
+        match result {
+            Ok(response) => {
+                #[cfg(feature = "metrics")]
+                if let Some(metrics) = &self.metrics {
+                    metrics.watch_started(&ns.0);
+                }
+                Ok(WatchStream {
+                    inner: response.into_inner(),
+                    #[cfg(feature = "metrics")]
+                    metrics: self.metrics.clone().map(|m| (m, ns.0)),
+                })
+            }
             Err(status) => Err(status.into()),
         }
     }
@@ -699,8 +1192,21 @@ impl CheckClient {
     /// Fetches the namespace configs the check server loaded: per namespace
     /// the declared relations and the rewrite kind of each. Schema metadata
     /// only — no tuples.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "nio.list_namespaces", skip_all, err(level = "debug"))
+    )]
     pub async fn list_namespaces(&mut self) -> Result<Vec<NamespaceMeta>, ReadError> {
-        match self.ns.list_namespaces(()).await.map(|r| r.into_inner()) {
+        let started = std::time::Instant::now();
+        let result = self.ns.list_namespaces(trace::request(())).await;
+        self.observe_rpc(
+            RpcMethod::ListNamespaces,
+            started,
+            result.as_ref().map(|r| r.get_ref().encoded_len()),
+            None,
+            String::new,
+        );
+        match result.map(|r| r.into_inner()) {
             Ok(resp) => Ok(resp
                 .namespaces
                 .into_iter()
@@ -777,6 +1283,10 @@ impl CheckClient {
 
     /// Returns stored tuples matching `filters` at a snapshot at least as
     /// fresh as `ts`. The returned `ts` is the snapshot the server used.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "nio.read", skip_all, err(level = "debug"))
+    )]
     pub async fn read_with_timestamp(
         &mut self,
         ts: Timestamp,
@@ -791,7 +1301,17 @@ impl CheckClient {
             ts: (ts != Timestamp::empty()).then_some(ts.0),
             tuple_sets: filters.into_iter().map(|f| f.set).collect(),
         };
-        let response = self.check.read(request).await?.into_inner();
+        let filters = request.tuple_sets.len();
+        let started = std::time::Instant::now();
+        let result = self.check.read(trace::request(request)).await;
+        self.observe_rpc(
+            RpcMethod::Read,
+            started,
+            result.as_ref().map(|r| r.get_ref().encoded_len()),
+            result.as_ref().ok().map(|r| r.get_ref().ts.as_str()),
+            || format!("{filters} filters"),
+        );
+        let response = result?.into_inner();
         let mut tuples = Vec::with_capacity(response.tuples.len());
         for tup in response.tuples {
             tuples.push(tuple_from_pb(tup)?);
@@ -804,7 +1324,12 @@ impl CheckClient {
 
     /// Commits `add` and `del` tuples atomically. `precondition` is an
     /// optional OCC zookie; `None` is an unconditional write. Returns the
-    /// commit zookie for read-your-writes / chaining subsequent reads.
+    /// commit zookie for read-your-writes / chaining subsequent reads, and
+    /// records it in the current [`zookie::ZookieJar`] scope, if any.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "nio.write", skip_all, err(level = "debug"))
+    )]
     pub async fn write(
         &mut self,
         add: Vec<Tuple>,
@@ -816,26 +1341,36 @@ impl CheckClient {
             add_tuples: add.into_iter().map(tuple_to_pb).collect(),
             del_tuples: del.into_iter().map(tuple_to_pb).collect(),
         };
-        self.check
-            .write(request)
-            .await
-            .map(|r| Timestamp(r.into_inner().ts))
-            .map_err(Into::into)
+        let (adds, dels) = (request.add_tuples.len(), request.del_tuples.len());
+        let started = std::time::Instant::now();
+        let result = self.check.write(trace::request(request)).await;
+        self.observe_rpc(
+            RpcMethod::Write,
+            started,
+            result.as_ref().map(|r| r.get_ref().encoded_len()),
+            result.as_ref().ok().map(|r| r.get_ref().ts.as_str()),
+            || format!("+{adds} -{dels}"),
+        );
+        let ts = result.map(|r| Timestamp(r.into_inner().ts))?;
+        zookie::record(&ts);
+        Ok(ts)
     }
 
-    /// Adds one tuple. Returns the commit zookie for read-your-writes.
+    /// Adds one tuple. Returns the commit zookie for read-your-writes and
+    /// records it like [`Self::write`].
     pub async fn add_one(&mut self, tuple: Tuple) -> Result<Timestamp, WriteError> {
         self.write(vec![tuple], vec![], None).await
     }
 
-    /// Adds many tuples atomically. Returns the commit zookie.
+    /// Adds many tuples atomically. Returns the commit zookie and records it
+    /// like [`Self::write`].
     pub async fn add_many(&mut self, tuples: Vec<Tuple>) -> Result<Timestamp, WriteError> {
         self.write(tuples, vec![], None).await
     }
 
     /// Adds an inheritance relationship using the quasi-standard relation
     /// `parent`: ns:obj#parent@parent_ns:parent_obj#`...`. Returns the commit
-    /// zookie.
+    /// zookie and records it like [`Self::write`].
     pub async fn add_parent(
         &mut self,
         ns: Namespace,
@@ -856,7 +1391,8 @@ impl CheckClient {
         .await
     }
 
-    /// Deletes one tuple. Returns the commit zookie.
+    /// Deletes one tuple. Returns the commit zookie and records it like
+    /// [`Self::write`].
     pub async fn delete_one(&mut self, tuple: Tuple) -> Result<Timestamp, WriteError> {
         self.write(vec![], vec![tuple], None).await
     }
@@ -947,6 +1483,46 @@ fn watch_event_from_pb(resp: pb::WatchResponse) -> Result<WatchEvent, ReadError>
 mod tests {
     use super::*;
 
+    #[test]
+    fn targets_parse_tcp_uris_and_unix_sockets() {
+        assert_eq!(
+            "http://localhost:50051".parse::<Target>().unwrap(),
+            Target::Uri(Uri::from_static("http://localhost:50051"))
+        );
+        let uds = "unix:///run/nio/check.sock".parse::<Target>().unwrap();
+        assert_eq!(uds, Target::Unix("/run/nio/check.sock".into()));
+        assert_eq!(uds.to_string(), "unix:///run/nio/check.sock");
+        assert_eq!("unix:/run/nio/check.sock".parse::<Target>().unwrap(), uds);
+        assert!("unix://".parse::<Target>().is_err());
+        assert!("not a uri".parse::<Target>().is_err());
+    }
+
+    #[tokio::test]
+    async fn unusable_settings_are_not_retryable_but_refused_connects_are() {
+        let kind = |result: Result<Channel, ConnectError>| result.unwrap_err().kind();
+        let bad_agent = ChannelOptions {
+            user_agent: Some("bad\nagent".into()),
+            ..ChannelOptions::default()
+        };
+        let uri = Target::Uri(Uri::from_static("http://127.0.0.1:1"));
+        assert_eq!(
+            kind(connect_with(uri.clone(), None, &bad_agent).await),
+            ErrorKind::InvalidArgument
+        );
+        let uds = Target::Unix("/nonexistent/check.sock".into());
+        let tls = Some(ClientTlsConfig::new());
+        let err = connect_with(uds, tls, &ChannelOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidArgument, "TLS over a socket");
+        assert!(!err.is_retryable());
+        let err = connect_with(uri, None, &ChannelOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unavailable, "connection refused");
+        assert!(err.is_retryable());
+    }
+
     #[test]
     fn timestamp_empty_is_packed_empty_zookie() {
         assert_eq!(Timestamp::empty().0, "AQAAAAAAAA==");
@@ -1101,7 +1677,7 @@ mod tests {
                 assert!(msg.contains("missing user"), "msg={msg}");
                 assert!(msg.contains("coll"));
             }
-            ReadError::Grpc(_) => panic!("expected InvalidResponse, got Grpc"),
+            other => panic!("expected InvalidResponse, got {other:?}"),
         }
     }
 
@@ -0,0 +1,247 @@
+//! Reauthorization for long-lived connections (websockets, SSE).
+//!
+//! Extractors authorize once, at upgrade time; a stream can then outlive the
+//! session or the permission it was granted for. [`LiveAccess`] holds the
+//! resolved session and the [`Guard`] that granted the connection and keeps
+//! re-validating both in a background task: every
+//! [`LiveAccessConfig::recheck_interval`], after every write to a namespace
+//! the guard names (via `watch`), and when the session's `expires_at` passes.
+//! The handler selects on [`LiveAccess::revoked`] next to its socket and
+//! closes the connection when it resolves.
+//!
+//! Transient failures (resolver or check errors) keep the connection and are
+//! retried at the next tick; a failed `watch` falls back to the periodic
+//! recheck.
+
+use crate::guard::{Denial, Guard, GuardOutcome};
+use crate::session::{ResolvedSession, SessionResolver};
+use crate::{
+    zookie, CheckClient, Namespace, ReadFilter, Timestamp, UserId, WatchEvent, WatchStream,
+};
+use chrono::Utc;
+use futures::future::{select_all, BoxFuture};
+use futures::FutureExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{interval_at, Instant, MissedTickBehavior};
+
+/// Tunables for [`LiveAccess`].
+#[derive(Clone, Debug)]
+pub struct LiveAccessConfig {
+    /// Period of the session + guard re-validation.
+    pub recheck_interval: Duration,
+    /// Also re-check after every write to a namespace the guard names.
+    pub watch: bool,
+}
+
+impl Default for LiveAccessConfig {
+    /// Recheck every 30s and on `watch` events.
+    fn default() -> Self {
+        LiveAccessConfig {
+            recheck_interval: Duration::from_secs(30),
+            watch: true,
+        }
+    }
+}
+
+/// Why a [`LiveAccess`] ended.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Revocation {
+    /// The session is unknown now: signed out or revoked.
+    #[error("session ended")]
+    SessionEnded,
+    /// The session's `expires_at` passed.
+    #[error("session expired")]
+    SessionExpired,
+    /// The principal no longer passes the guard.
+    #[error("access lost: {0}")]
+    AccessLost(Denial),
+}
+
+/// A granted long-lived connection under continuous re-validation. Dropping
+/// it stops the background task.
+pub struct LiveAccess {
+    revoked: watch::Receiver<Option<Revocation>>,
+    task: JoinHandle<()>,
+}
+
+impl LiveAccess {
+    /// Starts re-validating `session` (resolved from `token_hash`) against
+    /// `guard`. The caller has already authorized the connection; the first
+    /// recheck happens one interval (or one relevant write) later.
+    pub fn start(
+        client: CheckClient,
+        resolver: Arc<dyn SessionResolver>,
+        token_hash: String,
+        session: ResolvedSession,
+        guard: Guard,
+        config: LiveAccessConfig,
+    ) -> LiveAccess {
+        let (tx, revoked) = watch::channel(None);
+        let watcher = Watcher {
+            client,
+            resolver,
+            token_hash,
+            session,
+            guard,
+            config,
+        };
+        let task = tokio::spawn(async move {
+            let revocation = watcher.run().await;
+            log::debug!("nio-client: live access revoked: {revocation}");
+            let _ = tx.send(Some(revocation));
+        });
+        LiveAccess { revoked, task }
+    }
+
+    /// Resolves once access is lost; immediately if it already is. Cancel
+    /// safe — select on it next to the connection.
+    pub async fn revoked(&self) -> Revocation {
+        let mut rx = self.revoked.clone();
+        let revocation = match rx.wait_for(Option::is_some).await {
+            Ok(revocation) => revocation.clone(),
+            Err(_) => None,
+        };
+        match revocation {
+            Some(revocation) => revocation,
+            // The task only stops by revoking; never resolve otherwise.
+            None => std::future::pending().await,
+        }
+    }
+
+    /// The revocation, if access is already lost.
+    pub fn is_revoked(&self) -> Option<Revocation> {
+        self.revoked.borrow().clone()
+    }
+}
+
+impl Drop for LiveAccess {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Watcher {
+    client: CheckClient,
+    resolver: Arc<dyn SessionResolver>,
+    token_hash: String,
+    session: ResolvedSession,
+    guard: Guard,
+    config: LiveAccessConfig,
+}
+
+impl Watcher {
+    async fn run(mut self) -> Revocation {
+        let period = self.config.recheck_interval;
+        let mut ticks = interval_at(Instant::now() + period, period);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut streams = if self.config.watch {
+            self.watch_streams().await
+        } else {
+            vec![]
+        };
+        loop {
+            let expires_in = (self.session.expires_at - Utc::now())
+                .to_std()
+                .unwrap_or(Duration::ZERO);
+            let at = tokio::select! {
+                _ = tokio::time::sleep(expires_in) => return Revocation::SessionExpired,
+                _ = ticks.tick() => None,
+                (event, i) = next_event(&mut streams) => match event {
+                    Some(event) if event.updates.is_empty() => continue,
+                    Some(event) => Some(event.ts),
+                    None => {
+                        log::warn!("nio-client: live access watch ended; rechecking periodically");
+                        streams.swap_remove(i);
+                        continue;
+                    }
+                },
+            };
+            if let Some(revocation) = self.recheck(at).await {
+                return revocation;
+            }
+        }
+    }
+
+    /// One re-validation: the session first, then the guard (at `at`, the
+    /// triggering write, when there is one).
+    async fn recheck(&mut self, at: Option<Timestamp>) -> Option<Revocation> {
+        match self.resolver.resolve(&self.token_hash).await {
+            Ok(Some(session)) if session.principal == self.session.principal => {
+                self.session = session;
+            }
+            Ok(_) => return Some(Revocation::SessionEnded),
+            Err(err) => {
+                log::warn!("nio-client: live access session recheck failed: {err}");
+                return None;
+            }
+        }
+        if self.session.expires_at <= Utc::now() {
+            return Some(Revocation::SessionExpired);
+        }
+        let user = UserId(self.session.principal.clone());
+        match self.client.check_guard(&self.guard, user, at).await {
+            Ok(GuardOutcome::Granted(_)) => None,
+            Ok(GuardOutcome::Denied(denial)) => Some(Revocation::AccessLost(denial)),
+            Err(err) => {
+                log::warn!("nio-client: live access check failed: {err}");
+                None
+            }
+        }
+    }
+
+    /// One `watch` per namespace the guard names, starting at the current
+    /// snapshot (taken with a read of the first object checked there).
+    async fn watch_streams(&mut self) -> Vec<WatchStream> {
+        let mut streams = vec![];
+        for (ns, filter) in namespaces(&self.guard) {
+            let start = match self.client.read(vec![filter]).await {
+                Ok(result) => zookie::at_least(result.ts),
+                Err(err) => {
+                    log::warn!("nio-client: live access watch start on {}: {err}", ns.0);
+                    continue;
+                }
+            };
+            match self.client.watch(ns.clone(), start).await {
+                Ok(stream) => streams.push(stream),
+                Err(err) => log::warn!("nio-client: live access watch on {}: {err}", ns.0),
+            }
+        }
+        streams
+    }
+}
+
+/// The next event of any stream and the stream's index; `None` when that
+/// stream ended or failed. Pending forever without streams.
+fn next_event(streams: &mut [WatchStream]) -> BoxFuture<'_, (Option<WatchEvent>, usize)> {
+    if streams.is_empty() {
+        return std::future::pending().boxed();
+    }
+    let recvs = streams.iter_mut().map(|s| s.recv().boxed());
+    select_all(recvs)
+        .map(|(result, i, _)| (result.ok().flatten(), i))
+        .boxed()
+}
+
+/// The distinct namespaces of `guard`, each with a read filter on the first
+/// object checked there.
+fn namespaces(guard: &Guard) -> Vec<(Namespace, ReadFilter)> {
+    fn walk(guard: &Guard, out: &mut Vec<(Namespace, ReadFilter)>) {
+        match guard {
+            Guard::Check(clause) => {
+                if !out.iter().any(|(ns, _)| *ns == clause.ns) {
+                    let filter = ReadFilter::by_object(clause.ns.clone(), clause.obj.clone(), None);
+                    out.push((clause.ns.clone(), filter));
+                }
+            }
+            Guard::All(guards) | Guard::Any(guards) => {
+                guards.iter().for_each(|g| walk(g, out));
+            }
+        }
+    }
+    let mut out = vec![];
+    walk(guard, &mut out);
+    out
+}
@@ -16,10 +16,13 @@
 //! handler that fans checks out across tasks still issues one RPC per key.
 //! Errors are never cached.
 
+use crate::audit::DecisionRecord;
 use crate::auth::{CallError, CheckResult};
-use crate::{CheckClient, ListResult, Namespace, Obj, Rel, Timestamp, UserId};
+use crate::guard::{evaluate, Clause, Guard, GuardOutcome};
+use crate::{trace, zookie, CheckClient, ListResult, Namespace, Obj, Rel, Timestamp, UserId};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::OnceCell;
 
 type CheckKey = (String, String, String, String);
@@ -52,7 +55,9 @@ impl RequestMemo {
 
     /// Fixes the evaluation zookie used for every memoized check/list of this
     /// request (e.g. from a `check_ts` cookie). `None` accepts any current
-    /// snapshot.
+    /// snapshot, or the request's zookie inside a
+    /// [`ZookieJar`](crate::zookie::ZookieJar) scope (see
+    /// [`CheckClient::check`]).
     pub fn with_timestamp(mut self, ts: Option<Timestamp>) -> Self {
         self.ts = ts;
         self
@@ -65,12 +70,24 @@ impl RequestMemo {
         self
     }
 
-    fn report(&self, op: &str, hit: bool) {
+    fn report(&self, op: &'static str, hit: bool) {
         if let Some(observe) = &self.observe {
             observe(op, hit);
         }
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = self.client.metrics() {
+            metrics.memo_lookup(op, hit);
+        }
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.memo.check",
+            skip_all,
+            fields(ns = %ns.0, obj = %obj.0, rel = %rel.0, cache = tracing::field::Empty)
+        )
+    )]
     pub async fn check(
         &self,
         ns: Namespace,
@@ -88,17 +105,48 @@ impl RequestMemo {
             let mut map = self.checks.lock().expect("memo mutex poisoned");
             map.entry(key).or_default().clone()
         };
-        self.report("check", cell.initialized());
+        let hit = cell.initialized();
+        self.report("check", hit);
+        trace::record("cache", if hit { "hit" } else { "miss" });
+        // Set when this call runs the RPC; the client then audits it. Every
+        // other caller — a hit, or a miss that waited on another task's
+        // RPC — is audited here.
+        let mut ran = false;
         let result = cell
             .get_or_try_init(|| {
+                ran = true;
                 let mut client = self.client.clone();
                 let ts = self.ts.clone();
+                let (ns, obj, rel, user_id) =
+                    (ns.clone(), obj.clone(), rel.clone(), user_id.clone());
                 async move { client.check(ns, obj, rel, user_id, ts).await }
             })
             .await?;
+        if !ran {
+            if let Some(sink) = self.client.audit_sink() {
+                let ts = zookie::or_current(self.ts.clone());
+                let check = (&ns, &obj, &rel, &user_id);
+                let result = Ok(result.clone());
+                sink.record(&DecisionRecord::new(
+                    check,
+                    ts,
+                    &result,
+                    Duration::ZERO,
+                    true,
+                ));
+            }
+        }
         Ok(result.clone())
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.memo.list",
+            skip_all,
+            fields(ns = %ns.0, rel = %rel.0, cache = tracing::field::Empty)
+        )
+    )]
     pub async fn list(
         &self,
         ns: Namespace,
@@ -110,7 +158,9 @@ impl RequestMemo {
             let mut map = self.lists.lock().expect("memo mutex poisoned");
             map.entry(key).or_default().clone()
         };
-        self.report("list", cell.initialized());
+        let hit = cell.initialized();
+        self.report("list", hit);
+        trace::record("cache", if hit { "hit" } else { "miss" });
         let result = cell
             .get_or_try_init(|| {
                 let mut client = self.client.clone();
@@ -120,4 +170,15 @@ impl RequestMemo {
             .await?;
         Ok(result.clone())
     }
+
+    /// Evaluates `guard` for `user_id` as [`CheckClient::check_guard`] does,
+    /// answering each clause through this memo.
+    pub async fn check_guard(
+        &self,
+        guard: &Guard,
+        user_id: UserId,
+    ) -> Result<GuardOutcome, CallError> {
+        let check = |clause: Clause| self.check(clause.ns, clause.obj, clause.rel, user_id.clone());
+        evaluate(guard, &check).await
+    }
 }
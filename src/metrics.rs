@@ -0,0 +1,344 @@
+//! Standard client metrics in the Prometheus text exposition format.
+//!
+//! A [`Metrics`] handle is a small registry. Hand clones of it to the
+//! [`CheckClient`] ([`CheckClient::with_metrics`]) and to session resolvers
+//! ([`ResolverConfig::metrics`]); [`RequestMemo`]s and watch streams report
+//! through their client; the RPC instruments are fed by its
+//! [`RpcObserver`] impl. [`Metrics::render`] produces the exposition text,
+//! e.g. for a `/metrics` route:
+//!
+//! ```rust,ignore
+//! let metrics = Metrics::new();
+//! let app = Router::new().route("/metrics", get({
+//!     let metrics = metrics.clone();
+//!     move || async move { ([(CONTENT_TYPE, metrics::CONTENT_TYPE)], metrics.render()) }
+//! }));
+//! ```
+//!
+//! Instruments:
+//!
+//! - `nio_client_rpc_duration_seconds{method, outcome}` — histogram
+//! - `nio_client_rpc_errors_total{method, code}` — gRPC code of failures
+//! - `nio_client_session_cache_total{result}` — hit / tombstone (a cached
+//!   unknown token) / miss / stale (a miss answered from a stale entry
+//!   after a failed fetch)
+//! - `nio_client_memo_lookups_total{op, result}` — hit / miss
+//! - `nio_client_watch_lag_seconds{ns}` — age of the last event's watermark
+//! - `nio_client_watch_reconnects_total{ns}` — watches opened after an
+//!   earlier stream on the namespace ended
+//!
+//! [`CheckClient`]: crate::CheckClient
+//! [`CheckClient::with_metrics`]: crate::CheckClient::with_metrics
+//! [`ResolverConfig::metrics`]: crate::session::ResolverConfig::metrics
+//! [`RequestMemo`]: crate::memo::RequestMemo
+
+use crate::observe::{RpcEvent, RpcObserver};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Content type of [`Metrics::render`].
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds (seconds) of the RPC latency histogram buckets.
+const BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Default)]
+struct Registry {
+    rpc_duration: BTreeMap<Labels, Histogram>,
+    rpc_errors: BTreeMap<Labels, u64>,
+    session_cache: BTreeMap<Labels, u64>,
+    memo_lookups: BTreeMap<Labels, u64>,
+    watch_lag: BTreeMap<Labels, f64>,
+    watch_reconnects: BTreeMap<Labels, u64>,
+    /// Ended watch streams per namespace not yet followed by a new watch.
+    watch_ended: BTreeMap<String, u64>,
+}
+
+/// Registry handle. Cheap to clone; clones share the same instruments.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl std::fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut Registry) -> T) -> T {
+        f(&mut self.registry.lock().expect("metrics mutex poisoned"))
+    }
+
+    /// One session resolution: `hit`, `miss`, `stale` or `tombstone`.
+    pub(crate) fn session_cache(&self, result: &'static str) {
+        self.with(|r| {
+            *r.session_cache
+                .entry(vec![("result", result.into())])
+                .or_default() += 1
+        });
+    }
+
+    /// One memo lookup of `op` (`check` / `list`).
+    pub(crate) fn memo_lookup(&self, op: &'static str, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        let labels = vec![("op", op.to_string()), ("result", result.into())];
+        self.with(|r| *r.memo_lookups.entry(labels).or_default() += 1);
+    }
+
+    /// A watch on `ns` was opened.
+    pub(crate) fn watch_started(&self, ns: &str) {
+        self.with(|r| match r.watch_ended.get_mut(ns) {
+            Some(ended) if *ended > 0 => {
+                *ended -= 1;
+                *r.watch_reconnects
+                    .entry(vec![("ns", ns.into())])
+                    .or_default() += 1;
+            }
+            _ => {}
+        });
+    }
+
+    /// A watch stream on `ns` ended (cleanly or not).
+    pub(crate) fn watch_ended(&self, ns: &str) {
+        self.with(|r| *r.watch_ended.entry(ns.to_string()).or_default() += 1);
+    }
+
+    /// A watch event on `ns` whose watermark is `lag` old.
+    pub(crate) fn watch_lag(&self, ns: &str, lag: Duration) {
+        self.with(|r| {
+            r.watch_lag
+                .insert(vec![("ns", ns.into())], lag.as_secs_f64());
+        });
+    }
+
+    /// The registry in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        self.with(|r| {
+            let mut out = String::new();
+            header(
+                &mut out,
+                "nio_client_rpc_duration_seconds",
+                "histogram",
+                "Latency of nio RPCs by method and outcome.",
+            );
+            for (labels, h) in &r.rpc_duration {
+                let name = "nio_client_rpc_duration_seconds";
+                for (count, le) in h.buckets.iter().zip(BUCKETS) {
+                    let le = ("le", le.to_string());
+                    sample(&mut out, name, "_bucket", labels, Some(le), *count);
+                }
+                let le = ("le", "+Inf".to_string());
+                sample(&mut out, name, "_bucket", labels, Some(le), h.count);
+                sample(&mut out, name, "_sum", labels, None, h.sum);
+                sample(&mut out, name, "_count", labels, None, h.count);
+            }
+            counter(
+                &mut out,
+                "nio_client_rpc_errors_total",
+                "Failed nio RPCs by method and gRPC code.",
+                &r.rpc_errors,
+            );
+            counter(
+                &mut out,
+                "nio_client_session_cache_total",
+                "Session resolutions by cache result.",
+                &r.session_cache,
+            );
+            counter(
+                &mut out,
+                "nio_client_memo_lookups_total",
+                "Request memo lookups by operation and result.",
+                &r.memo_lookups,
+            );
+            header(
+                &mut out,
+                "nio_client_watch_lag_seconds",
+                "gauge",
+                "Age of the last watch event's watermark.",
+            );
+            for (labels, lag) in &r.watch_lag {
+                sample(
+                    &mut out,
+                    "nio_client_watch_lag_seconds",
+                    "",
+                    labels,
+                    None,
+                    lag,
+                );
+            }
+            counter(
+                &mut out,
+                "nio_client_watch_reconnects_total",
+                "Watches reopened after a stream on the namespace ended.",
+                &r.watch_reconnects,
+            );
+            out
+        })
+    }
+}
+
+/// Latency by method and outcome, failures by gRPC code.
+impl RpcObserver for Metrics {
+    fn observe(&self, event: &RpcEvent) {
+        let method = event.method.as_str();
+        let outcome = if event.is_error() { "error" } else { "ok" };
+        let secs = event.duration.as_secs_f64();
+        self.with(|r| {
+            let labels = vec![("method", method.to_string()), ("outcome", outcome.into())];
+            let h = r.rpc_duration.entry(labels).or_default();
+            for (bucket, le) in h.buckets.iter_mut().zip(BUCKETS) {
+                if secs <= le {
+                    *bucket += 1;
+                }
+            }
+            h.sum += secs;
+            h.count += 1;
+            if event.is_error() {
+                let code = format!("{:?}", event.code);
+                let labels = vec![("method", method.to_string()), ("code", code)];
+                *r.rpc_errors.entry(labels).or_default() += 1;
+            }
+        });
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}");
+}
+
+fn counter(out: &mut String, name: &str, help: &str, values: &BTreeMap<Labels, u64>) {
+    header(out, name, "counter", help);
+    for (labels, value) in values {
+        sample(out, name, "", labels, None, value);
+    }
+}
+
+fn sample(
+    out: &mut String,
+    name: &str,
+    suffix: &str,
+    labels: &Labels,
+    extra: Option<(&'static str, String)>,
+    value: impl std::fmt::Display,
+) {
+    let _ = write!(out, "{name}{suffix}");
+    let mut labels = labels
+        .iter()
+        .map(|(k, v)| (*k, v.as_str()))
+        .collect::<Vec<_>>();
+    if let Some((k, v)) = &extra {
+        labels.push((k, v));
+    }
+    if !labels.is_empty() {
+        out.push('{');
+        for (i, (k, v)) in labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let v = v
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            let _ = write!(out, "{k}=\"{v}\"");
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {value}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observe::RpcMethod;
+
+    fn rpc(m: &Metrics, method: RpcMethod, millis: u64, code: tonic::Code) {
+        m.observe(&RpcEvent {
+            method,
+            request: String::new(),
+            duration: Duration::from_millis(millis),
+            code,
+            response_size: 0,
+            zookie: None,
+        });
+    }
+
+    #[test]
+    fn renders_histogram_buckets_cumulatively() {
+        let m = Metrics::new();
+        rpc(&m, RpcMethod::Check, 3, tonic::Code::Ok);
+        rpc(&m, RpcMethod::Check, 30, tonic::Code::Ok);
+        let text = m.render();
+        let line = |l: &str| text.lines().any(|x| x == l);
+        assert!(line("# TYPE nio_client_rpc_duration_seconds histogram"));
+        assert!(line(
+            "nio_client_rpc_duration_seconds_bucket{method=\"check\",outcome=\"ok\",le=\"0.0025\"} 0"
+        ));
+        assert!(line(
+            "nio_client_rpc_duration_seconds_bucket{method=\"check\",outcome=\"ok\",le=\"0.005\"} 1"
+        ));
+        assert!(line(
+            "nio_client_rpc_duration_seconds_bucket{method=\"check\",outcome=\"ok\",le=\"0.05\"} 2"
+        ));
+        assert!(line(
+            "nio_client_rpc_duration_seconds_bucket{method=\"check\",outcome=\"ok\",le=\"+Inf\"} 2"
+        ));
+        assert!(line(
+            "nio_client_rpc_duration_seconds_count{method=\"check\",outcome=\"ok\"} 2"
+        ));
+    }
+
+    #[test]
+    fn counts_errors_by_code_and_cache_results() {
+        let m = Metrics::new();
+        rpc(&m, RpcMethod::List, 0, tonic::Code::Unavailable);
+        m.session_cache("hit");
+        m.session_cache("hit");
+        m.memo_lookup("check", false);
+        let text = m.render();
+        assert!(
+            text.contains("nio_client_rpc_errors_total{method=\"list\",code=\"Unavailable\"} 1\n")
+        );
+        assert!(text.contains("nio_client_session_cache_total{result=\"hit\"} 2\n"));
+        assert!(text.contains("nio_client_memo_lookups_total{op=\"check\",result=\"miss\"} 1\n"));
+    }
+
+    #[test]
+    fn counts_a_reconnect_only_after_a_stream_ended() {
+        let m = Metrics::new();
+        m.watch_started("doc");
+        m.watch_ended("doc");
+        m.watch_started("doc");
+        m.watch_started("doc");
+        assert!(m
+            .render()
+            .contains("nio_client_watch_reconnects_total{ns=\"doc\"} 1\n"));
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        let m = Metrics::new();
+        m.watch_lag("a\"b", Duration::from_secs(2));
+        assert!(m
+            .render()
+            .contains("nio_client_watch_lag_seconds{ns=\"a\\\"b\"} 2\n"));
+    }
+}
@@ -0,0 +1,79 @@
+//! Per-RPC observer hooks.
+//!
+//! An [`RpcObserver`] installed with [`CheckClient::with_rpc_observer`] gets
+//! one [`RpcEvent`] after every RPC the client makes: check, list, expand,
+//! content change check, watch (opening the stream), list namespaces, read
+//! and write. The event names the method and summarizes the request. It
+//! also carries the latency, the gRPC status code, the encoded response size
+//! and the zookie the call ran at or returned.
+//!
+//! The positional hooks ([`CheckClient::with_observe_check`],
+//! [`CheckClient::with_observe_list`]) keep working next to it.
+//!
+//! [`CheckClient::with_rpc_observer`]: crate::CheckClient::with_rpc_observer
+//! [`CheckClient::with_observe_check`]: crate::CheckClient::with_observe_check
+//! [`CheckClient::with_observe_list`]: crate::CheckClient::with_observe_list
+
+use crate::Timestamp;
+use std::time::Duration;
+
+/// Receives one event per RPC. Called inline on the calling task, so
+/// implementations must not block.
+pub trait RpcObserver: Send + Sync + 'static {
+    fn observe(&self, event: &RpcEvent);
+}
+
+/// The RPC an [`RpcEvent`] reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RpcMethod {
+    Check,
+    List,
+    Expand,
+    ContentChangeCheck,
+    Watch,
+    ListNamespaces,
+    Read,
+    Write,
+}
+
+impl RpcMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RpcMethod::Check => "check",
+            RpcMethod::List => "list",
+            RpcMethod::Expand => "expand",
+            RpcMethod::ContentChangeCheck => "content_change_check",
+            RpcMethod::Watch => "watch",
+            RpcMethod::ListNamespaces => "list_namespaces",
+            RpcMethod::Read => "read",
+            RpcMethod::Write => "write",
+        }
+    }
+}
+
+/// One finished RPC.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RpcEvent {
+    pub method: RpcMethod,
+    /// What was asked: `doc:1#viewer@u1` for a check or content change
+    /// check, `doc:*#viewer@u1` for a list, `doc:1#viewer` for an expand,
+    /// the namespace for a watch, `2 filters` for a read, `+2 -1` for a
+    /// write; empty for list namespaces.
+    pub request: String,
+    pub duration: Duration,
+    /// `Code::Ok` on success.
+    pub code: tonic::Code,
+    /// Encoded size of the response message in bytes; 0 for failures and
+    /// for watch.
+    pub response_size: usize,
+    /// The zookie the call was evaluated at least as fresh as (check, watch)
+    /// or returned (list, expand, content change check, read, write). `None`
+    /// for none or [`Timestamp::EMPTY`].
+    pub zookie: Option<Timestamp>,
+}
+
+impl RpcEvent {
+    pub fn is_error(&self) -> bool {
+        self.code != tonic::Code::Ok
+    }
+}
@@ -10,8 +10,12 @@
 //! concurrent misses, refresh-ahead for hot entries, and an opt-in
 //! stale-if-error window.
 
+use crate::credentials::CredentialError;
+use crate::error::ErrorKind;
 use crate::pb::session_service_client::SessionServiceClient;
 use crate::pb::{resolve_response, ResolveRequest};
+use crate::trace;
+use crate::{Codec, GrpcTransport};
 use chrono::{DateTime, Utc};
 use futures::future::{BoxFuture, FutureExt, Shared};
 use sha2::{Digest, Sha256};
@@ -20,6 +24,7 @@ use std::future::Future;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tonic::codec::CompressionEncoding;
 use tonic::transport::Channel;
 
 /// Bounds a single fill's fetch (Go: resolveTimeout). The fill runs on a
@@ -57,19 +62,62 @@ pub struct ResolvedSession {
 }
 
 /// A resolution failure. `not_found` is *not* an error — it is `Ok(None)`.
-/// Only genuine faults (transport, backend) are errors; `Transport` marks the
-/// class eligible for stale-if-error fallback.
+/// Only genuine faults are errors. An unreachable or timed-out session
+/// service ([`Self::is_transport`]) is eligible for stale-if-error fallback.
+///
+/// Since 0.3.0 the gRPC resolver reports server faults as `Status`, never
+/// as `Transport` or `Backend`. Detect an outage with
+/// [`Self::is_transport`] or [`Self::kind`] instead of matching
+/// `Transport(_)`, and have custom fetchers return `Status`.
 #[derive(Clone, Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum ResolveError {
+    #[deprecated(
+        since = "0.3.0",
+        note = "return `Status` with `Code::Unavailable`; detect outages with `is_transport()`"
+    )]
     #[error("session resolve transport error: {0}")]
     Transport(String),
+    #[deprecated(
+        since = "0.3.0",
+        note = "return `Status` with the fault's code, e.g. `Code::Internal`"
+    )]
     #[error("session resolve backend error: {0}")]
     Backend(String),
+    /// The session service (or a custom fetcher's backend) answered with a
+    /// non-OK status.
+    #[error("session resolve status {code:?}: {message}")]
+    Status { code: tonic::Code, message: String },
+    /// No caller credential could be attached to the resolve call.
+    #[error("session resolve credentials: {0}")]
+    Credentials(CredentialError),
 }
 
 impl ResolveError {
-    fn is_transport(&self) -> bool {
-        matches!(self, ResolveError::Transport(_))
+    /// The session service could not be reached or did not answer in time
+    /// ([`ErrorKind::Unavailable`], [`ErrorKind::DeadlineExceeded`]).
+    pub fn is_transport(&self) -> bool {
+        matches!(
+            self.kind(),
+            ErrorKind::Unavailable | ErrorKind::DeadlineExceeded
+        )
+    }
+
+    /// `Transport` is [`ErrorKind::Unavailable`], `Backend`
+    /// [`ErrorKind::Internal`], and `Status` whatever [`ErrorKind::of`] maps
+    /// its code to.
+    pub fn kind(&self) -> ErrorKind {
+        #[allow(deprecated)]
+        match self {
+            ResolveError::Transport(_) => ErrorKind::Unavailable,
+            ResolveError::Backend(_) => ErrorKind::Internal,
+            ResolveError::Status { code, .. } => ErrorKind::of(*code),
+            ResolveError::Credentials(_) => ErrorKind::Unauthenticated,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
     }
 }
 
@@ -257,15 +305,34 @@ struct ResolverInner {
     cache: Mutex<Lru>,
     flight: SingleFlight,
     cfg: ResolverConfig,
+    #[cfg(feature = "metrics")]
+    metrics: Option<crate::metrics::Metrics>,
 }
 
 impl ResolverInner {
+    /// Counts one cache lookup `result`; no-op without the `metrics` feature.
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    fn count(&self, result: &'static str) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.session_cache(result);
+        }
+    }
+
     fn effective_ttl(&self) -> Duration {
         // Downward-only jitter U(0.8, 1.0): l1_ttl is a hard cap.
         let jitter = 0.8 + 0.2 * rand::random::<f64>();
         Duration::from_secs_f64(self.cfg.l1_ttl.as_secs_f64() * jitter)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "nio.session.resolve",
+            skip_all,
+            fields(cache = tracing::field::Empty, outcome = tracing::field::Empty)
+        )
+    )]
     async fn resolve(
         self: Arc<Self>,
         hash: String,
@@ -297,9 +364,17 @@ impl ResolverInner {
                         self.clone().spawn_refresh(hash.clone());
                     }
                 }
+                trace::record("cache", "hit");
+                trace::record("outcome", found(&entry.outcome));
+                self.count(match entry.outcome {
+                    Some(_) => "hit",
+                    None => "tombstone",
+                });
                 return Ok(entry.outcome);
             }
         }
+        trace::record("cache", "miss");
+        self.count("miss");
 
         // 2. Miss (or stale): capture a stale candidate, then single-flight fill.
         let stale = self.stale_candidate(&hash, now, now_wall);
@@ -314,24 +389,36 @@ impl ResolverInner {
             .await;
 
         match outcome {
-            Ok(v) => Ok(v),
+            Ok(v) => {
+                trace::record("outcome", found(&v));
+                Ok(v)
+            }
             Err(e) => {
                 if e.is_transport() {
                     if let Some((s, _fetched_at)) = stale {
                         log::warn!("session resolver: serving stale entry on transport error: {e}");
+                        trace::record("cache", "stale");
+                        trace::record("outcome", "found");
+                        self.count("stale");
                         return Ok(Some(s));
                     }
                 }
+                trace::record("outcome", "error");
                 Err(e)
             }
         }
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "nio.session.fill", skip_all, err(level = "debug"))
+    )]
     async fn fill(self: Arc<Self>, hash: String) -> Result<Option<ResolvedSession>, ResolveError> {
         let fetched = tokio::time::timeout(RESOLVE_TIMEOUT, self.fetcher.fetch(&hash))
             .await
-            .map_err(|_| {
-                ResolveError::Transport("session resolve timed out after 5s".to_string())
+            .map_err(|_| ResolveError::Status {
+                code: tonic::Code::DeadlineExceeded,
+                message: "session resolve timed out after 5s".to_string(),
             })??;
         let now = Instant::now();
         let entry = match &fetched {
@@ -412,6 +499,24 @@ impl CachedResolver {
                 flight: SingleFlight::default(),
                 fetcher,
                 cfg,
+                #[cfg(feature = "metrics")]
+                metrics: None,
+            }),
+        }
+    }
+
+    /// Counts cache hits, misses, tombstone hits and stale serves. Set it
+    /// on a new resolver: the one returned starts with an empty cache.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(self, metrics: crate::metrics::Metrics) -> Self {
+        let (fetcher, cfg) = (self.shared.fetcher.clone(), self.shared.cfg.clone());
+        CachedResolver {
+            shared: Arc::new(ResolverInner {
+                cache: Mutex::new(Lru::new(cfg.capacity)),
+                flight: SingleFlight::default(),
+                fetcher,
+                cfg,
+                metrics: Some(metrics),
             }),
         }
     }
@@ -448,14 +553,62 @@ impl GrpcSessionResolver {
     // Factory returning the object-safe trait; not a `Self` ctor.
     #[allow(clippy::new_ret_no_self)]
     pub fn new(channel: Channel, cfg: ResolverConfig) -> Arc<dyn SessionResolver> {
-        let client = SessionServiceClient::new(channel);
+        Self::from_transport(crate::transport(channel), cfg)
+    }
+
+    /// Resolves over any gRPC transport, e.g. a [`Channel`] with an
+    /// interceptor adding credentials. See [`crate::transport`].
+    pub fn from_transport(
+        transport: GrpcTransport,
+        cfg: ResolverConfig,
+    ) -> Arc<dyn SessionResolver> {
+        Self::cached(transport, cfg).into_dyn()
+    }
+
+    /// [`Self::from_transport`] before erasure, to configure further, e.g.
+    /// with [`CachedResolver::with_metrics`].
+    pub fn cached(transport: GrpcTransport, cfg: ResolverConfig) -> CachedResolver {
+        Self::with_codec(transport, cfg, Codec::default())
+    }
+
+    pub(crate) fn with_codec(
+        transport: GrpcTransport,
+        cfg: ResolverConfig,
+        codec: Codec,
+    ) -> CachedResolver {
+        let mut client = SessionServiceClient::new(transport);
+        if codec.gzip {
+            client = client
+                .send_compressed(CompressionEncoding::Gzip)
+                .accept_compressed(CompressionEncoding::Gzip);
+        }
+        if let Some(bytes) = codec.max_message_size {
+            client = client
+                .max_decoding_message_size(bytes)
+                .max_encoding_message_size(bytes);
+        }
         let fetcher: Arc<dyn SessionFetcher> = Arc::new(GrpcFetcher { client });
+        CachedResolver::new(fetcher, cfg)
+    }
+}
+
+/// Machine-client path: API keys resolved to their service account through
+/// a caller-supplied [`SessionFetcher`] (e.g. a point read on the key
+/// store). Keys are hashed with [`token_hash`] like session tokens and get
+/// the same L1 cache, tombstones and single-flight; the fetched session's
+/// `principal` is the service account checks run as.
+pub struct ApiKeyResolver;
+
+impl ApiKeyResolver {
+    // Factory returning the object-safe trait; not a `Self` ctor.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(fetcher: Arc<dyn SessionFetcher>, cfg: ResolverConfig) -> Arc<dyn SessionResolver> {
         CachedResolver::new(fetcher, cfg).into_dyn()
     }
 }
 
 struct GrpcFetcher {
-    client: SessionServiceClient<Channel>,
+    client: SessionServiceClient<GrpcTransport>,
 }
 
 impl SessionFetcher for GrpcFetcher {
@@ -464,7 +617,7 @@ impl SessionFetcher for GrpcFetcher {
         let token_hash = token_hash.to_string();
         Box::pin(async move {
             let resp = client
-                .resolve(ResolveRequest { token_hash })
+                .resolve(trace::request(ResolveRequest { token_hash }))
                 .await
                 .map_err(classify_status)?;
             match resp.into_inner().outcome {
@@ -481,12 +634,21 @@ impl SessionFetcher for GrpcFetcher {
     }
 }
 
+/// The span `outcome` of a resolution.
+fn found(outcome: &Option<ResolvedSession>) -> &'static str {
+    match outcome {
+        Some(_) => "found",
+        None => "not_found",
+    }
+}
+
 fn classify_status(status: tonic::Status) -> ResolveError {
-    match status.code() {
-        tonic::Code::Unavailable | tonic::Code::DeadlineExceeded => {
-            ResolveError::Transport(status.message().to_string())
-        }
-        _ => ResolveError::Backend(status.message().to_string()),
+    if let Some(e) = CredentialError::of(&status) {
+        return ResolveError::Credentials(e);
+    }
+    ResolveError::Status {
+        code: status.code(),
+        message: status.message().to_string(),
     }
 }
 
@@ -512,6 +674,7 @@ mod tests {
         calls: Arc<AtomicUsize>,
     }
 
+    #[allow(deprecated)]
     impl SessionFetcher for FailingFetcher {
         fn fetch<'a>(&'a self, _token_hash: &'a str) -> ResolveFuture<'a> {
             self.calls.fetch_add(1, Ordering::Relaxed);
@@ -624,6 +787,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(deprecated)]
     async fn transport_error_propagates_without_stale_window() {
         let calls = Arc::new(AtomicUsize::new(0));
         let fetcher = Arc::new(FailingFetcher {
@@ -744,7 +908,8 @@ mod tests {
             .resolve("k")
             .await
             .expect_err("hung backend must time out");
-        assert!(matches!(err, ResolveError::Transport(_)));
+        assert_eq!(err.kind(), ErrorKind::DeadlineExceeded);
+        assert!(err.is_transport());
     }
 
     struct SwitchableFetcher {
@@ -775,6 +940,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(deprecated)]
     async fn transport_error_serves_stale_within_window() {
         let fetcher = Arc::new(SwitchableFetcher {
             session: session_valid_for(120),
@@ -792,6 +958,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(deprecated)]
     async fn backend_error_propagates_despite_stale_window() {
         let fetcher = Arc::new(SwitchableFetcher {
             session: session_valid_for(120),
@@ -808,22 +975,27 @@ mod tests {
 
     #[test]
     fn classify_status_transport_vs_backend() {
-        assert!(matches!(
-            classify_status(tonic::Status::unavailable("x")),
-            ResolveError::Transport(_)
-        ));
-        assert!(matches!(
-            classify_status(tonic::Status::deadline_exceeded("x")),
-            ResolveError::Transport(_)
-        ));
-        assert!(matches!(
-            classify_status(tonic::Status::internal("x")),
-            ResolveError::Backend(_)
-        ));
-        assert!(matches!(
-            classify_status(tonic::Status::permission_denied("x")),
-            ResolveError::Backend(_)
-        ));
+        assert!(classify_status(tonic::Status::unavailable("x")).is_transport());
+        assert!(classify_status(tonic::Status::deadline_exceeded("x")).is_transport());
+        assert!(!classify_status(tonic::Status::internal("x")).is_transport());
+        assert!(!classify_status(tonic::Status::permission_denied("x")).is_transport());
+    }
+
+    #[test]
+    fn classify_status_kind_agrees_with_error_kind_of() {
+        use tonic::Code;
+        for code in [
+            Code::Unavailable,
+            Code::DeadlineExceeded,
+            Code::ResourceExhausted,
+            Code::PermissionDenied,
+            Code::Unauthenticated,
+            Code::Internal,
+        ] {
+            let err = classify_status(tonic::Status::new(code, "x"));
+            assert_eq!(err.kind(), ErrorKind::of(code), "{code:?}");
+        }
+        assert!(classify_status(tonic::Status::resource_exhausted("x")).is_retryable());
     }
 
     #[test]
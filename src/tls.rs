@@ -0,0 +1,372 @@
+//! TLS material that can change while the process runs.
+//!
+//! [`connect_channel`] fixes the TLS config at connect time. A
+//! [`ReloadableChannel`] loads it from a [`TlsSource`] instead: PEM files
+//! ([`TlsFiles`]) or a callback ([`TlsCallback`]). It polls the source every
+//! [`TlsReloadConfig::poll_interval`] (or on [`ReloadableChannel::reload`])
+//! and, when the material changed, connects a new channel and sends new
+//! calls over it. Calls and `watch` streams already running keep the
+//! connection they started on until they finish. If the new material does
+//! not connect (e.g. a certificate written before its key), the current
+//! channel stays in use and the next poll tries again.
+//!
+//! Use it as the transport of the check client and of the session
+//! resolver:
+//!
+//! ```rust,ignore
+//! let source = Arc::new(TlsFiles::new("/certs/tls.crt", "/certs/tls.key").with_ca("/certs/ca.crt"));
+//! let check = ReloadableChannel::connect(check_uri, source.clone(), TlsReloadConfig::default()).await?;
+//! let check_client = CheckClient::from_transport(nio_client::transport(check));
+//! ```
+//!
+//! [`connect_channel`]: crate::connect_channel
+
+use crate::{connect_with, ChannelOptions, ConnectError, ErrorKind, Target};
+use futures::future::BoxFuture;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock, Weak};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
+
+/// PEM-encoded TLS material for a client channel.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct TlsMaterial {
+    /// CA bundle the server certificate is verified against.
+    pub ca: Option<Vec<u8>>,
+    /// Client certificate chain and private key for mTLS.
+    pub identity: Option<(Vec<u8>, Vec<u8>)>,
+    /// Server name to verify instead of the URI's host.
+    pub domain: Option<String>,
+}
+
+impl TlsMaterial {
+    pub fn config(&self) -> ClientTlsConfig {
+        let mut config = ClientTlsConfig::new();
+        if let Some(ca) = &self.ca {
+            config = config.ca_certificate(Certificate::from_pem(ca));
+        }
+        if let Some((cert, key)) = &self.identity {
+            config = config.identity(Identity::from_pem(cert, key));
+        }
+        if let Some(domain) = &self.domain {
+            config = config.domain_name(domain);
+        }
+        config
+    }
+}
+
+impl std::fmt::Debug for TlsMaterial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsMaterial")
+            .field("ca", &self.ca.as_ref().map(Vec::len))
+            .field("identity", &self.identity.as_ref().map(|_| "<redacted>"))
+            .field("domain", &self.domain)
+            .finish()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("read TLS file {path}: {message}")]
+    File { path: PathBuf, message: String },
+    #[error("TLS source: {0}")]
+    Source(String),
+    #[error("connect with reloaded TLS material")]
+    Connect(#[source] ConnectError),
+}
+
+impl TlsError {
+    /// Unreadable or unusable material is [`ErrorKind::InvalidArgument`]; a
+    /// failed connect reports the [`ConnectError`]'s kind.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            TlsError::File { .. } | TlsError::Source(_) => ErrorKind::InvalidArgument,
+            TlsError::Connect(e) => e.kind(),
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
+}
+
+/// Where a [`ReloadableChannel`] gets its TLS material. Polled for changes.
+pub trait TlsSource: Send + Sync + 'static {
+    fn load(&self) -> BoxFuture<'_, Result<TlsMaterial, TlsError>>;
+}
+
+/// Certificate, key and optional CA read from PEM files.
+#[derive(Clone, Debug)]
+pub struct TlsFiles {
+    identity: Option<(PathBuf, PathBuf)>,
+    ca: Option<PathBuf>,
+    domain: Option<String>,
+}
+
+impl TlsFiles {
+    pub fn new(cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        TlsFiles {
+            identity: Some((cert.into(), key.into())),
+            ca: None,
+            domain: None,
+        }
+    }
+
+    /// Only a CA bundle to verify the server with; no client certificate.
+    pub fn ca_only(ca: impl Into<PathBuf>) -> Self {
+        TlsFiles {
+            identity: None,
+            ca: Some(ca.into()),
+            domain: None,
+        }
+    }
+
+    pub fn with_ca(mut self, ca: impl Into<PathBuf>) -> Self {
+        self.ca = Some(ca.into());
+        self
+    }
+
+    pub fn with_domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+}
+
+impl TlsSource for TlsFiles {
+    fn load(&self) -> BoxFuture<'_, Result<TlsMaterial, TlsError>> {
+        let files = self.clone();
+        Box::pin(async move {
+            let read = tokio::task::spawn_blocking(move || {
+                let read = |path: &PathBuf| {
+                    std::fs::read(path).map_err(|e| TlsError::File {
+                        path: path.clone(),
+                        message: e.to_string(),
+                    })
+                };
+                Ok(TlsMaterial {
+                    ca: files.ca.as_ref().map(read).transpose()?,
+                    identity: match &files.identity {
+                        Some((cert, key)) => Some((read(cert)?, read(key)?)),
+                        None => None,
+                    },
+                    domain: files.domain,
+                })
+            });
+            read.await.map_err(|e| TlsError::Source(e.to_string()))?
+        })
+    }
+}
+
+pub type TlsLoadFn =
+    Arc<dyn Fn() -> BoxFuture<'static, Result<TlsMaterial, TlsError>> + Send + Sync>;
+
+/// TLS material from an application callback, e.g. a secret store client.
+pub struct TlsCallback(TlsLoadFn);
+
+impl TlsCallback {
+    pub fn new(f: TlsLoadFn) -> Self {
+        TlsCallback(f)
+    }
+}
+
+impl TlsSource for TlsCallback {
+    fn load(&self) -> BoxFuture<'_, Result<TlsMaterial, TlsError>> {
+        (self.0)()
+    }
+}
+
+/// Tunables for [`ReloadableChannel`].
+#[derive(Clone, Debug)]
+pub struct TlsReloadConfig {
+    /// How often the source is checked for new material; zero disables
+    /// polling (reload with [`ReloadableChannel::reload`] only).
+    pub poll_interval: Duration,
+}
+
+impl Default for TlsReloadConfig {
+    /// Poll every 60s.
+    fn default() -> Self {
+        TlsReloadConfig {
+            poll_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+struct Shared {
+    target: Target,
+    options: ChannelOptions,
+    source: Arc<dyn TlsSource>,
+    channel: RwLock<Channel>,
+    /// The material `channel` was built from; held across a reload so
+    /// reloads run one at a time.
+    material: tokio::sync::Mutex<TlsMaterial>,
+}
+
+/// A TLS channel whose material is reloaded from a [`TlsSource`]. Cheap to
+/// clone; clones share the current channel. Turn it into a transport with
+/// [`crate::transport`].
+#[derive(Clone)]
+pub struct ReloadableChannel {
+    shared: Arc<Shared>,
+}
+
+impl std::fmt::Debug for ReloadableChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReloadableChannel")
+            .field("target", &self.shared.target)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReloadableChannel {
+    /// Loads the material, connects (see [`connect_channel`](crate::connect_channel)) and, unless
+    /// `cfg.poll_interval` is zero, starts polling `source` in a background
+    /// task that ends when the last clone is dropped.
+    pub async fn connect(
+        target: impl Into<Target>,
+        source: Arc<dyn TlsSource>,
+        cfg: TlsReloadConfig,
+    ) -> Result<Self, TlsError> {
+        Self::connect_with(target.into(), source, cfg, ChannelOptions::default()).await
+    }
+
+    /// [`Self::connect`] with the builder's endpoint settings, kept for
+    /// every reconnect.
+    pub(crate) async fn connect_with(
+        target: Target,
+        source: Arc<dyn TlsSource>,
+        cfg: TlsReloadConfig,
+        options: ChannelOptions,
+    ) -> Result<Self, TlsError> {
+        let material = source.load().await?;
+        let channel = connect_with(target.clone(), Some(material.config()), &options)
+            .await
+            .map_err(TlsError::Connect)?;
+        let shared = Arc::new(Shared {
+            target,
+            options,
+            source,
+            channel: RwLock::new(channel),
+            material: tokio::sync::Mutex::new(material),
+        });
+        if !cfg.poll_interval.is_zero() {
+            tokio::spawn(poll(Arc::downgrade(&shared), cfg.poll_interval));
+        }
+        Ok(ReloadableChannel { shared })
+    }
+
+    /// Loads the material now and switches to a new channel if it changed.
+    /// Returns whether it switched; on error the current channel is kept.
+    pub async fn reload(&self) -> Result<bool, TlsError> {
+        let mut material = self.shared.material.lock().await;
+        let next = self.shared.source.load().await?;
+        if next == *material {
+            return Ok(false);
+        }
+        let channel = connect_with(
+            self.shared.target.clone(),
+            Some(next.config()),
+            &self.shared.options,
+        )
+        .await
+        .map_err(TlsError::Connect)?;
+        *self.shared.channel.write().expect("channel lock poisoned") = channel;
+        *material = next;
+        log::info!(
+            "nio-client: reloaded TLS material for {}",
+            self.shared.target
+        );
+        Ok(true)
+    }
+
+    /// The channel new calls currently go over.
+    pub fn channel(&self) -> Channel {
+        self.shared
+            .channel
+            .read()
+            .expect("channel lock poisoned")
+            .clone()
+    }
+}
+
+async fn poll(shared: Weak<Shared>, every: Duration) {
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + every, every);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        if let Err(e) = (ReloadableChannel { shared }).reload().await {
+            log::warn!("nio-client: TLS reload failed, keeping the current channel: {e}");
+        }
+    }
+}
+
+impl tower::Service<http::Request<tonic::body::Body>> for ReloadableChannel {
+    type Response = http::Response<tonic::body::Body>;
+    type Error = tonic::transport::Error;
+    type Future = tower::util::Oneshot<Channel, http::Request<tonic::body::Body>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Readiness is checked on the channel each call is sent over.
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<tonic::body::Body>) -> Self::Future {
+        tower::ServiceExt::oneshot(self.channel(), request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nio-tls-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn files_are_read_into_material() {
+        let dir = temp_dir("read");
+        std::fs::write(dir.join("tls.crt"), "CERT").unwrap();
+        std::fs::write(dir.join("tls.key"), "KEY").unwrap();
+        std::fs::write(dir.join("ca.crt"), "CA").unwrap();
+        let files = TlsFiles::new(dir.join("tls.crt"), dir.join("tls.key"))
+            .with_ca(dir.join("ca.crt"))
+            .with_domain("check.internal");
+        let material = files.load().await.unwrap();
+        assert_eq!(material.ca.as_deref(), Some(&b"CA"[..]));
+        assert_eq!(material.identity, Some((b"CERT".to_vec(), b"KEY".to_vec())));
+        assert_eq!(material.domain.as_deref(), Some("check.internal"));
+
+        std::fs::write(dir.join("tls.crt"), "CERT2").unwrap();
+        assert_ne!(
+            files.load().await.unwrap(),
+            material,
+            "rotation is a change"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_files_are_errors() {
+        let files = TlsFiles::new("/nonexistent/tls.crt", "/nonexistent/tls.key");
+        assert!(matches!(files.load().await, Err(TlsError::File { .. })));
+    }
+
+    #[test]
+    fn material_debug_redacts_the_key() {
+        let material = TlsMaterial {
+            identity: Some((b"CERT".to_vec(), b"s3cret".to_vec())),
+            ..Default::default()
+        };
+        assert_eq!(
+            format!("{material:?}"),
+            r#"TlsMaterial { ca: None, identity: Some("<redacted>"), domain: None }"#
+        );
+    }
+}
@@ -0,0 +1,55 @@
+//! Support for the optional `tracing` / `opentelemetry` features.
+//!
+//! Spans are declared with `#[cfg_attr(feature = "tracing",
+//! tracing::instrument(..))]` on the instrumented functions; this module
+//! holds what they share. Every outgoing RPC is built with [`request`],
+//! which (with `opentelemetry`) carries the current span's W3C trace
+//! context to check and nio-client.
+
+/// Records `value` on the current span's declared `field`; no-op without
+/// the `tracing` feature.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn record(field: &'static str, value: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record(field, value);
+}
+
+/// An outgoing gRPC request for `message`.
+pub(crate) fn request<T>(message: T) -> tonic::Request<T> {
+    #[cfg_attr(not(feature = "opentelemetry"), allow(unused_mut))]
+    let mut request = tonic::Request::new(message);
+    #[cfg(feature = "opentelemetry")]
+    inject_trace_context(request.metadata_mut());
+    request
+}
+
+/// Sets `traceparent` (and a non-empty `tracestate`) from the current span's
+/// OpenTelemetry context; nothing for an invalid (unsampled, unexported)
+/// context.
+#[cfg(feature = "opentelemetry")]
+fn inject_trace_context(metadata: &mut tonic::metadata::MetadataMap) {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let context = tracing::Span::current().context();
+    let span = context.span();
+    let sc = span.span_context();
+    if !sc.is_valid() {
+        return;
+    }
+    let traceparent = format!(
+        "00-{}-{}-{:02x}",
+        sc.trace_id(),
+        sc.span_id(),
+        sc.trace_flags().to_u8()
+    );
+    if let Ok(value) = traceparent.parse() {
+        metadata.insert("traceparent", value);
+    }
+    let tracestate = sc.trace_state().header();
+    if !tracestate.is_empty() {
+        if let Ok(value) = tracestate.parse() {
+            metadata.insert("tracestate", value);
+        }
+    }
+}
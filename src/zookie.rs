@@ -0,0 +1,222 @@
+//! Request-scoped zookie propagation.
+//!
+//! A [`ZookieJar`] carries the freshest zookie known for one request: the one
+//! the client echoed back (e.g. from a `check_ts` cookie) and the commit
+//! zookies of writes made while handling it. Running the request inside
+//! [`ZookieJar::scope`] makes [`CheckClient::check`] / [`CheckClient::list`]
+//! calls without an explicit timestamp evaluate at least as fresh as the
+//! jar, and makes [`CheckClient::write`] (and its helpers) record their
+//! commit zookie in it — so a user sees their own permission changes on the
+//! next page load once the jar's zookie is echoed back.
+//!
+//! The scope is task-local: work spawned onto other tasks does not see it.
+//!
+//! This changes what existing calls send: inside a scope, a `None`
+//! timestamp on [`CheckClient::check`], [`CheckClient::list`],
+//! `check_guard` or a `RequestMemo` is the request's zookie, not "any
+//! snapshot". Pass [`Timestamp::empty`] where a call must not be bounded by
+//! it.
+//!
+//! [`CheckClient::check`]: crate::CheckClient::check
+//! [`CheckClient::list`]: crate::CheckClient::list
+//! [`CheckClient::write`]: crate::CheckClient::write
+
+use crate::Timestamp;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::cmp::Ordering;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "axum")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+tokio::task_local! {
+    static JAR: ZookieJar;
+}
+
+impl Timestamp {
+    /// Decodes the packed `[epoch:u8][millis:u48 BE]` wire value; `None` if
+    /// this is not a well-formed zookie.
+    pub(crate) fn decode(&self) -> Option<(u8, u64)> {
+        let bytes = STANDARD.decode(self.0.as_bytes()).ok()?;
+        let [epoch, m @ ..] = <[u8; 7]>::try_from(bytes).ok()?;
+        let millis = m.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+        Some((epoch, millis))
+    }
+
+    /// True for a well-formed zookie. The client only compares zookies to
+    /// keep the freshest; it never invents one.
+    pub fn is_valid(&self) -> bool {
+        self.decode().is_some()
+    }
+
+    /// False for a zookie naming a snapshot later than `limit`, which check
+    /// cannot have issued yet: a client made it up.
+    #[cfg(feature = "axum")]
+    pub(crate) fn not_after(&self, limit: SystemTime) -> bool {
+        let limit = limit
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        self.decode().is_some_and(|(_, millis)| millis <= limit)
+    }
+
+    /// Orders two zookies by the snapshot they name. Malformed zookies sort
+    /// before every well-formed one.
+    pub fn cmp_freshness(&self, other: &Timestamp) -> Ordering {
+        self.decode().cmp(&other.decode())
+    }
+}
+
+/// The freshest zookie seen while handling one request. Cheap to clone;
+/// clones share the same slot.
+#[derive(Clone, Debug, Default)]
+pub struct ZookieJar {
+    slot: Arc<Mutex<Option<Timestamp>>>,
+}
+
+impl ZookieJar {
+    /// A jar seeded with the zookie the client sent, if any. Malformed and
+    /// empty zookies are ignored.
+    pub fn new(initial: Option<Timestamp>) -> Self {
+        let jar = ZookieJar::default();
+        if let Some(ts) = initial {
+            jar.observe(ts);
+        }
+        jar
+    }
+
+    /// The freshest zookie so far.
+    pub fn get(&self) -> Option<Timestamp> {
+        self.slot.lock().expect("zookie jar mutex poisoned").clone()
+    }
+
+    /// Keeps `ts` if it is fresher than the current zookie.
+    pub fn observe(&self, ts: Timestamp) {
+        if !ts.is_valid() || ts == Timestamp::empty() {
+            return;
+        }
+        let mut slot = self.slot.lock().expect("zookie jar mutex poisoned");
+        let fresher = match slot.as_ref() {
+            None => true,
+            Some(current) => ts.cmp_freshness(current) == Ordering::Greater,
+        };
+        if fresher {
+            *slot = Some(ts);
+        }
+    }
+
+    /// Runs `fut` with this jar as the current request's jar.
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        JAR.scope(self, fut).await
+    }
+
+    /// The jar of the request being handled on this task, if any.
+    pub fn current() -> Option<ZookieJar> {
+        JAR.try_with(Clone::clone).ok()
+    }
+}
+
+/// `timestamp`, or the current request's zookie when the caller passed none.
+pub(crate) fn or_current(timestamp: Option<Timestamp>) -> Option<Timestamp> {
+    timestamp.or_else(|| ZookieJar::current().and_then(|jar| jar.get()))
+}
+
+/// The fresher of `ts` and the current request's zookie.
+pub(crate) fn at_least(ts: Timestamp) -> Timestamp {
+    match or_current(None) {
+        Some(current) if current.cmp_freshness(&ts) == Ordering::Greater => current,
+        _ => ts,
+    }
+}
+
+/// Records a commit zookie in the current request's jar, if any.
+pub(crate) fn record(ts: &Timestamp) {
+    if let Some(jar) = ZookieJar::current() {
+        jar.observe(ts.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zookie(epoch: u8, millis: u64) -> Timestamp {
+        let mut bytes = vec![epoch];
+        bytes.extend_from_slice(&millis.to_be_bytes()[2..]);
+        Timestamp(STANDARD.encode(bytes))
+    }
+
+    #[test]
+    fn decodes_packed_wire_value() {
+        assert_eq!(Timestamp::empty().decode(), Some((1, 0)));
+        assert_eq!(
+            zookie(1, 1_700_000_000_000).decode(),
+            Some((1, 1_700_000_000_000))
+        );
+        assert!(!Timestamp("not a zookie".into()).is_valid());
+        assert!(!Timestamp("AQAA".into()).is_valid(), "wrong length");
+    }
+
+    #[test]
+    fn freshness_orders_by_epoch_then_millis() {
+        assert_eq!(zookie(1, 5).cmp_freshness(&zookie(1, 4)), Ordering::Greater);
+        assert_eq!(zookie(2, 0).cmp_freshness(&zookie(1, 9)), Ordering::Greater);
+        assert_eq!(
+            Timestamp("junk".into()).cmp_freshness(&zookie(1, 0)),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "axum")]
+    fn future_zookies_are_after_the_limit() {
+        let limit = UNIX_EPOCH + std::time::Duration::from_millis(1_000);
+        assert!(zookie(1, 1_000).not_after(limit));
+        assert!(!zookie(1, 1_001).not_after(limit));
+        assert!(!Timestamp("junk".into()).not_after(limit));
+    }
+
+    #[test]
+    fn jar_keeps_the_freshest_valid_zookie() {
+        let jar = ZookieJar::new(Some(zookie(1, 10)));
+        jar.observe(zookie(1, 5));
+        assert_eq!(jar.get(), Some(zookie(1, 10)));
+        jar.observe(zookie(1, 20));
+        assert_eq!(jar.get(), Some(zookie(1, 20)));
+        jar.observe(Timestamp("junk".into()));
+        assert_eq!(jar.get(), Some(zookie(1, 20)));
+    }
+
+    #[test]
+    fn jar_ignores_empty_and_malformed_seeds() {
+        assert_eq!(ZookieJar::new(Some(Timestamp::empty())).get(), None);
+        assert_eq!(ZookieJar::new(Some(Timestamp("junk".into()))).get(), None);
+    }
+
+    #[tokio::test]
+    async fn scope_exposes_jar_to_current_task() {
+        assert!(ZookieJar::current().is_none());
+        let jar = ZookieJar::new(Some(zookie(1, 7)));
+        let seen = jar
+            .clone()
+            .scope(async {
+                record(&zookie(1, 9));
+                or_current(None)
+            })
+            .await;
+        assert_eq!(seen, Some(zookie(1, 9)));
+        assert_eq!(jar.get(), Some(zookie(1, 9)));
+        assert_eq!(or_current(Some(zookie(1, 1))), Some(zookie(1, 1)));
+    }
+
+    #[tokio::test]
+    async fn at_least_prefers_the_fresher_zookie() {
+        assert_eq!(at_least(zookie(1, 3)), zookie(1, 3));
+        let jar = ZookieJar::new(Some(zookie(1, 5)));
+        jar.scope(async {
+            assert_eq!(at_least(zookie(1, 3)), zookie(1, 5));
+            assert_eq!(at_least(zookie(1, 8)), zookie(1, 8));
+        })
+        .await;
+    }
+}
@@ -0,0 +1,144 @@
+//! `#[derive(WebResource)]`: the generated namespace / rel / parse / object
+//! and rejection type, exercised through a real axum router so path params
+//! are bound exactly as in production.
+#![cfg(feature = "derive")]
+
+use axum::body::Body;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{Method, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use nio_client::axum::{PathParamRejection, WebResource};
+use nio_client::{Namespace, Obj, Rel};
+use tower::ServiceExt;
+
+#[derive(WebResource)]
+#[nio(
+    namespace = "article",
+    get = "viewer",
+    put = "editor",
+    delete = "admin"
+)]
+struct Article {
+    #[nio(object)]
+    id: u64,
+}
+
+#[derive(WebResource)]
+#[nio(namespace = "doc", get = "doc.get")]
+struct FolderDoc {
+    #[nio(param = "folder_id")]
+    folder: String,
+    #[nio(object)]
+    doc: String,
+    #[nio(default)]
+    note: Option<String>,
+}
+
+/// Runs only `R::parse` so tests see the derived parse and its rejection.
+struct Parsed<R>(R);
+
+impl<S, R> FromRequestParts<S> for Parsed<R>
+where
+    R: WebResource + Send,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        R::parse(parts, state)
+            .await
+            .map(Parsed)
+            .map_err(IntoResponse::into_response)
+    }
+}
+
+async fn call(router: Router, uri: &str) -> (StatusCode, String) {
+    let resp = router
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = resp.status();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[test]
+fn namespace_object_and_rel_table() {
+    let a = Article { id: 7 };
+    assert_eq!(a.namespace(), Namespace("article".into()));
+    assert_eq!(a.object(), Obj("7".into()));
+    assert_eq!(a.rel(&Method::GET), Some(Rel::viewer()));
+    assert_eq!(a.rel(&Method::PUT), Some(Rel::editor()));
+    assert_eq!(a.rel(&Method::DELETE), Some(Rel::admin()));
+    assert_eq!(
+        a.rel(&Method::HEAD),
+        Some(Rel::viewer()),
+        "head follows get"
+    );
+    assert_eq!(a.rel(&Method::POST), None, "unmapped method");
+}
+
+#[tokio::test]
+async fn parse_reads_typed_path_param() {
+    let router = Router::new().route(
+        "/articles/{id}",
+        get(|Parsed(a): Parsed<Article>| async move { a.object().0 }),
+    );
+    assert_eq!(
+        call(router, "/articles/42").await,
+        (StatusCode::OK, "42".into())
+    );
+}
+
+#[tokio::test]
+async fn parse_renamed_and_default_fields() {
+    let router = Router::new().route(
+        "/folders/{folder_id}/docs/{doc}",
+        get(|Parsed(d): Parsed<FolderDoc>| async move {
+            format!("{}/{}/{:?}", d.folder, d.object().0, d.note)
+        }),
+    );
+    assert_eq!(
+        call(router, "/folders/f1/docs/d9").await,
+        (StatusCode::OK, "f1/d9/None".into())
+    );
+}
+
+#[tokio::test]
+async fn invalid_param_is_bad_request() {
+    let router = Router::new().route(
+        "/articles/{id}",
+        get(|Parsed(a): Parsed<Article>| async move { a.object().0 }),
+    );
+    let (status, _) = call(router, "/articles/not-a-number").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn unbound_param_is_missing() {
+    let router = Router::new().route(
+        "/articles/{other}",
+        get(|Parsed(a): Parsed<Article>| async move { a.object().0 }),
+    );
+    let (status, _) = call(router, "/articles/1").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn rejection_type_is_path_param_rejection() {
+    fn rejection_of<R: WebResource>() -> std::any::TypeId
+    where
+        R::Rejection: 'static,
+    {
+        std::any::TypeId::of::<R::Rejection>()
+    }
+    assert_eq!(
+        rejection_of::<Article>(),
+        std::any::TypeId::of::<PathParamRejection>()
+    );
+}
@@ -7,15 +7,19 @@ use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
-use futures::Stream;
+use futures::{Stream, StreamExt};
 use http::Uri;
-use nio_client::auth::CheckResult;
+use nio_client::audit::{AuditContext, AuditSink, DecisionOutcome, DecisionRecord};
+use nio_client::auth::{CallError, CheckResult};
+use nio_client::content::{ContentError, ContentVersion};
+use nio_client::credentials::{CallbackToken, CredentialError, CredentialLayer, StaticToken};
 use nio_client::memo::RequestMemo;
-use nio_client::session::{GrpcSessionResolver, ResolverConfig};
+use nio_client::observe::{RpcEvent, RpcMethod, RpcObserver};
+use nio_client::session::{GrpcSessionResolver, ResolveError, ResolverConfig};
 use nio_client::wire;
 use nio_client::{
-    connect_channel, CheckClient, Namespace, Obj, ReadFilter, Rel, Timestamp, Tuple, User, UserId,
-    UserSet,
+    connect_channel, CheckClient, Namespace, Obj, ReadError, ReadFilter, Rel, Timestamp, Tuple,
+    User, UserId, UserSet, WriteError,
 };
 use tokio::net::TcpListener;
 use tokio_stream::wrappers::TcpListenerStream;
@@ -26,6 +30,14 @@ struct MockState {
     check_requests: Vec<wire::CheckRequest>,
     check_response: Option<wire::CheckResponse>,
     check_fail_next: bool,
+    /// Checks on these relations are denied whatever `check_response` says.
+    check_deny_rels: Vec<String>,
+    /// The `traceparent` metadata of each check.
+    check_traceparents: Vec<Option<String>>,
+    /// The metadata of each check.
+    check_metadata: Vec<tonic::metadata::MetadataMap>,
+    /// The DER client certificate each check was made with, over TLS.
+    check_peer_certs: Vec<Option<Vec<u8>>>,
     list_requests: Vec<wire::ListRequest>,
     list_response: Option<wire::ListResponse>,
     list_fail_next: bool,
@@ -37,12 +49,16 @@ struct MockState {
     read_response: Option<wire::ReadResponse>,
     write_requests: Vec<wire::WriteRequest>,
     write_response: Option<wire::WriteResponse>,
+    write_fail_next: Option<Status>,
     watch_requests: Vec<wire::WatchRequest>,
     watch_responses: Vec<wire::WatchResponse>,
+    /// When set, watch streams pause after their first event until notified.
+    watch_hold: Option<Arc<tokio::sync::Notify>>,
     namespaces: Vec<wire::NamespaceMeta>,
     resolve_requests: Vec<wire::ResolveRequest>,
+    resolve_metadata: Vec<tonic::metadata::MetadataMap>,
     resolve_response: Option<wire::ResolveResponse>,
-    resolve_fail_next: bool,
+    resolve_fail_next: Option<tonic::Code>,
 }
 
 #[derive(Clone, Default)]
@@ -63,17 +79,30 @@ impl wire::check_service_server::CheckService for Mock {
         request: Request<wire::CheckRequest>,
     ) -> Result<Response<wire::CheckResponse>, Status> {
         let mut state = self.lock();
-        state.check_requests.push(request.into_inner());
+        let traceparent = request
+            .metadata()
+            .get("traceparent")
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        state.check_traceparents.push(traceparent);
+        state.check_metadata.push(request.metadata().clone());
+        let peer_cert = request
+            .peer_certs()
+            .and_then(|certs| certs.first().map(|cert| cert.to_vec()));
+        state.check_peer_certs.push(peer_cert);
+        let request = request.into_inner();
+        let denied = state.check_deny_rels.contains(&request.rel);
+        state.check_requests.push(request);
         if state.check_fail_next {
             state.check_fail_next = false;
             return Err(Status::internal("boom"));
         }
-        Ok(Response::new(state.check_response.clone().unwrap_or(
-            wire::CheckResponse {
-                principal: None,
-                ok: false,
-            },
-        )))
+        let mut response = state.check_response.clone().unwrap_or(wire::CheckResponse {
+            principal: None,
+            ok: false,
+        });
+        response.ok &= !denied;
+        Ok(Response::new(response))
     }
 
     async fn content_change_check(
@@ -130,6 +159,9 @@ impl wire::check_service_server::CheckService for Mock {
     ) -> Result<Response<wire::WriteResponse>, Status> {
         let mut state = self.lock();
         state.write_requests.push(request.into_inner());
+        if let Some(status) = state.write_fail_next.take() {
+            return Err(status);
+        }
         Ok(Response::new(
             state.write_response.clone().unwrap_or_default(),
         ))
@@ -144,9 +176,19 @@ impl wire::check_service_server::CheckService for Mock {
     ) -> Result<Response<Self::WatchStream>, Status> {
         let mut state = self.lock();
         state.watch_requests.push(request.into_inner());
-        let events: Vec<Result<wire::WatchResponse, Status>> =
+        let mut events: Vec<Result<wire::WatchResponse, Status>> =
             state.watch_responses.clone().into_iter().map(Ok).collect();
-        Ok(Response::new(Box::pin(tokio_stream::iter(events))))
+        let Some(hold) = state.watch_hold.clone() else {
+            return Ok(Response::new(Box::pin(tokio_stream::iter(events))));
+        };
+        let rest = events.split_off(events.len().min(1));
+        let held = futures::stream::once(async move { hold.notified().await })
+            .filter_map(|()| async { None });
+        Ok(Response::new(Box::pin(
+            tokio_stream::iter(events)
+                .chain(held)
+                .chain(tokio_stream::iter(rest)),
+        )))
     }
 }
 
@@ -170,10 +212,10 @@ impl wire::session_service_server::SessionService for Mock {
         request: Request<wire::ResolveRequest>,
     ) -> Result<Response<wire::ResolveResponse>, Status> {
         let mut state = self.lock();
+        state.resolve_metadata.push(request.metadata().clone());
         state.resolve_requests.push(request.into_inner());
-        if state.resolve_fail_next {
-            state.resolve_fail_next = false;
-            return Err(Status::internal("session backend down"));
+        if let Some(code) = state.resolve_fail_next.take() {
+            return Err(Status::new(code, "session backend down"));
         }
         Ok(Response::new(state.resolve_response.clone().unwrap_or(
             wire::ResolveResponse {
@@ -185,26 +227,87 @@ impl wire::session_service_server::SessionService for Mock {
 
 async fn start_mock() -> (Mock, Uri) {
     let mock = Mock::default();
+    let addr = serve(mock.clone(), None).await;
+    let uri: Uri = format!("http://{addr}").parse().expect("uri");
+    (mock, uri)
+}
+
+/// Like [`start_mock`] over mTLS with the fixtures in `tests/tls`; clients
+/// must present a certificate signed by `ca.pem`.
+async fn start_tls_mock() -> (Mock, Uri) {
+    let mock = Mock::default();
+    let tls = tonic::transport::ServerTlsConfig::new()
+        .identity(tonic::transport::Identity::from_pem(
+            tls_fixture("server.pem"),
+            tls_fixture("server.key"),
+        ))
+        .client_ca_root(tonic::transport::Certificate::from_pem(tls_fixture(
+            "ca.pem",
+        )));
+    let addr = serve(mock.clone(), Some(tls)).await;
+    let uri: Uri = format!("https://localhost:{}", addr.port())
+        .parse()
+        .expect("uri");
+    (mock, uri)
+}
+
+fn tls_fixture(name: &str) -> Vec<u8> {
+    std::fs::read(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/tls")
+            .join(name),
+    )
+    .expect("tls fixture")
+}
+
+async fn serve(mock: Mock, tls: Option<tonic::transport::ServerTlsConfig>) -> std::net::SocketAddr {
     let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
     let addr = listener.local_addr().expect("local addr");
-    let server = mock.clone();
+    let mut builder = tonic::transport::Server::builder();
+    if let Some(tls) = tls {
+        builder = builder.tls_config(tls).expect("server tls");
+    }
     tokio::spawn(async move {
-        tonic::transport::Server::builder()
-            .add_service(wire::check_service_server::CheckServiceServer::new(
-                server.clone(),
-            ))
-            .add_service(wire::namespace_service_server::NamespaceServiceServer::new(
-                server.clone(),
-            ))
-            .add_service(wire::session_service_server::SessionServiceServer::new(
-                server,
-            ))
+        services(mock, builder)
             .serve_with_incoming(TcpListenerStream::new(listener))
             .await
             .expect("mock server");
     });
-    let uri: Uri = format!("http://{addr}").parse().expect("uri");
-    (mock, uri)
+    addr
+}
+
+/// Like [`start_mock`] on a Unix socket; returns its `unix://` target.
+async fn start_uds_mock(name: &str) -> (Mock, String) {
+    let mock = Mock::default();
+    let path = std::env::temp_dir().join(format!("nio-{name}-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path).expect("bind");
+    let server = services(mock.clone(), tonic::transport::Server::builder());
+    tokio::spawn(async move {
+        server
+            .serve_with_incoming(tokio_stream::wrappers::UnixListenerStream::new(listener))
+            .await
+            .expect("mock server");
+    });
+    (mock, format!("unix://{}", path.display()))
+}
+
+fn services(mock: Mock, mut builder: tonic::transport::Server) -> tonic::transport::server::Router {
+    use tonic::codec::CompressionEncoding::Gzip;
+    builder
+        .add_service(
+            wire::check_service_server::CheckServiceServer::new(mock.clone())
+                .accept_compressed(Gzip)
+                .send_compressed(Gzip),
+        )
+        .add_service(wire::namespace_service_server::NamespaceServiceServer::new(
+            mock.clone(),
+        ))
+        .add_service(
+            wire::session_service_server::SessionServiceServer::new(mock)
+                .accept_compressed(Gzip)
+                .send_compressed(Gzip),
+        )
 }
 
 async fn client(uri: Uri) -> CheckClient {
@@ -268,6 +371,34 @@ async fn check_passes_explicit_timestamp() {
     assert_eq!(mock.lock().check_requests[0].ts, "zookie-1");
 }
 
+#[tokio::test]
+async fn guard_clauses_share_the_request_zookie_bound() {
+    use nio_client::guard::Guard;
+    use nio_client::zookie::ZookieJar;
+    let (mock, uri) = start_mock().await;
+    mock.lock().check_response = Some(wire::CheckResponse {
+        principal: Some(wire::Principal { id: "p-1".into() }),
+        ok: true,
+    });
+    let c = client(uri).await;
+    let guard = Guard::check(Namespace("doc".into()), Obj("1".into()), Rel::viewer()).and(
+        Guard::check(Namespace("folder".into()), Obj("f".into()), Rel::viewer()),
+    );
+    let jar = ZookieJar::new(Some(Timestamp("AQAAAAAAAQ==".into())));
+    let outcome = jar
+        .scope(c.check_guard(&guard, UserId("u1".into()), None))
+        .await
+        .expect("guard");
+    assert!(outcome.is_granted());
+    let ts: Vec<_> = mock
+        .lock()
+        .check_requests
+        .iter()
+        .map(|r| r.ts.clone())
+        .collect();
+    assert_eq!(ts, ["AQAAAAAAAQ==", "AQAAAAAAAQ=="]);
+}
+
 #[tokio::test]
 async fn check_forbidden_and_unknown_user() {
     let (mock, uri) = start_mock().await;
@@ -492,6 +623,93 @@ async fn content_change_check_maps_ok_and_ts() {
     );
 }
 
+#[tokio::test]
+async fn update_content_persists_with_the_change_check_zookie() {
+    let (mock, uri) = start_mock().await;
+    mock.lock().ccc_response = Some(wire::ContentChangeCheckResponse {
+        ok: true,
+        ts: "AQAAAAAAAQ==".into(),
+    });
+    let mut c = client(uri).await;
+    let version = c
+        .update_content(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::editor(),
+            UserId("u1".into()),
+            |ts| async move { Ok::<_, std::io::Error>(format!("body@{}", ts.0)) },
+        )
+        .await
+        .expect("update");
+    assert_eq!(version.content, "body@AQAAAAAAAQ==");
+    assert_eq!(version.zookie.0, "AQAAAAAAAQ==");
+}
+
+#[tokio::test]
+async fn update_content_denied_does_not_persist() {
+    let (mock, uri) = start_mock().await;
+    mock.lock().ccc_response = Some(wire::ContentChangeCheckResponse {
+        ok: false,
+        ts: "AQAAAAAAAQ==".into(),
+    });
+    let mut c = client(uri).await;
+    let persisted = AtomicBool::new(false);
+    let err = c
+        .update_content(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::editor(),
+            UserId("u1".into()),
+            |_| async {
+                persisted.store(true, Ordering::SeqCst);
+                Ok::<_, std::io::Error>(())
+            },
+        )
+        .await
+        .expect_err("denied");
+    assert!(matches!(err, ContentError::Forbidden));
+    assert!(!persisted.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn read_content_checks_at_the_stored_zookie() {
+    let (mock, uri) = start_mock().await;
+    mock.lock().check_response = Some(wire::CheckResponse {
+        principal: Some(wire::Principal { id: "u1".into() }),
+        ok: true,
+    });
+    let mut c = client(uri).await;
+    let version = ContentVersion::new("body", Timestamp("AQAAAAAAAQ==".into()));
+    let body = c
+        .read_content(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+            version.clone(),
+        )
+        .await
+        .expect("granted");
+    assert_eq!(body, "body");
+    assert_eq!(mock.lock().check_requests[0].ts, "AQAAAAAAAQ==");
+
+    mock.lock().check_response = Some(wire::CheckResponse {
+        principal: Some(wire::Principal { id: "u1".into() }),
+        ok: false,
+    });
+    let err = c
+        .read_content(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+            version,
+        )
+        .await
+        .expect_err("denied");
+    assert!(matches!(err, ContentError::Forbidden));
+}
+
 #[tokio::test]
 async fn read_sends_filters_and_maps_tuples() {
     let (mock, uri) = start_mock().await;
@@ -670,6 +888,29 @@ async fn write_sends_add_del_and_precondition() {
     ));
 }
 
+#[tokio::test]
+async fn stale_write_precondition_is_a_precondition_failure() {
+    let (mock, uri) = start_mock().await;
+    mock.lock().write_fail_next = Some(Status::failed_precondition("stale zookie"));
+    let mut c = client(uri).await;
+    let tuple = Tuple::new(
+        Namespace("doc".into()),
+        Obj("1".into()),
+        Rel::viewer(),
+        User::UserId("u1".into()),
+    );
+    let err = c
+        .write(vec![tuple], vec![], Some(Timestamp("old-ts".into())))
+        .await
+        .expect_err("stale precondition");
+    assert_eq!(err.kind(), nio_client::ErrorKind::PreconditionFailed);
+    assert!(!err.is_retryable());
+    assert!(
+        err.to_string().contains("FailedPrecondition: stale zookie"),
+        "{err}"
+    );
+}
+
 #[tokio::test]
 async fn add_one_and_delete_one_return_commit_zookie() {
     let (mock, uri) = start_mock().await;
@@ -853,6 +1094,55 @@ async fn grpc_session_resolver_not_found_is_none() {
     );
 }
 
+#[tokio::test]
+async fn grpc_session_resolver_errors_keep_the_status_code() {
+    let (mock, uri) = start_mock().await;
+    let channel = connect_channel(uri, None).await.expect("connect");
+    let resolver = GrpcSessionResolver::new(channel, ResolverConfig::default());
+    for code in [
+        tonic::Code::ResourceExhausted,
+        tonic::Code::PermissionDenied,
+        tonic::Code::Unauthenticated,
+        tonic::Code::DeadlineExceeded,
+    ] {
+        mock.lock().resolve_fail_next = Some(code);
+        let err = resolver.resolve("deadbeef").await.expect_err("must fail");
+        assert_eq!(err.kind(), nio_client::ErrorKind::of(code), "{err}");
+    }
+    mock.lock().resolve_fail_next = Some(tonic::Code::ResourceExhausted);
+    let err = resolver.resolve("deadbeef").await.expect_err("must fail");
+    assert!(err.is_retryable(), "rate limited resolves may be retried");
+}
+
+#[tokio::test]
+async fn check_and_session_channels_connect_over_unix_sockets() {
+    let (mock, target) = start_uds_mock("uds").await;
+    mock.lock().check_response = Some(wire::CheckResponse {
+        principal: Some(wire::Principal { id: "p-1".into() }),
+        ok: true,
+    });
+    mock.lock().resolve_response = Some(session_outcome("p-1", 3600));
+    let target: nio_client::Target = target.parse().expect("unix target");
+
+    let mut c = CheckClient::create(target.clone()).await.expect("connect");
+    let res = c
+        .check(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+            None,
+        )
+        .await
+        .expect("check");
+    assert!(matches!(res, CheckResult::Ok(_)));
+
+    let channel = connect_channel(target, None).await.expect("connect");
+    let resolver = GrpcSessionResolver::new(channel, ResolverConfig::default());
+    let session = resolver.resolve("deadbeef").await.expect("resolve");
+    assert_eq!(session.expect("session").principal, "p-1");
+}
+
 #[tokio::test]
 async fn memo_dedupes_identical_checks() {
     let (mock, uri) = start_mock().await;
@@ -946,6 +1236,280 @@ async fn memo_dedupes_identical_lists_and_fixes_timestamp() {
     assert_eq!(reqs[0].ts, "pinned-ts");
 }
 
+#[cfg(feature = "tracing")]
+mod tracing_spans {
+    use super::*;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+
+    /// Layer keeping the names of the spans opened.
+    struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for SpanNames {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _id: &tracing::span::Id,
+            _ctx: Context<'_, S>,
+        ) {
+            self.0.lock().unwrap().push(attrs.metadata().name());
+        }
+    }
+
+    #[tokio::test]
+    async fn memo_and_rpc_run_in_spans() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().check_response = Some(wire::CheckResponse {
+            principal: Some(wire::Principal { id: "p-1".into() }),
+            ok: true,
+        });
+        let names = Arc::new(Mutex::new(vec![]));
+        let subscriber = tracing_subscriber::registry().with(SpanNames(names.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let memo = RequestMemo::new(client(uri).await);
+        memo.check(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+        )
+        .await
+        .expect("check");
+        let names = names.lock().unwrap();
+        assert!(names.contains(&"nio.memo.check"), "{names:?}");
+        assert!(names.contains(&"nio.check"), "{names:?}");
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[tokio::test]
+    async fn check_propagates_w3c_trace_context() {
+        use opentelemetry::trace::TracerProvider as _;
+
+        let (mock, uri) = start_mock().await;
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder().build();
+        let otel = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(otel));
+        let mut client = client(uri).await;
+        let _ = client
+            .check(
+                Namespace("doc".into()),
+                Obj("1".into()),
+                Rel::viewer(),
+                UserId("u1".into()),
+                None,
+            )
+            .await;
+        let traceparent = mock.lock().check_traceparents[0]
+            .clone()
+            .expect("traceparent sent");
+        let parts: Vec<&str> = traceparent.split('-').collect();
+        assert_eq!(parts.len(), 4, "{traceparent}");
+        assert_eq!(parts[0], "00");
+        assert_eq!(parts[1].len(), 32);
+        assert_eq!(parts[2].len(), 16);
+        assert_eq!(parts[3], "01", "sampled");
+    }
+}
+
+#[cfg(feature = "metrics")]
+mod metrics {
+    use super::*;
+    use base64::Engine;
+    use nio_client::metrics::Metrics;
+
+    fn has_line(text: &str, line: &str) -> bool {
+        text.lines().any(|l| l == line)
+    }
+
+    #[tokio::test]
+    async fn rpcs_and_memo_lookups_are_counted() {
+        let (mock, uri) = start_mock().await;
+        let metrics = Metrics::new();
+        let mut c = client(uri).await.with_metrics(metrics.clone());
+        let memo = RequestMemo::new(c.clone());
+        for _ in 0..2 {
+            memo.check(
+                Namespace("doc".into()),
+                Obj("1".into()),
+                Rel::viewer(),
+                UserId("u1".into()),
+            )
+            .await
+            .expect("check");
+        }
+        mock.lock().list_fail_next = true;
+        let _ = c
+            .list(
+                Namespace("doc".into()),
+                Rel::viewer(),
+                UserId("u1".into()),
+                None,
+            )
+            .await;
+
+        let text = metrics.render();
+        assert!(
+            has_line(
+                &text,
+                "nio_client_rpc_duration_seconds_count{method=\"check\",outcome=\"ok\"} 1"
+            ),
+            "{text}"
+        );
+        assert!(has_line(
+            &text,
+            "nio_client_rpc_duration_seconds_count{method=\"list\",outcome=\"error\"} 1"
+        ));
+        assert!(has_line(
+            &text,
+            "nio_client_rpc_errors_total{method=\"list\",code=\"Internal\"} 1"
+        ));
+        assert!(has_line(
+            &text,
+            "nio_client_memo_lookups_total{op=\"check\",result=\"hit\"} 1"
+        ));
+        assert!(has_line(
+            &text,
+            "nio_client_memo_lookups_total{op=\"check\",result=\"miss\"} 1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn session_cache_results_are_counted() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().resolve_response = Some(session_outcome("p-1", 3600));
+        let metrics = Metrics::new();
+        let channel = connect_channel(uri, None).await.expect("connect");
+        let resolver =
+            GrpcSessionResolver::cached(nio_client::transport(channel), ResolverConfig::default())
+                .with_metrics(metrics.clone())
+                .into_dyn();
+        let known = nio_client::session::token_hash("known");
+        let _ = resolver.resolve(&known).await.expect("resolve");
+        let _ = resolver.resolve(&known).await.expect("resolve");
+        mock.lock().resolve_response = None;
+        let unknown = nio_client::session::token_hash("unknown");
+        let _ = resolver.resolve(&unknown).await.expect("resolve");
+        let _ = resolver.resolve(&unknown).await.expect("resolve");
+
+        let text = metrics.render();
+        for result in ["hit", "tombstone"] {
+            let line = format!("nio_client_session_cache_total{{result=\"{result}\"}} 1");
+            assert!(has_line(&text, &line), "{text}");
+        }
+        assert!(has_line(
+            &text,
+            "nio_client_session_cache_total{result=\"miss\"} 2"
+        ));
+    }
+
+    #[tokio::test]
+    async fn watch_reports_lag_and_reconnects() {
+        let (mock, uri) = start_mock().await;
+        // A zookie committed one minute ago.
+        let millis = (chrono::Utc::now().timestamp_millis() - 60_000) as u64;
+        let mut packed = vec![0u8];
+        packed.extend_from_slice(&millis.to_be_bytes()[2..]);
+        mock.lock().watch_responses = vec![wire::WatchResponse {
+            ts: base64::engine::general_purpose::STANDARD.encode(packed),
+            updates: vec![],
+        }];
+        let metrics = Metrics::new();
+        let mut c = client(uri).await.with_metrics(metrics.clone());
+        let ns = Namespace("doc".into());
+        let mut stream = c
+            .watch(ns.clone(), Timestamp::empty())
+            .await
+            .expect("watch");
+        stream.recv().await.expect("recv").expect("heartbeat");
+        assert!(stream.recv().await.expect("recv").is_none());
+        let text = metrics.render();
+        assert!(
+            !text.contains("nio_client_watch_reconnects_total{"),
+            "{text}"
+        );
+        let lag: f64 = text
+            .lines()
+            .find_map(|l| l.strip_prefix("nio_client_watch_lag_seconds{ns=\"doc\"} "))
+            .expect("lag sample")
+            .parse()
+            .expect("lag value");
+        assert!((60.0..120.0).contains(&lag), "{lag}");
+
+        let _ = c.watch(ns, Timestamp::empty()).await.expect("watch");
+        assert!(has_line(
+            &metrics.render(),
+            "nio_client_watch_reconnects_total{ns=\"doc\"} 1"
+        ));
+    }
+}
+
+#[tokio::test]
+async fn check_without_trace_context_sends_no_traceparent() {
+    let (mock, uri) = start_mock().await;
+    let mut client = client(uri).await;
+    let _ = client
+        .check(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+            None,
+        )
+        .await;
+    assert_eq!(mock.lock().check_traceparents, [None]);
+}
+
+/// Audit sink keeping every record.
+#[derive(Default)]
+struct Records(Mutex<Vec<DecisionRecord>>);
+
+impl AuditSink for Records {
+    fn record(&self, record: &DecisionRecord) {
+        self.0.lock().unwrap().push(record.clone());
+    }
+}
+
+#[tokio::test]
+async fn audit_records_rpcs_and_memo_hits_with_context() {
+    let (mock, uri) = start_mock().await;
+    mock.lock().check_response = Some(wire::CheckResponse {
+        principal: Some(wire::Principal { id: "p-1".into() }),
+        ok: true,
+    });
+    let records = Arc::new(Records::default());
+    let client = client(uri).await.with_audit(records.clone());
+    let memo = RequestMemo::new(client);
+    let context = AuditContext {
+        request_id: Some("r-1".into()),
+        ..AuditContext::default()
+    };
+    context
+        .scope(async {
+            for _ in 0..2 {
+                memo.check(
+                    Namespace("doc".into()),
+                    Obj("1".into()),
+                    Rel::viewer(),
+                    UserId("u1".into()),
+                )
+                .await
+                .expect("check");
+            }
+        })
+        .await;
+
+    let records = records.0.lock().unwrap();
+    let hits: Vec<_> = records.iter().map(|r| r.memo_hit).collect();
+    assert_eq!(hits, [false, true]);
+    assert!(records
+        .iter()
+        .all(|r| r.outcome == DecisionOutcome::Granted));
+    assert!(records
+        .iter()
+        .all(|r| r.context.request_id.as_deref() == Some("r-1")));
+    assert_eq!(records[0].user, UserId("u1".into()));
+}
+
 #[tokio::test]
 async fn memo_concurrent_identical_misses_single_flight() {
     let (mock, uri) = start_mock().await;
@@ -980,14 +1544,41 @@ async fn memo_concurrent_identical_misses_single_flight() {
 }
 
 #[tokio::test]
-async fn observe_list_reports_outcome_and_errors() {
+async fn memo_audits_callers_that_waited_on_the_single_flight() {
     let (mock, uri) = start_mock().await;
-    mock.lock().list_response = Some(wire::ListResponse {
-        objs: vec![],
-        ts: "t".into(),
+    mock.lock().check_response = Some(wire::CheckResponse {
+        principal: Some(wire::Principal { id: "p-1".into() }),
+        ok: true,
     });
-    let observed = Arc::new(AtomicUsize::new(0));
-    let errored = Arc::new(AtomicBool::new(false));
+    let records = Arc::new(Records::default());
+    let memo = RequestMemo::new(client(uri).await.with_audit(records.clone()));
+    // Polled together, so every call but one misses and waits on the RPC.
+    let checks = (0..4).map(|_| {
+        memo.check(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+        )
+    });
+    for result in futures::future::join_all(checks).await {
+        assert!(result.expect("check").is_ok());
+    }
+    assert_eq!(mock.lock().check_requests.len(), 1);
+    let records = records.0.lock().unwrap();
+    let hits: Vec<_> = records.iter().map(|r| r.memo_hit).collect();
+    assert_eq!(hits, [false, true, true, true], "one record per decision");
+}
+
+#[tokio::test]
+async fn observe_list_reports_outcome_and_errors() {
+    let (mock, uri) = start_mock().await;
+    mock.lock().list_response = Some(wire::ListResponse {
+        objs: vec![],
+        ts: "t".into(),
+    });
+    let observed = Arc::new(AtomicUsize::new(0));
+    let errored = Arc::new(AtomicBool::new(false));
     let (obs, err_flag) = (observed.clone(), errored.clone());
     let mut c = client(uri).await.with_observe_list(Arc::new(
         move |_ns, _rel, _user, _duration, is_error| {
@@ -1020,6 +1611,294 @@ async fn observe_list_reports_outcome_and_errors() {
     assert!(errored.load(Ordering::Relaxed));
 }
 
+/// Interceptor adding an `x-caller` header.
+#[allow(clippy::result_large_err)] // tonic's Interceptor signature
+fn add_caller(mut request: Request<()>) -> Result<Request<()>, Status> {
+    request
+        .metadata_mut()
+        .insert("x-caller", "billing".parse().unwrap());
+    Ok(request)
+}
+
+fn caller(metadata: &tonic::metadata::MetadataMap) -> Option<&str> {
+    metadata.get("x-caller").and_then(|v| v.to_str().ok())
+}
+
+#[tokio::test]
+async fn interceptor_adds_metadata_to_every_call() {
+    let (mock, uri) = start_mock().await;
+    let mut c = client(uri).await.with_interceptor(add_caller);
+    let _ = c
+        .check(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+            None,
+        )
+        .await
+        .expect("check");
+    assert_eq!(caller(&mock.lock().check_metadata[0]), Some("billing"));
+}
+
+#[tokio::test]
+async fn failing_interceptor_fails_the_call_without_an_rpc() {
+    let (mock, uri) = start_mock().await;
+    #[allow(clippy::result_large_err)] // tonic's Interceptor signature
+    fn rate_limited(_: Request<()>) -> Result<Request<()>, Status> {
+        Err(Status::resource_exhausted("rate limited"))
+    }
+    let mut c = client(uri).await.with_interceptor(rate_limited);
+    let err = c
+        .list(
+            Namespace("doc".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+            None,
+        )
+        .await
+        .expect_err("must fail");
+    assert!(err.to_string().contains("rate limited"), "{err}");
+    assert!(mock.lock().list_requests.is_empty());
+}
+
+#[tokio::test]
+async fn client_runs_over_a_layered_transport() {
+    let (mock, uri) = start_mock().await;
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = calls.clone();
+    let channel = connect_channel(uri, None).await.expect("connect");
+    let stack = tower::ServiceBuilder::new()
+        .map_request(move |request: http::Request<tonic::body::Body>| {
+            counted.fetch_add(1, Ordering::Relaxed);
+            request
+        })
+        .service(channel);
+    let mut c = CheckClient::from_transport(nio_client::transport(stack));
+    let _ = c.list_namespaces().await.expect("list namespaces");
+    let _ = c
+        .list(
+            Namespace("doc".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+            None,
+        )
+        .await
+        .expect("list");
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+    assert_eq!(mock.lock().list_requests.len(), 1);
+}
+
+#[tokio::test]
+async fn session_resolver_runs_over_an_intercepted_transport() {
+    let (mock, uri) = start_mock().await;
+    let channel = connect_channel(uri, None).await.expect("connect");
+    let transport = nio_client::transport(tonic::service::interceptor::InterceptedService::new(
+        channel, add_caller,
+    ));
+    let resolver = GrpcSessionResolver::from_transport(transport, ResolverConfig::default());
+    let _ = resolver.resolve("deadbeef").await.expect("resolve");
+    assert_eq!(caller(&mock.lock().resolve_metadata[0]), Some("billing"));
+}
+
+#[tokio::test]
+async fn credentials_are_sent_as_bearer_metadata() {
+    let (mock, uri) = start_mock().await;
+    let mut c = client(uri)
+        .await
+        .with_credentials(Arc::new(StaticToken::new("s3cret")));
+    let _ = c
+        .check(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+            None,
+        )
+        .await
+        .expect("check");
+    let metadata = mock.lock().check_metadata[0].clone();
+    assert_eq!(
+        metadata.get("authorization").and_then(|v| v.to_str().ok()),
+        Some("Bearer s3cret")
+    );
+}
+
+fn failing_credentials() -> Arc<CallbackToken> {
+    Arc::new(CallbackToken::new(Arc::new(|| {
+        Box::pin(async { Err(CredentialError::Provider("idp down".into())) })
+    })))
+}
+
+#[tokio::test]
+async fn credential_failures_are_distinct_errors_without_an_rpc() {
+    let (mock, uri) = start_mock().await;
+    let mut c = client(uri).await.with_credentials(failing_credentials());
+    let err = c
+        .check(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+            None,
+        )
+        .await
+        .expect_err("must fail");
+    assert!(
+        matches!(&err, CallError::Credentials(CredentialError::Provider(m)) if m == "idp down"),
+        "{err:?}"
+    );
+    let err = c
+        .read(vec![ReadFilter::by_object(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            None,
+        )])
+        .await
+        .expect_err("must fail");
+    assert!(matches!(err, ReadError::Credentials(_)), "{err:?}");
+    let err = c.add_many(vec![]).await.expect_err("must fail");
+    assert!(matches!(err, WriteError::Credentials(_)), "{err:?}");
+    let state = mock.lock();
+    assert!(state.check_requests.is_empty());
+    assert!(state.read_requests.is_empty());
+    assert!(state.write_requests.is_empty());
+}
+
+#[tokio::test]
+async fn session_resolver_sends_credentials() {
+    let (mock, uri) = start_mock().await;
+    let channel = connect_channel(uri, None).await.expect("connect");
+    let layer = CredentialLayer::new(Arc::new(StaticToken::new("s3cret")));
+    let transport = nio_client::transport(tower::Layer::layer(&layer, channel.clone()));
+    let resolver = GrpcSessionResolver::from_transport(transport, ResolverConfig::default());
+    let _ = resolver.resolve("deadbeef").await.expect("resolve");
+    let metadata = mock.lock().resolve_metadata[0].clone();
+    assert_eq!(
+        metadata.get("authorization").and_then(|v| v.to_str().ok()),
+        Some("Bearer s3cret")
+    );
+
+    let layer = CredentialLayer::new(failing_credentials());
+    let transport = nio_client::transport(tower::Layer::layer(&layer, channel));
+    let resolver = GrpcSessionResolver::from_transport(transport, ResolverConfig::default());
+    let err = resolver.resolve("deadbeef").await.expect_err("must fail");
+    assert!(matches!(err, ResolveError::Credentials(_)), "{err:?}");
+}
+
+/// RPC observer keeping every event.
+#[derive(Default)]
+struct Events(Mutex<Vec<RpcEvent>>);
+
+impl RpcObserver for Events {
+    fn observe(&self, event: &RpcEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+#[tokio::test]
+async fn rpc_observer_sees_every_rpc() {
+    let (mock, uri) = start_mock().await;
+    mock.lock().list_response = Some(wire::ListResponse {
+        objs: vec!["a".into()],
+        ts: "list-ts".into(),
+    });
+    mock.lock().write_response = Some(wire::WriteResponse {
+        ts: "commit-ts".into(),
+    });
+    let events = Arc::new(Events::default());
+    let mut c = client(uri).await.with_rpc_observer(events.clone());
+    let (ns, obj, user) = (
+        Namespace("doc".into()),
+        Obj("1".into()),
+        UserId("u1".into()),
+    );
+    let ts = Timestamp("check-ts".into());
+    let _ = c
+        .check(
+            ns.clone(),
+            obj.clone(),
+            Rel::viewer(),
+            user.clone(),
+            Some(ts),
+        )
+        .await;
+    let _ = c.list(ns.clone(), Rel::viewer(), user.clone(), None).await;
+    let _ = c.expand(ns.clone(), obj.clone(), Rel::viewer(), None).await;
+    let _ = c
+        .content_change_check(ns.clone(), obj.clone(), Rel::editor(), user.clone())
+        .await;
+    let _ = c.watch(ns.clone(), Timestamp::empty()).await;
+    let _ = c.list_namespaces().await;
+    let _ = c.get_all(&ns, &obj).await;
+    let tuple = Tuple::new(
+        ns.clone(),
+        obj.clone(),
+        Rel::viewer(),
+        User::UserId(user.0.clone()),
+    );
+    let _ = c.add_one(tuple).await;
+
+    let events = events.0.lock().unwrap().clone();
+    let methods: Vec<RpcMethod> = events.iter().map(|e| e.method).collect();
+    assert_eq!(
+        methods,
+        [
+            RpcMethod::Check,
+            RpcMethod::List,
+            RpcMethod::Expand,
+            RpcMethod::ContentChangeCheck,
+            RpcMethod::Watch,
+            RpcMethod::ListNamespaces,
+            RpcMethod::Read,
+            RpcMethod::Write,
+        ]
+    );
+    let requests: Vec<&str> = events.iter().map(|e| e.request.as_str()).collect();
+    assert_eq!(
+        requests,
+        [
+            "doc:1#viewer@u1",
+            "doc:*#viewer@u1",
+            "doc:1#viewer",
+            "doc:1#editor@u1",
+            "doc",
+            "",
+            "1 filters",
+            "+1 -0",
+        ]
+    );
+    assert!(events.iter().all(|e| e.code == tonic::Code::Ok));
+    assert_eq!(events[0].zookie, Some(Timestamp("check-ts".into())));
+    assert_eq!(events[1].zookie, Some(Timestamp("list-ts".into())));
+    assert!(events[1].response_size > 0);
+    assert_eq!(events[4].zookie, None, "Timestamp::empty() is no zookie");
+    assert_eq!(events[7].zookie, Some(Timestamp("commit-ts".into())));
+}
+
+#[tokio::test]
+async fn rpc_observer_reports_the_grpc_code_of_failures() {
+    let (mock, uri) = start_mock().await;
+    mock.lock().list_fail_next = true;
+    let events = Arc::new(Events::default());
+    let mut c = client(uri).await.with_rpc_observer(events.clone());
+    let _ = c
+        .list(
+            Namespace("doc".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+            None,
+        )
+        .await
+        .expect_err("must fail");
+    let events = events.0.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_error());
+    assert_eq!(events[0].code, tonic::Code::Internal);
+    assert_eq!(events[0].response_size, 0);
+    assert_eq!(events[0].zookie, None);
+}
+
 #[tokio::test]
 async fn read_by_user_and_user_set_send_reverse_filters() {
     let (mock, uri) = start_mock().await;
@@ -1090,58 +1969,224 @@ async fn add_many_commits_one_atomic_write() {
 
 // End-to-end coverage of the axum auth extractors against the in-process
 // mock — the Rust counterpart of nioclient-go's wrap_test.go.
-#[cfg(feature = "axum")]
-mod axum_extractors {
+mod live_access {
     use super::*;
-    use axum::extract::FromRequestParts;
-    use axum::http::request::Parts;
-    use axum::http::Method;
-    use nio_client::axum::{
-        AuthState, Authenticated, BearerTokenAuth, WebResource, WebResourceError, WithOptPrincipal,
-        WithPrincipal,
-    };
-    use nio_client::session::{token_hash, GrpcSessionResolver, ResolverConfig};
+    use chrono::{Duration as ChronoDuration, Utc};
+    use nio_client::guard::Guard;
+    use nio_client::live::{LiveAccess, LiveAccessConfig, Revocation};
+    use nio_client::session::{ResolveFuture, ResolvedSession, SessionResolver};
+    use std::time::Duration;
+
+    /// Resolves every token to the current session, if any.
+    struct Sessions(Mutex<Option<ResolvedSession>>);
+
+    impl SessionResolver for Sessions {
+        fn resolve<'a>(&'a self, _token_hash: &'a str) -> ResolveFuture<'a> {
+            let session = self.0.lock().unwrap().clone();
+            Box::pin(async move { Ok(session) })
+        }
+        fn evict(&self, _token_hash: &str) {}
+    }
 
-    struct DocResource;
+    fn session(expires_in: ChronoDuration) -> ResolvedSession {
+        ResolvedSession {
+            principal: "u1".into(),
+            tenant_id: "t1".into(),
+            expires_at: Utc::now() + expires_in,
+        }
+    }
 
-    impl WebResource for DocResource {
-        type Rejection = std::convert::Infallible;
+    fn guard() -> Guard {
+        Guard::check(Namespace("doc".into()), Obj("1".into()), Rel::viewer())
+    }
 
-        fn namespace(&self) -> Namespace {
-            Namespace("doc".into())
-        }
-        fn rel(&self, _method: &Method) -> Option<Rel> {
-            Some(Rel::viewer())
+    fn set_check(mock: &Mock, ok: bool) {
+        mock.lock().check_response = Some(wire::CheckResponse {
+            principal: Some(wire::Principal { id: "u1".into() }),
+            ok,
+        });
+    }
+
+    fn periodic(every: Duration) -> LiveAccessConfig {
+        LiveAccessConfig {
+            recheck_interval: every,
+            watch: false,
         }
-        async fn parse<S: Send + Sync>(
-            _parts: &mut Parts,
-            _state: &S,
-        ) -> Result<Self, Self::Rejection> {
-            Ok(DocResource)
+    }
+
+    async fn start(uri: Uri, sessions: &Arc<Sessions>, config: LiveAccessConfig) -> LiveAccess {
+        let s = sessions.0.lock().unwrap().clone().expect("session");
+        LiveAccess::start(
+            client(uri).await,
+            sessions.clone(),
+            "hash".into(),
+            s,
+            guard(),
+            config,
+        )
+    }
+
+    async fn revoked(access: &LiveAccess) -> Revocation {
+        tokio::time::timeout(Duration::from_secs(5), access.revoked())
+            .await
+            .expect("revoked in time")
+    }
+
+    #[tokio::test]
+    async fn expired_session_is_revoked() {
+        let (_mock, uri) = start_mock().await;
+        let sessions = Arc::new(Sessions(Mutex::new(Some(session(
+            ChronoDuration::seconds(-1),
+        )))));
+        let access = start(uri, &sessions, periodic(Duration::from_secs(3600))).await;
+        assert_eq!(revoked(&access).await, Revocation::SessionExpired);
+        assert_eq!(access.is_revoked(), Some(Revocation::SessionExpired));
+    }
+
+    #[tokio::test]
+    async fn periodic_recheck_notices_lost_permission() {
+        let (mock, uri) = start_mock().await;
+        set_check(&mock, true);
+        let sessions = Arc::new(Sessions(Mutex::new(Some(session(ChronoDuration::hours(
+            1,
+        ))))));
+        let access = start(uri, &sessions, periodic(Duration::from_millis(20))).await;
+        tokio::time::sleep(Duration::from_millis(70)).await;
+        assert_eq!(access.is_revoked(), None, "still granted");
+        set_check(&mock, false);
+        match revoked(&access).await {
+            Revocation::AccessLost(denial) => {
+                assert_eq!(denial.to_string(), "denied: doc:1#viewer")
+            }
+            other => panic!("expected access lost, got {other:?}"),
         }
+    }
+
+    #[tokio::test]
+    async fn periodic_recheck_notices_ended_session_without_check() {
+        let (mock, uri) = start_mock().await;
+        let sessions = Arc::new(Sessions(Mutex::new(Some(session(ChronoDuration::hours(
+            1,
+        ))))));
+        let access = start(uri, &sessions, periodic(Duration::from_millis(20))).await;
+        *sessions.0.lock().unwrap() = None;
+        assert_eq!(revoked(&access).await, Revocation::SessionEnded);
+        assert!(mock.lock().check_requests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn watch_event_triggers_recheck_at_its_zookie() {
+        let (mock, uri) = start_mock().await;
+        set_check(&mock, false);
+        mock.lock().read_response = Some(wire::ReadResponse {
+            ts: "AQAAAAAAAQ==".into(),
+            tuples: vec![],
+        });
+        mock.lock().watch_responses = vec![wire::WatchResponse {
+            ts: "AQAAAAAAAg==".into(),
+            updates: vec![wire::Update {
+                tuple: Some(wire::Tuple {
+                    ns: "doc".into(),
+                    obj: "1".into(),
+                    rel: "viewer".into(),
+                    user: Some(wire::tuple::User::UserId("u1".into())),
+                    condition: None,
+                }),
+                deleted: true,
+            }],
+        }];
+        let sessions = Arc::new(Sessions(Mutex::new(Some(session(ChronoDuration::hours(
+            1,
+        ))))));
+        let config = LiveAccessConfig {
+            recheck_interval: Duration::from_secs(3600),
+            watch: true,
+        };
+        let access = start(uri, &sessions, config).await;
+        assert!(matches!(revoked(&access).await, Revocation::AccessLost(_)));
+        let state = mock.lock();
+        assert_eq!(state.watch_requests[0].ns, "doc");
+        assert_eq!(state.watch_requests[0].start_ts, "AQAAAAAAAQ==");
+        assert_eq!(state.check_requests[0].ts, "AQAAAAAAAg==");
+    }
+}
+
+#[cfg(feature = "grpc-server")]
+mod grpc_server {
+    use super::*;
+    use http_body_util::Full;
+    use nio_client::auth::Principal;
+    use nio_client::grpc::{GrpcPolicy, GrpcResource, NioAuthLayer};
+    use tonic::body::Body;
+    use tower::{Layer, ServiceExt};
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct GetDoc {
+        #[prost(string, tag = "1")]
+        id: String,
+    }
+
+    impl GrpcResource for GetDoc {
         fn object(&self) -> Obj {
-            Obj("1".into())
+            Obj(self.id.clone())
         }
     }
 
-    async fn auth_state(uri: Uri, prefix: Option<&str>) -> AuthState {
-        let check_client = client(uri.clone()).await;
-        let channel = connect_channel(uri, None).await.expect("connect");
-        let resolver = GrpcSessionResolver::new(channel, ResolverConfig::default());
-        AuthState::new(check_client, resolver, prefix)
+    const GET: &str = "/docs.Docs/Get";
+
+    /// Answers with the principal the layer put in the extensions.
+    fn echo_principal() -> impl tower::Service<
+        http::Request<Body>,
+        Response = http::Response<Body>,
+        Error = std::convert::Infallible,
+        Future = impl Send,
+    > + Clone
+           + Send
+           + 'static {
+        tower::service_fn(|req: http::Request<Body>| async move {
+            let principal = req.extensions().get::<Principal>().cloned();
+            let mut resp = http::Response::new(Body::empty());
+            if let Some(p) = principal {
+                resp.headers_mut()
+                    .insert("x-principal", p.as_str().parse().unwrap());
+            }
+            Ok(resp)
+        })
     }
 
-    fn parts_with_headers(headers: &[(&str, &str)]) -> Parts {
-        let mut builder = axum::http::Request::builder().uri("/docs/1?x=1");
-        for (name, value) in headers {
-            builder = builder.header(*name, *value);
+    fn call_request(method: &str, bearer: Option<&str>, msg: &GetDoc) -> http::Request<Body> {
+        use prost::Message;
+        let encoded = msg.encode_to_vec();
+        let mut frame = vec![0u8];
+        frame.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&encoded);
+        let mut builder = http::Request::post(method).header("content-type", "application/grpc");
+        if let Some(token) = bearer {
+            builder = builder.header("authorization", format!("Bearer {token}"));
         }
-        let (parts, _) = builder.body(()).unwrap().into_parts();
-        parts
+        builder
+            .body(Body::new(Full::new(prost::bytes::Bytes::from(frame))))
+            .unwrap()
+    }
+
+    async fn layer(uri: Uri, policy: GrpcPolicy) -> NioAuthLayer {
+        let channel = connect_channel(uri.clone(), None).await.expect("connect");
+        let resolver = GrpcSessionResolver::new(channel, ResolverConfig::default());
+        NioAuthLayer::new(client(uri).await, resolver, policy)
+    }
+
+    fn grpc_status(resp: &http::Response<Body>) -> Option<tonic::Code> {
+        resp.headers()
+            .get("grpc-status")
+            .map(|v| tonic::Code::from_bytes(v.as_bytes()))
+    }
+
+    fn policy() -> GrpcPolicy {
+        GrpcPolicy::new().unary::<GetDoc>(GET, Namespace("doc".into()), Rel::viewer())
     }
 
     #[tokio::test]
-    async fn cookie_auth_checks_resolved_principal_not_raw_token() {
+    async fn checks_object_from_request_message_and_exposes_principal() {
         let (mock, uri) = start_mock().await;
         mock.lock().resolve_response = Some(session_outcome("p-uuid", 3600));
         mock.lock().check_response = Some(wire::CheckResponse {
@@ -1150,12 +2195,159 @@ mod axum_extractors {
             }),
             ok: true,
         });
-        let state = auth_state(uri, None).await;
-        let mut parts = parts_with_headers(&[("cookie", "session=raw-token")]);
-        let got = WithPrincipal::<DocResource>::from_request_parts(&mut parts, &state)
-            .await
-            .expect("authorized");
-        assert_eq!(got.principal.as_str(), "p-uuid");
+        let svc = layer(uri, policy()).await.layer(echo_principal());
+        let req = call_request(GET, Some("tok"), &GetDoc { id: "d7".into() });
+        let resp = svc.oneshot(req).await.unwrap();
+        assert_eq!(grpc_status(&resp), None, "forwarded to the service");
+        assert_eq!(resp.headers()["x-principal"], "p-uuid");
+        let check = mock.lock().check_requests[0].clone();
+        assert_eq!((check.ns.as_str(), check.obj.as_str()), ("doc", "d7"));
+        assert_eq!(check.user_id, "p-uuid");
+    }
+
+    #[tokio::test]
+    async fn missing_or_unknown_token_is_unauthenticated() {
+        let (mock, uri) = start_mock().await;
+        let svc = layer(uri, policy()).await.layer(echo_principal());
+        let doc = GetDoc { id: "d7".into() };
+        let resp = svc
+            .clone()
+            .oneshot(call_request(GET, None, &doc))
+            .await
+            .unwrap();
+        assert_eq!(grpc_status(&resp), Some(tonic::Code::Unauthenticated));
+        let resp = svc
+            .oneshot(call_request(GET, Some("tok"), &doc))
+            .await
+            .unwrap();
+        assert_eq!(grpc_status(&resp), Some(tonic::Code::Unauthenticated));
+        assert!(mock.lock().check_requests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn denied_check_and_unlisted_method_are_permission_denied() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().resolve_response = Some(session_outcome("p-uuid", 3600));
+        mock.lock().check_response = Some(wire::CheckResponse {
+            principal: Some(wire::Principal {
+                id: "p-uuid".into(),
+            }),
+            ok: false,
+        });
+        let svc = layer(uri, policy().public("/grpc.health.v1.Health/Check"))
+            .await
+            .layer(echo_principal());
+        let doc = GetDoc { id: "d7".into() };
+        let resp = svc
+            .clone()
+            .oneshot(call_request(GET, Some("tok"), &doc))
+            .await
+            .unwrap();
+        assert_eq!(grpc_status(&resp), Some(tonic::Code::PermissionDenied));
+        let resp = svc
+            .clone()
+            .oneshot(call_request("/docs.Docs/Delete", Some("tok"), &doc))
+            .await
+            .unwrap();
+        assert_eq!(grpc_status(&resp), Some(tonic::Code::PermissionDenied));
+        let resp = svc
+            .oneshot(call_request("/grpc.health.v1.Health/Check", None, &doc))
+            .await
+            .unwrap();
+        assert_eq!(grpc_status(&resp), None, "public method");
+    }
+
+    #[tokio::test]
+    async fn oversized_message_is_resource_exhausted_without_check() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().resolve_response = Some(session_outcome("p-uuid", 3600));
+        let svc = layer(uri, policy())
+            .await
+            .with_max_message_size(64)
+            .layer(echo_principal());
+        let doc = GetDoc {
+            id: "d".repeat(100),
+        };
+        let resp = svc
+            .oneshot(call_request(GET, Some("tok"), &doc))
+            .await
+            .unwrap();
+        assert_eq!(grpc_status(&resp), Some(tonic::Code::ResourceExhausted));
+        assert!(mock.lock().check_requests.is_empty());
+    }
+}
+
+#[cfg(feature = "axum")]
+mod axum_extractors {
+    use super::*;
+    use axum::extract::FromRequest;
+    use axum::extract::FromRequestParts;
+    use axum::http::request::Parts;
+    use axum::http::Method;
+    use axum::response::IntoResponse;
+    use nio_client::axum::{
+        ApiKeyAuth, AuthState, Authenticated, BearerTokenAuth, BodyResource, CsrfConfig,
+        ImpersonationAuditFn, ImpersonationEvent, RequireIamAdmin, RequireIamViewer, ShadowEvent,
+        ShadowReportFn, VersionedResource, WebResource, WebResourceError, WithLiveAccess,
+        WithOptPrincipal, WithPrincipal, WithPrincipalAt, WithPrincipalBody,
+    };
+    use nio_client::guard::Guard;
+    use nio_client::session::{token_hash, ApiKeyResolver, GrpcSessionResolver, ResolverConfig};
+
+    struct DocResource;
+
+    impl WebResource for DocResource {
+        type Rejection = std::convert::Infallible;
+
+        fn namespace(&self) -> Namespace {
+            Namespace("doc".into())
+        }
+        fn rel(&self, _method: &Method) -> Option<Rel> {
+            Some(Rel::viewer())
+        }
+        async fn parse<S: Send + Sync>(
+            _parts: &mut Parts,
+            _state: &S,
+        ) -> Result<Self, Self::Rejection> {
+            Ok(DocResource)
+        }
+        fn object(&self) -> Obj {
+            Obj("1".into())
+        }
+    }
+
+    async fn auth_state(uri: Uri, prefix: Option<&str>) -> AuthState {
+        let check_client = client(uri.clone()).await;
+        let channel = connect_channel(uri, None).await.expect("connect");
+        let resolver = GrpcSessionResolver::new(channel, ResolverConfig::default());
+        AuthState::new(check_client, resolver, prefix)
+    }
+
+    fn parts_with_headers(headers: &[(&str, &str)]) -> Parts {
+        let mut builder = axum::http::Request::builder().uri("/docs/1?x=1");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let (parts, _) = builder.body(()).unwrap().into_parts();
+        parts
+    }
+
+    #[tokio::test]
+    async fn cookie_auth_checks_resolved_principal_not_raw_token() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().resolve_response = Some(session_outcome("p-uuid", 3600));
+        mock.lock().check_response = Some(wire::CheckResponse {
+            principal: Some(wire::Principal {
+                id: "p-uuid".into(),
+            }),
+            ok: true,
+        });
+        let state = auth_state(uri, None).await;
+        let mut parts = parts_with_headers(&[("cookie", "session=raw-token")]);
+        let got = WithPrincipal::<DocResource>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("authorized");
+        assert_eq!(got.principal.as_str(), "p-uuid");
 
         let reqs = mock.lock().check_requests.clone();
         assert_eq!(reqs.len(), 1);
@@ -1191,7 +2383,7 @@ mod axum_extractors {
     #[tokio::test]
     async fn resolver_fault_is_internal_error_without_check() {
         let (mock, uri) = start_mock().await;
-        mock.lock().resolve_fail_next = true;
+        mock.lock().resolve_fail_next = Some(tonic::Code::Internal);
         let state = auth_state(uri, None).await;
         let mut parts = parts_with_headers(&[("cookie", "session=tok")]);
         let err = match WithPrincipal::<DocResource>::from_request_parts(&mut parts, &state).await {
@@ -1269,4 +2461,1414 @@ mod axum_extractors {
         assert_eq!(got.principal.0, "p-uuid");
         assert!(mock.lock().check_requests.is_empty(), "no check RPC");
     }
+
+    /// A comment post: the target document is named in the body.
+    struct CommentResource;
+
+    #[derive(serde::Deserialize)]
+    struct CommentBody {
+        doc: String,
+        text: String,
+    }
+
+    impl BodyResource<CommentBody> for CommentResource {
+        type Rejection = std::convert::Infallible;
+
+        fn namespace(&self) -> Namespace {
+            Namespace("doc".into())
+        }
+        fn rel(&self, method: &Method) -> Option<Rel> {
+            (*method == Method::POST).then(|| Rel("doc.comment".into()))
+        }
+        async fn parse<S: Send + Sync>(
+            _parts: &mut Parts,
+            _state: &S,
+        ) -> Result<Self, Self::Rejection> {
+            Ok(CommentResource)
+        }
+        fn object(&self, body: &CommentBody) -> Obj {
+            Obj(body.doc.clone())
+        }
+    }
+
+    fn post(content_type: &str, body: &str) -> axum::extract::Request {
+        axum::http::Request::builder()
+            .method(Method::POST)
+            .uri("/comments")
+            .header("cookie", "session=tok")
+            .header("content-type", content_type)
+            .body(axum::body::Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn allow_p_uuid(mock: &Mock) {
+        mock.lock().resolve_response = Some(session_outcome("p-uuid", 3600));
+        mock.lock().check_response = Some(wire::CheckResponse {
+            principal: Some(wire::Principal {
+                id: "p-uuid".into(),
+            }),
+            ok: true,
+        });
+    }
+
+    #[tokio::test]
+    async fn body_extractor_checks_object_from_json_body() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let state = auth_state(uri, None).await;
+        let req = post("application/json", r#"{"doc":"d-7","text":"hi"}"#);
+        let got = WithPrincipalBody::<CommentResource, CommentBody>::from_request(req, &state)
+            .await
+            .expect("authorized");
+        assert_eq!(got.principal.as_str(), "p-uuid");
+        assert_eq!(got.body.text, "hi");
+
+        let reqs = mock.lock().check_requests.clone();
+        assert_eq!(reqs.len(), 1);
+        assert_eq!(reqs[0].obj, "d-7", "object comes from the body");
+        assert_eq!(reqs[0].rel, "doc.comment");
+        assert_eq!(reqs[0].user_id, "p-uuid");
+    }
+
+    #[tokio::test]
+    async fn body_extractor_accepts_form_body() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let state = auth_state(uri, None).await;
+        let req = post("application/x-www-form-urlencoded", "doc=d-9&text=yo");
+        let got = WithPrincipalBody::<CommentResource, CommentBody>::from_request(req, &state)
+            .await
+            .expect("authorized");
+        assert_eq!(got.body.doc, "d-9");
+        assert_eq!(mock.lock().check_requests[0].obj, "d-9");
+    }
+
+    #[tokio::test]
+    async fn body_extractor_rejects_bad_body_without_check() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let state = auth_state(uri, None).await;
+        let req = post("application/json", "{not json");
+        let err = match WithPrincipalBody::<CommentResource, CommentBody>::from_request(req, &state)
+            .await
+        {
+            Ok(_) => panic!("bad body must reject"),
+            Err(err) => err,
+        };
+        assert!(matches!(err, WebResourceError::Parse(_)));
+        assert!(mock.lock().check_requests.is_empty(), "no check RPC");
+    }
+
+    #[tokio::test]
+    async fn body_extractor_without_session_redirects_before_reading_body() {
+        let (mock, uri) = start_mock().await;
+        let state = auth_state(uri, None).await;
+        let req = axum::http::Request::builder()
+            .method(Method::POST)
+            .uri("/comments")
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from("{not json"))
+            .unwrap();
+        let err = match WithPrincipalBody::<CommentResource, CommentBody>::from_request(req, &state)
+            .await
+        {
+            Ok(_) => panic!("missing session must reject"),
+            Err(err) => err,
+        };
+        assert!(matches!(err, WebResourceError::MissingSession(_)));
+        assert!(mock.lock().resolve_requests.is_empty());
+        assert!(mock.lock().check_requests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn body_extractor_forbidden_is_forbidden() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().resolve_response = Some(session_outcome("p-uuid", 3600));
+        let state = auth_state(uri, None).await;
+        let req = post("application/json", r#"{"doc":"d-7","text":"hi"}"#);
+        let err = match WithPrincipalBody::<CommentResource, CommentBody>::from_request(req, &state)
+            .await
+        {
+            Ok(_) => panic!("forbidden check must reject"),
+            Err(err) => err,
+        };
+        assert!(matches!(err, WebResourceError::Forbidden));
+    }
+
+    /// "editor on the article AND viewer on its folder" / "admin OR owner".
+    struct GuardedDoc;
+
+    impl WebResource for GuardedDoc {
+        type Rejection = std::convert::Infallible;
+
+        fn namespace(&self) -> Namespace {
+            Namespace("doc".into())
+        }
+        fn rel(&self, _method: &Method) -> Option<Rel> {
+            Some(Rel::editor())
+        }
+        async fn parse<S: Send + Sync>(
+            _parts: &mut Parts,
+            _state: &S,
+        ) -> Result<Self, Self::Rejection> {
+            Ok(GuardedDoc)
+        }
+        fn object(&self) -> Obj {
+            Obj("1".into())
+        }
+        fn guard(&self, method: &Method) -> Option<Guard> {
+            let doc = |rel: Rel| Guard::check(self.namespace(), self.object(), rel);
+            match *method {
+                Method::GET => Some(doc(Rel::editor()).and(Guard::check(
+                    Namespace("folder".into()),
+                    Obj("f".into()),
+                    Rel::viewer(),
+                ))),
+                Method::DELETE => Some(doc(Rel::admin()).or(doc(Rel("owner".into())))),
+                _ => None,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn all_of_guard_runs_every_clause() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let state = auth_state(uri, None).await;
+        let mut parts = parts_with_headers(&[("cookie", "session=tok")]);
+        let got = WithPrincipal::<GuardedDoc>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("authorized");
+        assert_eq!(got.principal.as_str(), "p-uuid");
+        let mut checked: Vec<_> = mock
+            .lock()
+            .check_requests
+            .iter()
+            .map(|r| format!("{}:{}#{}@{}", r.ns, r.obj, r.rel, r.ts))
+            .collect();
+        checked.sort();
+        assert_eq!(
+            checked,
+            vec![
+                "doc:1#editor@AQAAAAAAAA==".to_string(),
+                "folder:f#viewer@AQAAAAAAAA==".to_string(),
+            ],
+            "every clause at the same zookie"
+        );
+    }
+
+    #[tokio::test]
+    async fn any_of_guard_denial_names_the_clauses() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().resolve_response = Some(session_outcome("p-uuid", 3600));
+        let state = auth_state(uri, None).await;
+        let (mut parts, _) = axum::http::Request::builder()
+            .method(Method::DELETE)
+            .uri("/docs/1")
+            .header("cookie", "session=tok")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let err = match WithPrincipal::<GuardedDoc>::from_request_parts(&mut parts, &state).await {
+            Ok(_) => panic!("denied guard must reject"),
+            Err(err) => err,
+        };
+        match err {
+            WebResourceError::GuardDenied(denial) => {
+                let mut rels: Vec<_> = denial.clauses.iter().map(|c| c.rel.0.clone()).collect();
+                rels.sort();
+                assert_eq!(rels, vec!["admin".to_string(), "owner".to_string()]);
+            }
+            other => panic!("expected GuardDenied, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn anonymous_checks_grant_public_resource_as_anonymous() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().check_response = Some(wire::CheckResponse {
+            principal: Some(wire::Principal {
+                id: "allUsers".into(),
+            }),
+            ok: true,
+        });
+        let state = auth_state(uri, None).await.with_anonymous_checks(true);
+        let mut parts = parts_with_headers(&[]);
+        let got = WithOptPrincipal::<DocResource>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("public resource");
+        assert!(got.principal.expect("anonymous principal").is_anonymous());
+        let reqs = mock.lock().check_requests.clone();
+        assert_eq!(reqs.len(), 1);
+        assert_eq!(reqs[0].user_id, "allUsers");
+        assert!(mock.lock().resolve_requests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn anonymous_checks_redirect_private_resource_to_signin() {
+        let (mock, uri) = start_mock().await; // default check: unknown user
+        let state = auth_state(uri, Some("/app"))
+            .await
+            .with_anonymous_checks(true);
+        let mut parts = parts_with_headers(&[]);
+        let err =
+            match WithOptPrincipal::<DocResource>::from_request_parts(&mut parts, &state).await {
+                Ok(_) => panic!("private resource must reject anonymous"),
+                Err(err) => err,
+            };
+        match err {
+            WebResourceError::MissingSession(loc) => {
+                assert!(loc.starts_with("/app/signin?back="), "loc={loc}")
+            }
+            other => panic!("expected MissingSession, got {other:?}"),
+        }
+        assert_eq!(mock.lock().check_requests[0].user_id, "allUsers");
+    }
+
+    #[tokio::test]
+    async fn anonymous_checks_apply_to_unknown_session() {
+        let (mock, uri) = start_mock().await; // default resolve: NotFound
+        mock.lock().check_response = Some(wire::CheckResponse {
+            principal: Some(wire::Principal {
+                id: "allUsers".into(),
+            }),
+            ok: true,
+        });
+        let state = auth_state(uri, None).await.with_anonymous_checks(true);
+        let mut parts = parts_with_headers(&[("cookie", "session=stale")]);
+        let got = WithOptPrincipal::<DocResource>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("public resource");
+        assert!(got.principal.expect("anonymous principal").is_anonymous());
+        assert_eq!(mock.lock().check_requests[0].user_id, "allUsers");
+    }
+
+    #[tokio::test]
+    async fn iam_gates_check_root_with_get_and_update() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let state = auth_state(uri, None).await;
+
+        let mut parts = parts_with_headers(&[("cookie", "session=tok")]);
+        let viewer = <RequireIamViewer>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("viewer gate");
+        assert_eq!(viewer.principal.as_str(), "p-uuid");
+
+        let mut parts = parts_with_headers(&[("authorization", "Bearer tok")]);
+        let admin = RequireIamAdmin::<BearerTokenAuth>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("admin gate");
+        assert_eq!(admin.principal.as_str(), "p-uuid");
+
+        let checked: Vec<_> = mock
+            .lock()
+            .check_requests
+            .iter()
+            .map(|r| format!("{}:{}#{}", r.ns, r.obj, r.rel))
+            .collect();
+        assert_eq!(checked, vec!["iam:root#iam.get", "iam:root#iam.update"]);
+    }
+
+    #[tokio::test]
+    async fn iam_admin_gate_denial_is_forbidden() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().resolve_response = Some(session_outcome("p-uuid", 3600));
+        let state = auth_state(uri, None).await;
+        let mut parts = parts_with_headers(&[("cookie", "session=tok")]);
+        let err = match <RequireIamAdmin>::from_request_parts(&mut parts, &state).await {
+            Ok(_) => panic!("admin gate must reject"),
+            Err(err) => err,
+        };
+        assert!(matches!(err, WebResourceError::Forbidden));
+    }
+
+    const Z1: &str = "AQAAAAAAAQ==";
+    const Z2: &str = "AQAAAAAAAg==";
+
+    fn zookie_router(state: AuthState) -> axum::Router {
+        use axum::extract::State;
+        axum::Router::new()
+            .route(
+                "/docs/1",
+                axum::routing::get(|_: WithPrincipal<DocResource>| async { "ok" }).put(
+                    |_: WithPrincipal<DocResource>, State(state): State<AuthState>| async move {
+                        let mut client = state.check_client.clone();
+                        client.write(vec![], vec![], None).await.expect("write");
+                        "written"
+                    },
+                ),
+            )
+            .layer(axum::middleware::from_fn(
+                nio_client::axum::propagate_zookie,
+            ))
+            .with_state(state)
+    }
+
+    async fn send(
+        router: axum::Router,
+        req: axum::http::Request<axum::body::Body>,
+    ) -> axum::response::Response {
+        use tower::ServiceExt;
+        router.oneshot(req).await.expect("infallible")
+    }
+
+    #[tokio::test]
+    async fn zookie_cookie_pins_checks_and_write_zookie_is_reemitted() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        mock.lock().write_response = Some(wire::WriteResponse { ts: Z2.into() });
+        let router = zookie_router(auth_state(uri, None).await);
+        let req = axum::http::Request::put("/docs/1")
+            .header("cookie", format!("session=tok; check_ts={Z1}"))
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = send(router, req).await;
+        assert_eq!(resp.status(), axum::http::StatusCode::OK);
+        assert_eq!(
+            mock.lock().check_requests[0].ts,
+            Z1,
+            "check at the cookie's zookie"
+        );
+        assert_eq!(resp.headers()["x-nio-zookie"], Z2, "commit zookie wins");
+        let cookie = resp.headers()["set-cookie"].to_str().unwrap();
+        assert!(cookie.starts_with(&format!("check_ts={Z2};")), "{cookie}");
+    }
+
+    #[tokio::test]
+    async fn zookie_header_wins_and_malformed_zookies_are_ignored() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let router = zookie_router(auth_state(uri, None).await);
+        let req = axum::http::Request::get("/docs/1")
+            .header("cookie", format!("session=tok; check_ts={Z1}"))
+            .header("x-nio-zookie", Z2)
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = send(router.clone(), req).await;
+        assert_eq!(resp.headers()["x-nio-zookie"], Z2);
+
+        let req = axum::http::Request::get("/docs/1")
+            .header("cookie", "session=tok; check_ts=garbage")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = send(router, req).await;
+        assert_eq!(resp.status(), axum::http::StatusCode::OK);
+        assert!(resp.headers().get("set-cookie").is_none());
+        let ts: Vec<_> = mock
+            .lock()
+            .check_requests
+            .iter()
+            .map(|r| r.ts.clone())
+            .collect();
+        assert_eq!(ts, vec![Z2.to_string(), Timestamp::EMPTY.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn future_zookies_are_ignored_and_the_cookie_is_secure() {
+        use base64::Engine;
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        mock.lock().write_response = Some(wire::WriteResponse { ts: Z2.into() });
+        let router = zookie_router(auth_state(uri, None).await);
+        // epoch 1, millis 2^48 - 1: thousands of years from now.
+        let future =
+            base64::engine::general_purpose::STANDARD.encode([1, 255, 255, 255, 255, 255, 255]);
+        let req = axum::http::Request::put("/docs/1")
+            .header("cookie", "session=tok")
+            .header("x-nio-zookie", &future)
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = send(router, req).await;
+        assert_eq!(
+            mock.lock().check_requests[0].ts,
+            Timestamp::EMPTY,
+            "a zookie check cannot have issued yet bounds nothing"
+        );
+        let cookie = resp.headers()["set-cookie"].to_str().unwrap();
+        assert!(cookie.starts_with(&format!("check_ts={Z2};")), "{cookie}");
+        assert!(cookie.ends_with("; Secure"), "{cookie}");
+    }
+
+    #[tokio::test]
+    async fn zookie_cookie_can_drop_secure_for_plain_http() {
+        use nio_client::axum::{propagate_zookie_with, ZookieConfig};
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let config = ZookieConfig {
+            secure_cookie: false,
+            ..ZookieConfig::default()
+        };
+        let router = axum::Router::new()
+            .route(
+                "/docs/1",
+                axum::routing::get(|_: WithPrincipal<DocResource>| async { "ok" }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                config,
+                propagate_zookie_with,
+            ))
+            .with_state(auth_state(uri, None).await);
+        let req = axum::http::Request::get("/docs/1")
+            .header("cookie", format!("session=tok; check_ts={Z1}"))
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = send(router, req).await;
+        let cookie = resp.headers()["set-cookie"].to_str().unwrap();
+        assert!(!cookie.contains("Secure"), "{cookie}");
+    }
+
+    #[tokio::test]
+    async fn installed_memo_shares_the_guard_check_with_the_handler() {
+        use nio_client::axum::{install_memo, Memo};
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let state = auth_state(uri, None).await;
+        let router = axum::Router::new()
+            .route(
+                "/docs/1",
+                axum::routing::get(|p: WithPrincipal<DocResource>, memo: Memo| async move {
+                    let user = UserId(p.principal.as_str().to_string());
+                    for _ in 0..3 {
+                        let r = memo
+                            .check(
+                                Namespace("doc".into()),
+                                Obj("1".into()),
+                                Rel::viewer(),
+                                user.clone(),
+                            )
+                            .await
+                            .expect("check");
+                        assert!(matches!(r, CheckResult::Ok(_)));
+                    }
+                    "ok"
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                install_memo,
+            ))
+            .with_state(state);
+        let req = axum::http::Request::get("/docs/1")
+            .header("cookie", "session=tok")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = send(router, req).await;
+        assert_eq!(resp.status(), axum::http::StatusCode::OK);
+        assert_eq!(
+            mock.lock().check_requests.len(),
+            1,
+            "guard check answers the widgets"
+        );
+    }
+
+    /// A document loaded with the zookie stored with its content.
+    struct VersionedDoc;
+
+    impl WebResource for VersionedDoc {
+        type Rejection = std::convert::Infallible;
+
+        fn namespace(&self) -> Namespace {
+            Namespace("doc".into())
+        }
+        fn rel(&self, _method: &Method) -> Option<Rel> {
+            Some(Rel::viewer())
+        }
+        async fn parse<S: Send + Sync>(
+            _parts: &mut Parts,
+            _state: &S,
+        ) -> Result<Self, Self::Rejection> {
+            Ok(VersionedDoc)
+        }
+        fn object(&self) -> Obj {
+            Obj("1".into())
+        }
+    }
+
+    impl VersionedResource for VersionedDoc {
+        fn zookie(&self) -> Option<Timestamp> {
+            Some(Timestamp(Z1.into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn versioned_extractor_checks_at_the_content_zookie() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let state = auth_state(uri, None).await;
+        let mut parts = parts_with_headers(&[("cookie", "session=tok")]);
+        let got = WithPrincipalAt::<VersionedDoc>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("authorized");
+        assert_eq!(got.principal.as_str(), "p-uuid");
+        assert_eq!(mock.lock().check_requests[0].ts, Z1);
+    }
+
+    #[tokio::test]
+    async fn csrf_rejects_cross_site_cookie_post_before_resolving() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let state = auth_state(uri, None).await.with_csrf(CsrfConfig {
+            allowed_origins: vec!["https://app.example".into()],
+            double_submit: false,
+        });
+        let mut parts = parts_with_headers(&[
+            ("cookie", "session=tok"),
+            ("origin", "https://evil.example"),
+        ]);
+        parts.method = Method::POST;
+        let err = match WithPrincipal::<DocResource>::from_request_parts(&mut parts, &state).await {
+            Ok(_) => panic!("cross-site post must be rejected"),
+            Err(err) => err,
+        };
+        assert!(matches!(err, WebResourceError::Csrf(_)));
+        assert!(mock.lock().resolve_requests.is_empty());
+
+        // Bearer credentials are not ambient and skip the defenses.
+        let mut parts = parts_with_headers(&[
+            ("authorization", "Bearer tok"),
+            ("origin", "https://evil.example"),
+        ]);
+        parts.method = Method::POST;
+        WithPrincipal::<DocResource, BearerTokenAuth>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("bearer is not subject to csrf");
+    }
+
+    fn audited(events: &Arc<Mutex<Vec<ImpersonationEvent>>>) -> ImpersonationAuditFn {
+        let events = events.clone();
+        Arc::new(move |e: &ImpersonationEvent| events.lock().unwrap().push(e.clone()))
+    }
+
+    #[tokio::test]
+    async fn admin_acts_as_named_principal_and_decision_is_audited() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let events = Arc::new(Mutex::new(vec![]));
+        let state = auth_state(uri, None)
+            .await
+            .with_impersonation(audited(&events));
+        let mut parts =
+            parts_with_headers(&[("cookie", "session=tok"), ("x-nio-act-as", "u-target")]);
+        let got = WithPrincipal::<DocResource>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("authorized");
+        assert_eq!(got.actor.expect("actor").as_str(), "p-uuid");
+        let checked: Vec<_> = mock
+            .lock()
+            .check_requests
+            .iter()
+            .map(|r| format!("{}:{}#{}@{}", r.ns, r.obj, r.rel, r.user_id))
+            .collect();
+        assert_eq!(
+            checked,
+            vec!["iam:root#iam.update@p-uuid", "doc:1#viewer@u-target"],
+            "gate as the caller, then the resource as the target"
+        );
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].actor.0, "p-uuid");
+        assert_eq!(events[0].subject.0, "u-target");
+        assert!(events[0].granted);
+        assert_eq!(events[0].uri, "/docs/1?x=1");
+    }
+
+    #[tokio::test]
+    async fn admin_gate_decision_carries_request_context() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let records = Arc::new(Records::default());
+        let mut state = auth_state(uri, None)
+            .await
+            .with_impersonation(Arc::new(|_: &ImpersonationEvent| {}));
+        state.check_client = state.check_client.with_audit(records.clone());
+        let mut parts = parts_with_headers(&[
+            ("cookie", "session=tok"),
+            ("x-nio-act-as", "u-target"),
+            ("x-request-id", "req-7"),
+        ]);
+        WithPrincipal::<DocResource>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("authorized");
+        let records = records.0.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].rel, Rel::iam_update(), "the admin gate");
+        for record in records.iter() {
+            assert_eq!(record.context.request_id.as_deref(), Some("req-7"));
+            assert_eq!(record.context.tenant.as_deref(), Some("t1"));
+        }
+    }
+
+    #[tokio::test]
+    async fn impersonation_needs_the_admin_gate() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        mock.lock().check_response = Some(wire::CheckResponse {
+            principal: Some(wire::Principal {
+                id: "p-uuid".into(),
+            }),
+            ok: false,
+        });
+        let events = Arc::new(Mutex::new(vec![]));
+        let state = auth_state(uri, None)
+            .await
+            .with_impersonation(audited(&events));
+        let mut parts = parts_with_headers(&[("cookie", "session=tok; act_as=u-target")]);
+        let err = match WithPrincipal::<DocResource>::from_request_parts(&mut parts, &state).await {
+            Ok(_) => panic!("non-admin must not impersonate"),
+            Err(err) => err,
+        };
+        assert!(matches!(err, WebResourceError::Forbidden));
+        assert_eq!(
+            mock.lock().check_requests.len(),
+            1,
+            "resource never checked"
+        );
+        let events = events.lock().unwrap();
+        assert!(!events[0].granted);
+        assert_eq!(
+            events[0].guard,
+            Guard::check(Namespace::iam(), Obj::root(), Rel::iam_update())
+        );
+    }
+
+    #[tokio::test]
+    async fn opt_principal_acts_as_named_principal() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let events = Arc::new(Mutex::new(vec![]));
+        let state = auth_state(uri, None)
+            .await
+            .with_impersonation(audited(&events));
+        let mut parts =
+            parts_with_headers(&[("cookie", "session=tok"), ("x-nio-act-as", "u-target")]);
+        let got = WithOptPrincipal::<DocResource>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("authorized");
+        assert_eq!(got.actor.expect("actor").as_str(), "p-uuid");
+        assert_eq!(mock.lock().check_requests[1].user_id, "u-target");
+        assert_eq!(events.lock().unwrap()[0].subject.0, "u-target");
+    }
+
+    #[tokio::test]
+    async fn extractors_without_act_as_reject_it_instead_of_using_the_caller() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let events = Arc::new(Mutex::new(vec![]));
+        let state = auth_state(uri, None)
+            .await
+            .with_impersonation(audited(&events));
+
+        let mut parts =
+            parts_with_headers(&[("cookie", "session=tok"), ("x-nio-act-as", "u-target")]);
+        let err = match WithLiveAccess::<DocResource>::from_request_parts(&mut parts, &state).await
+        {
+            Ok(_) => panic!("live access cannot act as another principal"),
+            Err(err) => err,
+        };
+        assert!(matches!(err, WebResourceError::Forbidden));
+
+        let mut parts = parts_with_headers(&[
+            ("authorization", "Bearer api-token"),
+            ("cookie", "act_as=u-target"),
+        ]);
+        let err =
+            match Authenticated::<BearerTokenAuth>::from_request_parts(&mut parts, &state).await {
+                Ok(_) => panic!("authenticated cannot act as another principal"),
+                Err(err) => err,
+            };
+        assert!(matches!(err, WebResourceError::Forbidden));
+        assert!(mock.lock().check_requests.is_empty(), "no check ran");
+
+        // Naming yourself is not impersonation.
+        let mut parts = parts_with_headers(&[
+            ("authorization", "Bearer api-token"),
+            ("x-nio-act-as", "p-uuid"),
+        ]);
+        Authenticated::<BearerTokenAuth>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("authenticated");
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|e| !e.granted && e.subject.0 == "u-target"));
+    }
+
+    #[tokio::test]
+    async fn act_as_is_ignored_without_impersonation_enabled() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let state = auth_state(uri, None).await;
+        let mut parts =
+            parts_with_headers(&[("cookie", "session=tok"), ("x-nio-act-as", "u-target")]);
+        let got = WithPrincipal::<DocResource>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("authorized");
+        assert!(got.actor.is_none());
+        assert_eq!(mock.lock().check_requests[0].user_id, "p-uuid");
+    }
+
+    /// Key store fetcher: knows one key hash, counts fetches.
+    struct KeyStore {
+        key_hash: String,
+        fetches: AtomicUsize,
+    }
+
+    impl nio_client::session::SessionFetcher for KeyStore {
+        fn fetch<'a>(&'a self, token_hash: &'a str) -> nio_client::session::ResolveFuture<'a> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            let known = token_hash == self.key_hash;
+            Box::pin(async move {
+                Ok(known.then(|| nio_client::session::ResolvedSession {
+                    principal: "svc-billing".into(),
+                    tenant_id: "t1".into(),
+                    expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+                }))
+            })
+        }
+    }
+
+    async fn api_key_state(uri: Uri) -> (AuthState, Arc<KeyStore>) {
+        let store = Arc::new(KeyStore {
+            key_hash: token_hash("key-1"),
+            fetches: AtomicUsize::new(0),
+        });
+        let resolver = ApiKeyResolver::new(store.clone(), ResolverConfig::default());
+        (auth_state(uri, None).await.with_api_keys(resolver), store)
+    }
+
+    #[tokio::test]
+    async fn api_key_checks_as_service_account_through_cache() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().check_response = Some(wire::CheckResponse {
+            principal: Some(wire::Principal {
+                id: "svc-billing".into(),
+            }),
+            ok: true,
+        });
+        let (state, store) = api_key_state(uri).await;
+        for _ in 0..2 {
+            let mut parts = parts_with_headers(&[("x-api-key", "key-1")]);
+            let got =
+                WithPrincipal::<DocResource, ApiKeyAuth>::from_request_parts(&mut parts, &state)
+                    .await
+                    .expect("authorized");
+            assert_eq!(got.principal.as_str(), "svc-billing");
+        }
+        assert_eq!(store.fetches.load(Ordering::SeqCst), 1, "second hit cached");
+        let reqs = mock.lock().check_requests.clone();
+        assert!(reqs.iter().all(|r| r.user_id == "svc-billing"));
+        assert!(
+            mock.lock().resolve_requests.is_empty(),
+            "keys never reach the session service"
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_or_unknown_api_key_is_unauthorized() {
+        let (mock, uri) = start_mock().await;
+        let (state, store) = api_key_state(uri).await;
+        for headers in [
+            vec![],
+            vec![("x-api-key", "nope")],
+            vec![("x-api-key", "nope")],
+        ] {
+            let mut parts = parts_with_headers(&headers);
+            let got =
+                WithPrincipal::<DocResource, ApiKeyAuth>::from_request_parts(&mut parts, &state)
+                    .await;
+            assert!(matches!(got, Err(WebResourceError::Unauthorized)));
+        }
+        assert_eq!(
+            store.fetches.load(Ordering::SeqCst),
+            1,
+            "unknown key tombstoned"
+        );
+        assert!(mock.lock().check_requests.is_empty(), "zero check RPCs");
+    }
+
+    #[tokio::test]
+    async fn api_key_auth_without_resolver_is_internal_error() {
+        let (mock, uri) = start_mock().await;
+        let state = auth_state(uri, None).await;
+        let mut parts = parts_with_headers(&[("x-api-key", "key-1")]);
+        let got =
+            WithPrincipal::<DocResource, ApiKeyAuth>::from_request_parts(&mut parts, &state).await;
+        assert!(matches!(got, Err(WebResourceError::InternalServerError(_))));
+        assert!(mock.lock().check_requests.is_empty());
+    }
+
+    /// Migrating from `viewer` to `doc.read`, in shadow mode.
+    struct MigratingDoc;
+
+    impl WebResource for MigratingDoc {
+        type Rejection = std::convert::Infallible;
+
+        fn namespace(&self) -> Namespace {
+            Namespace("doc".into())
+        }
+        fn rel(&self, _method: &Method) -> Option<Rel> {
+            Some(Rel("doc.read".into()))
+        }
+        async fn parse<S: Send + Sync>(
+            _parts: &mut Parts,
+            _state: &S,
+        ) -> Result<Self, Self::Rejection> {
+            Ok(MigratingDoc)
+        }
+        fn object(&self) -> Obj {
+            Obj("1".into())
+        }
+        fn shadow(&self, _method: &Method) -> bool {
+            true
+        }
+        fn old_rel(&self, _method: &Method) -> Option<Rel> {
+            Some(Rel::viewer())
+        }
+    }
+
+    fn shadow_events() -> (ShadowReportFn, Arc<Mutex<Vec<ShadowEvent>>>) {
+        let events = Arc::new(Mutex::new(vec![]));
+        let sink = events.clone();
+        let report: ShadowReportFn = Arc::new(move |e: &ShadowEvent| {
+            sink.lock().unwrap().push(e.clone());
+        });
+        (report, events)
+    }
+
+    #[tokio::test]
+    async fn shadow_denial_is_reported_with_divergence_but_not_enforced() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        mock.lock().check_deny_rels = vec!["doc.read".into()];
+        let (report, events) = shadow_events();
+        let state = auth_state(uri, None).await.with_shadow_report(report);
+        let mut parts = parts_with_headers(&[("cookie", "session=tok")]);
+        let got = WithPrincipal::<MigratingDoc>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("shadow denial lets the request through");
+        assert_eq!(got.principal.as_str(), "p-uuid");
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].granted);
+        assert_eq!(events[0].old_granted, Some(true));
+        assert!(events[0].diverged());
+        let rels: Vec<_> = mock
+            .lock()
+            .check_requests
+            .iter()
+            .map(|r| r.rel.clone())
+            .collect();
+        assert_eq!(rels, ["doc.read", "viewer"]);
+    }
+
+    #[tokio::test]
+    async fn shadow_enforces_the_old_relation_when_both_deny() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        mock.lock().check_deny_rels = vec!["doc.read".into(), "viewer".into()];
+        let (report, events) = shadow_events();
+        let state = auth_state(uri, None).await.with_shadow_report(report);
+        let mut parts = parts_with_headers(&[("cookie", "session=tok")]);
+        let err = match WithPrincipal::<MigratingDoc>::from_request_parts(&mut parts, &state).await
+        {
+            Ok(_) => panic!("the old relation is in force"),
+            Err(err) => err,
+        };
+        assert_eq!(err.into_response().status(), http::StatusCode::FORBIDDEN);
+        let events = events.lock().unwrap();
+        assert!(!events[0].granted);
+        assert_eq!(events[0].old_granted, Some(false));
+        assert!(!events[0].diverged());
+    }
+
+    #[tokio::test]
+    async fn global_shadow_mode_without_old_relation_enforces_the_guard() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().resolve_response = Some(session_outcome("p-uuid", 3600));
+        let (report, events) = shadow_events();
+        let state = auth_state(uri, None)
+            .await
+            .with_shadow_mode(true)
+            .with_shadow_report(report);
+        let mut parts = parts_with_headers(&[("cookie", "session=tok")]);
+        let got = WithPrincipal::<DocResource>::from_request_parts(&mut parts, &state).await;
+        assert!(matches!(got, Err(WebResourceError::Forbidden)));
+        let events = events.lock().unwrap();
+        assert!(!events[0].granted);
+        assert_eq!(events[0].old_granted, None, "no old relation");
+        assert!(!events[0].diverged());
+    }
+
+    #[tokio::test]
+    async fn opt_principal_honors_shadow_mode() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        mock.lock().check_deny_rels = vec!["doc.read".into()];
+        let (report, events) = shadow_events();
+        let state = auth_state(uri, None).await.with_shadow_report(report);
+        let mut parts = parts_with_headers(&[("cookie", "session=tok")]);
+        let got = WithOptPrincipal::<MigratingDoc>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("the old relation grants");
+        assert_eq!(got.principal.unwrap().as_str(), "p-uuid");
+        assert!(events.lock().unwrap()[0].diverged());
+    }
+
+    #[tokio::test]
+    async fn live_access_reports_the_new_relation_in_shadow_mode() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        mock.lock().check_deny_rels = vec!["doc.read".into()];
+        let (report, events) = shadow_events();
+        let state = auth_state(uri, None).await.with_shadow_report(report);
+        let mut parts = parts_with_headers(&[("cookie", "session=tok")]);
+        let got = WithLiveAccess::<MigratingDoc>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("the old relation grants");
+        assert_eq!(got.principal.as_str(), "p-uuid");
+        let events = events.lock().unwrap();
+        let doc =
+            |rel: &str| Guard::check(Namespace("doc".into()), Obj("1".into()), Rel(rel.into()));
+        assert_eq!(events[0].guard, doc("doc.read"));
+        assert!(!events[0].granted, "the new relation's decision");
+        assert_eq!(events[0].old_guard, Some(doc("viewer")));
+        assert_eq!(events[0].old_granted, Some(true));
+        assert!(events[0].diverged());
+    }
+
+    #[tokio::test]
+    async fn shadow_does_not_hide_backend_faults() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        mock.lock().check_fail_next = true;
+        let (report, events) = shadow_events();
+        let state = auth_state(uri, None)
+            .await
+            .with_shadow_mode(true)
+            .with_shadow_report(report);
+        let mut parts = parts_with_headers(&[("cookie", "session=tok")]);
+        let got = WithPrincipal::<DocResource>::from_request_parts(&mut parts, &state).await;
+        assert!(matches!(got, Err(WebResourceError::InternalServerError(_))));
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn extractor_decisions_carry_request_context() {
+        let (mock, uri) = start_mock().await;
+        allow_p_uuid(&mock);
+        let records = Arc::new(Records::default());
+        let mut state = auth_state(uri, None).await;
+        state.check_client = state.check_client.with_audit(records.clone());
+        let mut parts =
+            parts_with_headers(&[("authorization", "Bearer tok"), ("x-request-id", "req-42")]);
+        WithPrincipal::<DocResource, BearerTokenAuth>::from_request_parts(&mut parts, &state)
+            .await
+            .expect("authorized");
+
+        let records = records.0.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        let context = &records[0].context;
+        assert_eq!(context.request_id.as_deref(), Some("req-42"));
+        assert_eq!(context.route.as_deref(), Some("/docs/1"));
+        assert_eq!(context.auth_type, Some("bearer_token"));
+        assert_eq!(context.tenant.as_deref(), Some("t1"));
+        assert_eq!(records[0].user, UserId("p-uuid".into()));
+    }
+}
+
+mod tls_reload {
+    use super::*;
+    use base64::Engine;
+    use nio_client::tls::{
+        ReloadableChannel, TlsCallback, TlsError, TlsFiles, TlsMaterial, TlsReloadConfig,
+    };
+    use std::time::Duration;
+
+    const MANUAL: TlsReloadConfig = TlsReloadConfig {
+        poll_interval: Duration::ZERO,
+    };
+
+    fn der(pem: &[u8]) -> Vec<u8> {
+        let body: String = std::str::from_utf8(pem)
+            .unwrap()
+            .lines()
+            .filter(|l| !l.starts_with("-----"))
+            .collect();
+        base64::engine::general_purpose::STANDARD
+            .decode(body)
+            .unwrap()
+    }
+
+    fn material(client: &str) -> TlsMaterial {
+        TlsMaterial {
+            ca: Some(tls_fixture("ca.pem")),
+            identity: Some((
+                tls_fixture(&format!("{client}.pem")),
+                tls_fixture(&format!("{client}.key")),
+            )),
+            domain: None,
+        }
+    }
+
+    /// A callback source serving whatever `current` holds.
+    fn callback(current: Arc<Mutex<Result<TlsMaterial, String>>>) -> Arc<TlsCallback> {
+        Arc::new(TlsCallback::new(Arc::new(move || {
+            let current = current.lock().unwrap().clone();
+            Box::pin(async move { current.map_err(TlsError::Source) })
+        })))
+    }
+
+    async fn check(c: &mut CheckClient) {
+        c.check(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::viewer(),
+            UserId("u1".into()),
+            None,
+        )
+        .await
+        .expect("check");
+    }
+
+    fn last_peer_cert(mock: &Mock) -> Vec<u8> {
+        let state = mock.lock();
+        state
+            .check_peer_certs
+            .last()
+            .cloned()
+            .flatten()
+            .expect("client cert")
+    }
+
+    #[tokio::test]
+    async fn rotated_files_switch_new_calls_and_keep_open_watch_streams() {
+        let (mock, uri) = start_tls_mock().await;
+        let dir = std::env::temp_dir().join(format!("nio-tls-rotate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ca.crt"), tls_fixture("ca.pem")).unwrap();
+        std::fs::write(dir.join("tls.crt"), tls_fixture("client-a.pem")).unwrap();
+        std::fs::write(dir.join("tls.key"), tls_fixture("client-a.key")).unwrap();
+        let source = Arc::new(
+            TlsFiles::new(dir.join("tls.crt"), dir.join("tls.key")).with_ca(dir.join("ca.crt")),
+        );
+        let channel = ReloadableChannel::connect(uri, source, MANUAL)
+            .await
+            .expect("connect");
+        let mut c = CheckClient::from_transport(nio_client::transport(channel.clone()));
+        check(&mut c).await;
+        assert_eq!(last_peer_cert(&mock), der(&tls_fixture("client-a.pem")));
+
+        let hold = Arc::new(tokio::sync::Notify::new());
+        {
+            let mut state = mock.lock();
+            state.watch_hold = Some(hold.clone());
+            state.watch_responses = vec![
+                wire::WatchResponse {
+                    ts: "hb-1".into(),
+                    updates: vec![],
+                },
+                wire::WatchResponse {
+                    ts: "hb-2".into(),
+                    updates: vec![],
+                },
+            ];
+        }
+        let mut stream = c
+            .watch(Namespace("doc".into()), Timestamp("resume-ts".into()))
+            .await
+            .expect("watch");
+        assert_eq!(stream.recv().await.unwrap().unwrap().ts.0, "hb-1");
+
+        std::fs::write(dir.join("tls.crt"), tls_fixture("client-b.pem")).unwrap();
+        std::fs::write(dir.join("tls.key"), tls_fixture("client-b.key")).unwrap();
+        assert!(channel.reload().await.expect("reload"), "material changed");
+        assert!(!channel.reload().await.expect("reload"), "nothing new");
+        check(&mut c).await;
+        assert_eq!(last_peer_cert(&mock), der(&tls_fixture("client-b.pem")));
+
+        hold.notify_one();
+        assert_eq!(
+            stream.recv().await.unwrap().unwrap().ts.0,
+            "hb-2",
+            "the stream opened before the reload survives it"
+        );
+        assert!(stream.recv().await.unwrap().is_none());
+
+        mock.lock().resolve_response = Some(session_outcome("p-1", 3600));
+        let resolver = GrpcSessionResolver::from_transport(
+            nio_client::transport(channel),
+            ResolverConfig::default(),
+        );
+        let session = resolver.resolve("deadbeef").await.expect("resolve");
+        assert!(session.is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_reloads_keep_the_current_channel() {
+        let (mock, uri) = start_tls_mock().await;
+        let current = Arc::new(Mutex::new(Ok(material("client-a"))));
+        let channel = ReloadableChannel::connect(uri, callback(current.clone()), MANUAL)
+            .await
+            .expect("connect");
+        let mut c = CheckClient::from_transport(nio_client::transport(channel.clone()));
+
+        *current.lock().unwrap() = Err("secret store down".into());
+        assert!(matches!(channel.reload().await, Err(TlsError::Source(_))));
+        let untrusted = TlsMaterial {
+            ca: Some(tls_fixture("client-b.pem")),
+            ..material("client-b")
+        };
+        *current.lock().unwrap() = Ok(untrusted);
+        assert!(matches!(channel.reload().await, Err(TlsError::Connect(_))));
+
+        check(&mut c).await;
+        assert_eq!(last_peer_cert(&mock), der(&tls_fixture("client-a.pem")));
+    }
+
+    #[tokio::test]
+    async fn polling_picks_up_new_material() {
+        let (mock, uri) = start_tls_mock().await;
+        let current = Arc::new(Mutex::new(Ok(material("client-a"))));
+        let cfg = TlsReloadConfig {
+            poll_interval: Duration::from_millis(20),
+        };
+        let channel = ReloadableChannel::connect(uri, callback(current.clone()), cfg)
+            .await
+            .expect("connect");
+        let mut c = CheckClient::from_transport(nio_client::transport(channel));
+
+        *current.lock().unwrap() = Ok(material("client-b"));
+        let want = der(&tls_fixture("client-b.pem"));
+        for _ in 0..250 {
+            check(&mut c).await;
+            if last_peer_cert(&mock) == want {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("the poller never switched to client-b");
+    }
+
+    #[tokio::test]
+    async fn builder_tls_paths_reload_when_an_interval_is_set() {
+        use nio_client::builder::{NioClientBuilder, NioClientConfig, TlsConfig};
+
+        let (mock, uri) = start_tls_mock().await;
+        let dir = std::env::temp_dir().join(format!("nio-tls-builder-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ca.crt"), tls_fixture("ca.pem")).unwrap();
+        std::fs::write(dir.join("tls.crt"), tls_fixture("client-a.pem")).unwrap();
+        std::fs::write(dir.join("tls.key"), tls_fixture("client-a.key")).unwrap();
+        let config = NioClientConfig {
+            check: Some(uri.into()),
+            tls: Some(TlsConfig {
+                ca: Some(dir.join("ca.crt")),
+                cert: Some(dir.join("tls.crt")),
+                key: Some(dir.join("tls.key")),
+                reload_interval: Some(Duration::from_millis(20)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut c = NioClientBuilder::from_config(config)
+            .build_check_client()
+            .await
+            .expect("build");
+        check(&mut c).await;
+        assert_eq!(last_peer_cert(&mock), der(&tls_fixture("client-a.pem")));
+
+        std::fs::write(dir.join("tls.crt"), tls_fixture("client-b.pem")).unwrap();
+        std::fs::write(dir.join("tls.key"), tls_fixture("client-b.key")).unwrap();
+        let want = der(&tls_fixture("client-b.pem"));
+        for _ in 0..250 {
+            check(&mut c).await;
+            if last_peer_cert(&mock) == want {
+                std::fs::remove_dir_all(dir).unwrap();
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("the builder's client never switched to client-b");
+    }
+}
+
+mod builder {
+    use super::*;
+    use nio_client::builder::{BuildError, NioClientBuilder, NioClientConfig};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn builds_both_clients_from_one_config() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().check_response = Some(wire::CheckResponse {
+            principal: Some(wire::Principal { id: "p-1".into() }),
+            ok: true,
+        });
+        mock.lock().resolve_response = Some(session_outcome("p-1", 3600));
+        let events = Arc::new(Events::default());
+        let config = NioClientConfig {
+            check: Some(uri.clone().into()),
+            session: Some(uri.into()),
+            request_timeout: Some(Duration::from_secs(5)),
+            gzip: true,
+            user_agent: Some("billing/1.2".into()),
+            ..Default::default()
+        };
+        let builder = NioClientBuilder::from_config(config).with_rpc_observer(events.clone());
+
+        let mut c = builder.build_check_client().await.expect("check client");
+        let res = c
+            .check(
+                Namespace("doc".into()),
+                Obj("1".into()),
+                Rel::viewer(),
+                UserId("u1".into()),
+                None,
+            )
+            .await
+            .expect("check");
+        assert!(matches!(res, CheckResult::Ok(_)));
+        let metadata = mock.lock().check_metadata[0].clone();
+        let header = |name| {
+            metadata
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        assert!(header("user-agent").unwrap().starts_with("billing/1.2 "));
+        assert_eq!(header("grpc-encoding").as_deref(), Some("gzip"));
+        assert_eq!(events.0.lock().unwrap()[0].method, RpcMethod::Check);
+
+        let resolver = builder.build_session_resolver().await.expect("resolver");
+        let session = resolver.resolve("deadbeef").await.expect("resolve");
+        assert_eq!(session.expect("session").principal, "p-1");
+    }
+
+    #[tokio::test]
+    async fn max_message_size_rejects_bigger_responses() {
+        let (mock, uri) = start_mock().await;
+        mock.lock().list_response = Some(wire::ListResponse {
+            objs: (0..100).map(|i| format!("obj-{i}")).collect(),
+            ..Default::default()
+        });
+        let mut c = NioClientBuilder::new()
+            .with_check(uri)
+            .with_max_message_size(64)
+            .build_check_client()
+            .await
+            .expect("check client");
+        let err = c
+            .list(
+                Namespace("doc".into()),
+                Rel::viewer(),
+                UserId("u1".into()),
+                None,
+            )
+            .await
+            .expect_err("response over the limit");
+        assert!(matches!(err, CallError::Status(s) if s.code() == tonic::Code::OutOfRange));
+    }
+
+    #[tokio::test]
+    async fn missing_endpoints_and_half_tls_configs_are_errors() {
+        let builder = NioClientBuilder::new();
+        assert!(matches!(
+            builder.build_session_resolver().await,
+            Err(BuildError::MissingTarget("session"))
+        ));
+        let config = NioClientConfig {
+            check: Some("http://localhost:1".parse().unwrap()),
+            tls: Some(nio_client::builder::TlsConfig {
+                cert: Some("/certs/tls.crt".into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let err = NioClientBuilder::from_config(config)
+            .build_check_client()
+            .await
+            .expect_err("cert without key");
+        assert!(matches!(err, BuildError::InvalidTls(_)), "{err:?}");
+        assert_eq!(err.kind(), nio_client::ErrorKind::InvalidArgument);
+        assert!(!err.is_retryable());
+
+        let err = NioClientBuilder::new()
+            .with_check("http://localhost:1".parse::<Uri>().unwrap())
+            .with_tls_reload(nio_client::tls::TlsReloadConfig::default())
+            .build_check_client()
+            .await
+            .expect_err("reload without TLS files");
+        assert!(matches!(err, BuildError::InvalidTls(_)), "{err:?}");
+    }
+}
+
+#[cfg(feature = "blocking")]
+mod blocking {
+    use super::*;
+
+    /// Serves the mock from its own runtime so the test thread stays
+    /// synchronous.
+    fn start_mock_in_background() -> (tokio::runtime::Runtime, Mock, Uri) {
+        let runtime = tokio::runtime::Runtime::new().expect("runtime");
+        let (mock, uri) = runtime.block_on(start_mock());
+        (runtime, mock, uri)
+    }
+
+    #[test]
+    fn synchronous_callers_check_read_and_write() {
+        let (_server, mock, uri) = start_mock_in_background();
+        mock.lock().check_response = Some(wire::CheckResponse {
+            principal: Some(wire::Principal { id: "p-1".into() }),
+            ok: true,
+        });
+        mock.lock().write_response = Some(wire::WriteResponse {
+            ts: "commit-1".into(),
+        });
+        mock.lock().namespaces = vec![wire::NamespaceMeta {
+            name: "doc".into(),
+            ..Default::default()
+        }];
+        let client = nio_client::blocking::CheckClient::create(uri).expect("connect");
+
+        let res = client
+            .check(
+                Namespace("doc".into()),
+                Obj("1".into()),
+                Rel::viewer(),
+                UserId("u1".into()),
+                None,
+            )
+            .expect("check");
+        assert!(matches!(res, CheckResult::Ok(p) if p.as_str() == "p-1"));
+
+        let tuple = Tuple::new(
+            Namespace("doc".into()),
+            Obj("1".into()),
+            Rel::viewer(),
+            User::UserId("u2".into()),
+        );
+        let ts = client.clone().add_one(tuple).expect("write");
+        assert_eq!(ts.0, "commit-1");
+        client
+            .get_all(&Namespace("doc".into()), &Obj("1".into()))
+            .expect("read");
+        let namespaces = client.list_namespaces().expect("namespaces");
+        assert_eq!(namespaces[0].name, "doc");
+
+        let state = mock.lock();
+        assert_eq!(state.check_requests.len(), 1);
+        assert_eq!(state.write_requests.len(), 1);
+        assert_eq!(state.read_requests.len(), 1);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "inside an async runtime")]
+    async fn calling_from_async_code_panics() {
+        let _ = nio_client::blocking::CheckClient::create(Uri::from_static("http://127.0.0.1:1"));
+    }
 }
@@ -0,0 +1,20 @@
+#!/bin/sh
+# Regenerates the TLS fixtures used by tests/mock.rs: a CA, a server
+# certificate for localhost and two client certificates (a and b) to rotate
+# between.
+set -e
+cd "$(dirname "$0")"
+days=36500
+openssl req -x509 -newkey rsa:2048 -nodes -days $days -subj "/CN=nio test ca" \
+    -keyout ca.key -out ca.pem
+issue() {
+    openssl req -newkey rsa:2048 -nodes -subj "/CN=$1" -keyout "$1.key" -out "$1.csr"
+    printf '%s\n' "$2" > "$1.ext"
+    openssl x509 -req -in "$1.csr" -CA ca.pem -CAkey ca.key -CAcreateserial -days $days \
+        -extfile "$1.ext" -out "$1.pem"
+    rm "$1.csr" "$1.ext"
+}
+issue server "subjectAltName=DNS:localhost,IP:127.0.0.1"
+issue client-a "extendedKeyUsage=clientAuth"
+issue client-b "extendedKeyUsage=clientAuth"
+rm -f ca.key ca.srl